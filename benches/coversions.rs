@@ -2,7 +2,6 @@
 
 extern crate test;
 
-#[macro_use]
 extern crate mrusty;
 
 use test::Bencher;
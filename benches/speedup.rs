@@ -7,7 +7,7 @@ extern crate mrusty;
 
 use test::Bencher;
 
-use mrusty::{Mruby, MrubyImpl};
+use mrusty::{Mruby, MrubyImpl, MrubyVm, MrInt};
 
 #[bench]
 fn fib_rust(b: &mut Bencher) {
@@ -67,3 +67,85 @@ fn fib_ruby(b: &mut Bencher) {
         mruby.run("fib 20").unwrap()
     });
 }
+
+#[bench]
+fn intern_uncached(b: &mut Bencher) {
+    let vm = MrubyVm::new();
+
+    b.iter(|| {
+        for _ in 0..1_000_000 {
+            test::black_box(vm.intern(test::black_box("succ")));
+        }
+    });
+}
+
+#[bench]
+fn intern_cached(b: &mut Bencher) {
+    let mruby = Mruby::new();
+
+    b.iter(|| {
+        for _ in 0..1_000_000 {
+            test::black_box(mruby.intern(test::black_box("succ")));
+        }
+    });
+}
+
+#[bench]
+fn call_by_name(b: &mut Bencher) {
+    let mruby = Mruby::new();
+    let one = mruby.fixnum(1);
+
+    b.iter(|| {
+        for _ in 0..1000 {
+            test::black_box(one.call("succ", vec![]).unwrap());
+        }
+    });
+}
+
+#[bench]
+fn call_by_sym(b: &mut Bencher) {
+    let mruby = Mruby::new();
+    let one = mruby.fixnum(1);
+    let succ = mruby.intern("succ");
+
+    b.iter(|| {
+        for _ in 0..1000 {
+            test::black_box(one.call_sym(&succ, vec![]).unwrap());
+        }
+    });
+}
+
+#[bench]
+fn new_by_class_cache(b: &mut Bencher) {
+    let mruby = Mruby::new();
+
+    mruby.run("class Point; end").unwrap();
+
+    b.iter(|| {
+        for _ in 0..1000 {
+            let _guard = unsafe { mruby.arena_guard() };
+            let class = mruby.get_class("Point").unwrap();
+
+            test::black_box(class.to_value().call("new", vec![]).unwrap());
+        }
+    });
+}
+
+#[bench]
+fn new_by_class_lookup(b: &mut Bencher) {
+    let mruby = Mruby::new();
+
+    mruby.run("class Point; end").unwrap();
+
+    b.iter(|| {
+        for _ in 0..1000 {
+            let _guard = unsafe { mruby.arena_guard() };
+
+            mruby.clear_class_cache();
+
+            let class = mruby.get_class("Point").unwrap();
+
+            test::black_box(class.to_value().call("new", vec![]).unwrap());
+        }
+    });
+}
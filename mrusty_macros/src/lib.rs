@@ -0,0 +1,128 @@
+// mrusty. mruby safe bindings for Rust
+// Copyright (C) 2016  Dragoș Tiselice
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Procedural macros for mrusty. Use them through mrusty's `macros` feature.
+
+extern crate proc_macro;
+extern crate proc_macro2;
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{FnArg, Ident, ItemFn, Pat, ReturnType, Type};
+
+/// Generates an `extern "C" fn <name>_mrb(*const MrState, MrValue) -> MrValue` next to the
+/// annotated function so that it can be passed to `mrb_define_method` and friends.
+///
+/// Arguments are read with `FromMrValue` and the result is returned with `IntoMrValue`. Trailing
+/// `Option<T>` arguments are optional and become `None` when omitted or `nil`. A wrong number of
+/// arguments or a failed cast raises an `ArgumentError` in mruby and a panic is rescued like in
+/// `mrfn!`. The `self` of the method is ignored.
+///
+/// # Examples
+///
+/// ```ignore
+/// #[mruby_method]
+/// fn add(x: i32, y: Option<i32>) -> i32 {
+///     x + y.unwrap_or(1)
+/// }
+///
+/// // add_mrb: extern "C" fn(*const MrState, MrValue) -> MrValue
+/// ```
+#[proc_macro_attribute]
+pub fn mruby_method(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return syn::Error::new(Span::call_site(), "mruby_method does not take arguments")
+            .to_compile_error().into();
+    }
+
+    let function = syn::parse_macro_input!(item as ItemFn);
+
+    match expand(&function) {
+        Ok(tokens) => tokens.into(),
+        Err(err)   => err.to_compile_error().into()
+    }
+}
+
+fn expand(function: &ItemFn) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &function.sig.ident;
+    let vis = &function.vis;
+    let wrapper = Ident::new(&format!("{}_mrb", name), name.span());
+
+    let mut types = Vec::new();
+
+    for input in &function.sig.inputs {
+        match *input {
+            FnArg::Typed(ref arg) => {
+                match *arg.pat {
+                    Pat::Ident(_) => types.push(&*arg.ty),
+                    ref pat => {
+                        return Err(syn::Error::new_spanned(pat, "expected a plain argument name"));
+                    }
+                }
+            },
+            FnArg::Receiver(ref receiver) => {
+                return Err(syn::Error::new_spanned(receiver, "mruby_method cannot take self"));
+            }
+        }
+    }
+
+    let total = types.len();
+    let required = types.iter().rposition(|ty| !is_option(ty)).map_or(0, |i| i + 1);
+
+    let args: Vec<_> = (0..total).map(|i| Ident::new(&format!("arg{}", i), Span::call_site()))
+        .collect();
+    let indices = 0..total;
+
+    let call = match function.sig.output {
+        ReturnType::Default => quote! {
+            #name(#( #args ),*);
+
+            Ok(::mrusty::MrValue::nil())
+        },
+        ReturnType::Type(..) => quote! {
+            Ok(::mrusty::IntoMrValue::into_mrvalue(#name(#( #args ),*), mrb))
+        }
+    };
+
+    Ok(quote! {
+        #function
+
+        #[allow(non_snake_case)]
+        #vis extern "C" fn #wrapper(mrb: *const ::mrusty::MrState,
+                                    _slf: ::mrusty::MrValue) -> ::mrusty::MrValue {
+            unsafe {
+                ::mrusty::MrValue::catch_panic(mrb, || {
+                    let result = (|| -> ::std::result::Result<::mrusty::MrValue,
+                                                              ::mrusty::MrubyError> {
+                        let args = ::mrusty::MrValue::method_args(mrb, #required, #total)?;
+
+                        #(
+                            let #args = <#types as ::mrusty::FromMrValue>::from_mrvalue(
+                                mrb, args[#indices])?;
+                        )*
+
+                        #call
+                    })();
+
+                    ::mrusty::MrValue::method_return(mrb, result)
+                })
+            }
+        }
+    })
+}
+
+fn is_option(ty: &Type) -> bool {
+    match *ty {
+        Type::Path(ref path) if path.qself.is_none() => {
+            path.path.segments.last().is_some_and(|segment| segment.ident == "Option")
+        },
+        _ => false
+    }
+}
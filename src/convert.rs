@@ -0,0 +1,527 @@
+// mrusty. mruby safe bindings for Rust
+// Copyright (C) 2016  Dragoș Tiselice
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::mruby::{MrubyError, MrubyImpl, MrubyType, Value};
+use super::mruby_ffi::MrType;
+
+/// A `trait` used to cast mruby `Value`s to Rust types generically.
+///
+/// `bool`, `i32`, `i64`, `f64`, `String` and `Vec<Value>` also implement `TryFrom<Value>` with
+/// their `FromValue` casts, so `value.try_into()?` works as well.
+///
+/// # Examples
+///
+/// ```
+/// # use mrusty::Mruby;
+/// # use mrusty::MrubyImpl;
+/// use mrusty::FromValue;
+///
+/// let mruby = Mruby::new();
+/// let result = mruby.run("'hi'").unwrap();
+///
+/// assert_eq!(String::from_value(result).unwrap(), "hi");
+/// ```
+///
+/// ```
+/// # use mrusty::Mruby;
+/// # use mrusty::MrubyImpl;
+/// use std::convert::TryInto;
+///
+/// let mruby = Mruby::new();
+/// let result = mruby.run("[1, 2]").unwrap();
+///
+/// let values: Vec<_> = result.try_into().unwrap();
+/// let first: i32 = values[0].clone().try_into().unwrap();
+///
+/// assert_eq!(first, 1);
+/// ```
+pub trait FromValue: Sized {
+    fn from_value(value: Value) -> Result<Self, MrubyError>;
+}
+
+impl FromValue for Value {
+    #[inline]
+    fn from_value(value: Value) -> Result<Value, MrubyError> {
+        Ok(value)
+    }
+}
+
+impl FromValue for bool {
+    #[inline]
+    fn from_value(value: Value) -> Result<bool, MrubyError> {
+        value.to_bool()
+    }
+}
+
+impl FromValue for i32 {
+    #[inline]
+    fn from_value(value: Value) -> Result<i32, MrubyError> {
+        value.to_i32()
+    }
+}
+
+impl FromValue for i64 {
+    #[inline]
+    fn from_value(value: Value) -> Result<i64, MrubyError> {
+        value.to_i64()
+    }
+}
+
+impl FromValue for f64 {
+    #[inline]
+    fn from_value(value: Value) -> Result<f64, MrubyError> {
+        value.to_f64()
+    }
+}
+
+impl FromValue for String {
+    #[inline]
+    fn from_value(value: Value) -> Result<String, MrubyError> {
+        value.to_str()
+    }
+}
+
+impl FromValue for Duration {
+    #[inline]
+    fn from_value(value: Value) -> Result<Duration, MrubyError> {
+        value.to_duration()
+    }
+}
+
+impl FromValue for PathBuf {
+    #[inline]
+    fn from_value(value: Value) -> Result<PathBuf, MrubyError> {
+        value.to_path()
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    #[inline]
+    fn from_value(value: Value) -> Result<Option<T>, MrubyError> {
+        if value.is_nil() {
+            Ok(None)
+        } else {
+            T::from_value(value).map(Some)
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    #[inline]
+    fn from_value(value: Value) -> Result<Vec<T>, MrubyError> {
+        value.to_vec()?.into_iter().enumerate().map(|(i, value)| {
+            T::from_value(value).map_err(|err| at_path(err, i.to_string()))
+        }).collect()
+    }
+}
+
+impl<K: FromValue + Eq + Hash, V: FromValue> FromValue for HashMap<K, V> {
+    fn from_value(value: Value) -> Result<HashMap<K, V>, MrubyError> {
+        let pairs = value.to_hash()?;
+        let mut map = HashMap::with_capacity(pairs.len());
+
+        map_from_pairs(pairs, |key, value| map.insert(key, value).is_none())?;
+
+        Ok(map)
+    }
+}
+
+impl<K: FromValue + Ord, V: FromValue> FromValue for BTreeMap<K, V> {
+    #[inline]
+    fn from_value(value: Value) -> Result<BTreeMap<K, V>, MrubyError> {
+        let mut map = BTreeMap::new();
+
+        map_from_pairs(value.to_hash()?, |key, value| map.insert(key, value).is_none())?;
+
+        Ok(map)
+    }
+}
+
+/// A `trait` used to cast Rust types to mruby `Value`s generically.
+///
+/// # Examples
+///
+/// ```
+/// # use mrusty::Mruby;
+/// # use mrusty::MrubyImpl;
+/// use mrusty::ToValue;
+///
+/// let mruby = Mruby::new();
+/// let value = vec![Some(1), None].to_value(&mruby);
+///
+/// assert_eq!(value.to_vec().unwrap(), vec![mruby.fixnum(1), mruby.nil()]);
+/// ```
+pub trait ToValue {
+    fn to_value(self, mruby: &MrubyType) -> Value;
+}
+
+impl ToValue for Value {
+    #[inline]
+    fn to_value(self, _mruby: &MrubyType) -> Value {
+        self
+    }
+}
+
+impl ToValue for bool {
+    #[inline]
+    fn to_value(self, mruby: &MrubyType) -> Value {
+        mruby.bool(self)
+    }
+}
+
+impl ToValue for i32 {
+    #[inline]
+    fn to_value(self, mruby: &MrubyType) -> Value {
+        mruby.fixnum(self)
+    }
+}
+
+impl ToValue for i64 {
+    #[inline]
+    fn to_value(self, mruby: &MrubyType) -> Value {
+        mruby.fixnum_i64(self)
+    }
+}
+
+impl ToValue for f64 {
+    #[inline]
+    fn to_value(self, mruby: &MrubyType) -> Value {
+        mruby.float(self)
+    }
+}
+
+impl ToValue for String {
+    #[inline]
+    fn to_value(self, mruby: &MrubyType) -> Value {
+        mruby.string(&self)
+    }
+}
+
+impl ToValue for &str {
+    #[inline]
+    fn to_value(self, mruby: &MrubyType) -> Value {
+        mruby.string(self)
+    }
+}
+
+impl ToValue for Duration {
+    #[inline]
+    fn to_value(self, mruby: &MrubyType) -> Value {
+        mruby.float(self.as_secs_f64())
+    }
+}
+
+impl ToValue for PathBuf {
+    #[inline]
+    fn to_value(self, mruby: &MrubyType) -> Value {
+        mruby.path(&self)
+    }
+}
+
+impl ToValue for &Path {
+    #[inline]
+    fn to_value(self, mruby: &MrubyType) -> Value {
+        mruby.path(self)
+    }
+}
+
+impl<T: ToValue> ToValue for Option<T> {
+    #[inline]
+    fn to_value(self, mruby: &MrubyType) -> Value {
+        match self {
+            Some(value) => value.to_value(mruby),
+            None        => mruby.nil()
+        }
+    }
+}
+
+impl<T: ToValue> ToValue for Vec<T> {
+    #[inline]
+    fn to_value(self, mruby: &MrubyType) -> Value {
+        let values = self.into_iter().map(|value| value.to_value(mruby)).collect();
+
+        mruby.array(values)
+    }
+}
+
+impl<K: ToValue + Eq + Hash, V: ToValue> ToValue for HashMap<K, V> {
+    #[inline]
+    fn to_value(self, mruby: &MrubyType) -> Value {
+        let pairs = self.into_iter().map(|(key, value)| {
+            (key.to_value(mruby), value.to_value(mruby))
+        }).collect();
+
+        mruby.hash(pairs)
+    }
+}
+
+impl<K: ToValue + Ord, V: ToValue> ToValue for BTreeMap<K, V> {
+    #[inline]
+    fn to_value(self, mruby: &MrubyType) -> Value {
+        mruby.hash_from_pairs(self.into_iter().map(|(key, value)| {
+            (key.to_value(mruby), value.to_value(mruby))
+        }))
+    }
+}
+
+/// A `trait` mapping a field-less Rust `enum` to mruby `Symbol`s, usually implemented with
+/// `symbol_enum!`. Implementors get `ToValue`, which creates the variant's `Symbol`, and
+/// `FromValue`, which accepts both a `Symbol` and a `String` and lists the allowed names in its
+/// cast error otherwise.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate mrusty;
+/// # use mrusty::{Mruby, MrubyImpl};
+/// use mrusty::{FromValue, SymbolEnum, ToValue};
+///
+/// # fn main() {
+/// #[derive(Debug, PartialEq)]
+/// enum Mode {
+///     Fast,
+///     Safe
+/// }
+///
+/// symbol_enum!(Mode {
+///     Fast => "fast",
+///     Safe => "safe"
+/// });
+///
+/// let mruby = Mruby::new();
+///
+/// assert_eq!(Mode::from_value(mruby.run(":safe").unwrap()).unwrap(), Mode::Safe);
+/// assert_eq!(Mode::from_value(mruby.string("fast")).unwrap(), Mode::Fast);
+/// assert_eq!(Mode::Fast.to_value(&mruby).to_s(), "fast");
+/// assert_eq!(Mode::symbol_names(), &["fast", "safe"]);
+/// # }
+/// ```
+pub trait SymbolEnum: Sized {
+    /// Returns the `Symbol` name of this variant.
+    fn to_symbol_name(&self) -> &'static str;
+
+    /// Returns the variant named `name` in a `Some` or `None` if there is none.
+    fn from_symbol_name(name: &str) -> Option<Self>;
+
+    /// Returns the `Symbol` names of all variants, in declaration order.
+    fn symbol_names() -> &'static [&'static str];
+}
+
+impl<T: SymbolEnum> FromValue for T {
+    fn from_value(value: Value) -> Result<T, MrubyError> {
+        let variant = match value.value.typ() {
+            MrType::MRB_TT_STRING | MrType::MRB_TT_SYMBOL => {
+                value.to_str().ok().and_then(|name| T::from_symbol_name(&name))
+            },
+            _ => None
+        };
+
+        variant.ok_or_else(|| {
+            let names: Vec<String> = T::symbol_names().iter().map(|name| {
+                format!(":{}", name)
+            }).collect();
+
+            MrubyError::Cast(format!("one of {}, found {}", names.join(", "), value.inspect()))
+        })
+    }
+}
+
+impl<T: SymbolEnum> ToValue for T {
+    #[inline]
+    fn to_value(self, mruby: &MrubyType) -> Value {
+        mruby.symbol(self.to_symbol_name())
+    }
+}
+
+/// A `trait` that maps the bits of a flag set, e.g. a `bitflags` type, to mruby `Symbol` names.
+/// It is usually implemented with `flag_set!` which also implements `FromValue` and `ToValue` with
+/// `from_flags_value` and `to_flags_value`.
+///
+/// A flag set is an mruby `Array` of `Symbol`s, e.g. `[:read, :write]`. Casting accepts both
+/// `Symbol`s and `String`s and ORs their bits together; unknown names are a cast error.
+///
+/// # Examples
+///
+/// ```
+/// # use mrusty::{Mruby, MrubyImpl};
+/// use mrusty::FlagSet;
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Perms(u8);
+///
+/// impl FlagSet for Perms {
+///     fn to_flag_bits(&self) -> u64 { self.0 as u64 }
+///     fn from_flag_bits(bits: u64) -> Perms { Perms(bits as u8) }
+///     fn flag_names() -> &'static [(u64, &'static str)] { &[(1, "read"), (2, "write")] }
+/// }
+///
+/// let mruby = Mruby::new();
+/// let result = mruby.run("[:write, 'read']").unwrap();
+///
+/// assert_eq!(Perms::from_flags_value(result).unwrap(), Perms(3));
+/// assert_eq!(Perms(2).to_flags_value(&mruby).to_s(), "[:write]");
+/// ```
+pub trait FlagSet: Sized {
+    /// Returns the bits of this set.
+    fn to_flag_bits(&self) -> u64;
+
+    /// Returns the set made of `bits`.
+    fn from_flag_bits(bits: u64) -> Self;
+
+    /// Returns the bits and `Symbol` name of every flag, in declaration order.
+    fn flag_names() -> &'static [(u64, &'static str)];
+
+    /// Casts an mruby `Array` of `Symbol`s or `String`s to a set by OR-ing the flags' bits.
+    fn from_flags_value(value: Value) -> Result<Self, MrubyError> {
+        let mut bits = 0;
+
+        for (i, name) in value.to_vec()?.into_iter().enumerate() {
+            let flag = match name.value.typ() {
+                MrType::MRB_TT_STRING | MrType::MRB_TT_SYMBOL => {
+                    name.to_str().ok().and_then(|name| {
+                        Self::flag_names().iter().find(|&&(_, flag)| flag == name)
+                    })
+                },
+                _ => None
+            };
+
+            match flag {
+                Some(&(flag, _)) => bits |= flag,
+                None             => {
+                    let names: Vec<String> = Self::flag_names().iter().map(|&(_, name)| {
+                        format!(":{}", name)
+                    }).collect();
+
+                    let err = MrubyError::Cast(format!("one of {}, found {}", names.join(", "),
+                                                       name.inspect()));
+
+                    return Err(at_path(err, i.to_string()));
+                }
+            }
+        }
+
+        Ok(Self::from_flag_bits(bits))
+    }
+
+    /// Converts the set to an mruby `Array` of the `Symbol`s of all flags it contains.
+    fn to_flags_value(&self, mruby: &MrubyType) -> Value {
+        let bits = self.to_flag_bits();
+
+        let symbols = Self::flag_names().iter().filter(|&&(flag, _)| {
+            flag != 0 && bits & flag == flag
+        }).map(|&(_, name)| mruby.symbol(name)).collect();
+
+        mruby.array(symbols)
+    }
+}
+
+// Casts every pair and hands it to `insert`, which returns `false` when the key was already
+// there, i.e. when two mruby keys end up equal once cast.
+fn map_from_pairs<K, V, F>(pairs: Vec<(Value, Value)>, mut insert: F) -> Result<(), MrubyError>
+    where K: FromValue, V: FromValue, F: FnMut(K, V) -> bool {
+
+    for (key, value) in pairs {
+        let name = key.inspect();
+
+        let key = K::from_value(key).map_err(|err| match err {
+            MrubyError::Cast(expected) => {
+                MrubyError::Cast(format!("{} for Hash key {}", expected, name))
+            },
+            err => err
+        })?;
+        let value = V::from_value(value).map_err(|err| at_path(err, name.clone()))?;
+
+        if !insert(key, value) {
+            return Err(MrubyError::Cast(format!("unique Hash keys, found duplicate {}", name)));
+        }
+    }
+
+    Ok(())
+}
+
+// Prepends `segment` to the path of cast errors, e.g. `Fixnum at [0]` becomes `Fixnum at [1][0]`,
+// so that nested failures point at the exact element.
+#[inline]
+fn at_path(err: MrubyError, segment: String) -> MrubyError {
+    match err {
+        MrubyError::Nested { mut path, error } => {
+            path.insert(0, segment);
+
+            MrubyError::Nested {
+                path,
+                error
+            }
+        },
+        err @ MrubyError::Cast(_) | err @ MrubyError::Overflow(_) => {
+            MrubyError::Nested {
+                path:  vec![segment],
+                error: Box::new(err)
+            }
+        },
+        err => err
+    }
+}
+
+macro_rules! tuple {
+    ( $len:expr; $( $index:tt: $name:ident ),+ ) => {
+        impl<$( $name: FromValue ),+> FromValue for ( $( $name, )+ ) {
+            #[inline]
+            fn from_value(value: Value) -> Result<( $( $name, )+ ), MrubyError> {
+                let values = value.to_vec()?;
+
+                if values.len() != $len {
+                    return Err(MrubyError::Cast(format!("{} elements, found {}", $len,
+                                                        values.len())));
+                }
+
+                let mut values = values.into_iter();
+
+                Ok(( $(
+                    $name::from_value(values.next().unwrap())
+                        .map_err(|err| at_path(err, stringify!($index).to_owned()))?,
+                )+ ))
+            }
+        }
+
+        impl<$( $name: ToValue ),+> ToValue for ( $( $name, )+ ) {
+            #[inline]
+            fn to_value(self, mruby: &MrubyType) -> Value {
+                mruby.array(vec![ $( self.$index.to_value(mruby) ),+ ])
+            }
+        }
+    }
+}
+
+tuple!(1; 0: A);
+tuple!(2; 0: A, 1: B);
+tuple!(3; 0: A, 1: B, 2: C);
+tuple!(4; 0: A, 1: B, 2: C, 3: D);
+tuple!(5; 0: A, 1: B, 2: C, 3: D, 4: E);
+tuple!(6; 0: A, 1: B, 2: C, 3: D, 4: E, 5: F);
+tuple!(7; 0: A, 1: B, 2: C, 3: D, 4: E, 5: F, 6: G);
+tuple!(8; 0: A, 1: B, 2: C, 3: D, 4: E, 5: F, 6: G, 7: H);
+
+macro_rules! try_from {
+    ( $( $typ:ty ),+ ) => { $(
+        impl TryFrom<Value> for $typ {
+            type Error = MrubyError;
+
+            #[inline]
+            fn try_from(value: Value) -> Result<$typ, MrubyError> {
+                <$typ as FromValue>::from_value(value)
+            }
+        }
+    )+ }
+}
+
+try_from!(bool, i32, i64, f64, String, Vec<Value>);
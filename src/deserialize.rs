@@ -0,0 +1,404 @@
+// mrusty. mruby safe bindings for Rust
+// Copyright (C) 2016  Dragoș Tiselice
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::fmt;
+
+use serde::ser::{self, Serialize};
+
+use super::mruby::MrubyError;
+use super::mruby_ffi::{MrState, MrValue};
+
+impl ser::Error for MrubyError {
+    fn custom<T: fmt::Display>(msg: T) -> MrubyError {
+        MrubyError::runtime(msg.to_string())
+    }
+}
+
+impl MrValue {
+    /// Creates an `MrValue` from any serde-serializable Rust value with `MrValueDeserializer`.
+    ///
+    /// Only available with the `deserializer` feature.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state.
+    #[inline]
+    pub unsafe fn from_serde<T: Serialize + ?Sized>(mrb: *const MrState,
+                                                    value: &T) -> Result<MrValue, MrubyError> {
+        value.serialize(MrValueDeserializer::new(mrb))
+    }
+}
+
+/// A `struct` that turns serde-serializable Rust values into `MrValue`s, the inverse of
+/// `MrValueSerializer`. It plugs into serde as a `Serializer`. Structs and maps become `Hash`es
+/// (with `Symbol` keys for struct fields), sequences and tuples become `Array`s, `None` and `()`
+/// become `nil` and unit enum variants become `Symbol`s. Other enum variants become a
+/// single-entry `Hash` from the variant `Symbol` to their content.
+///
+/// Only available with the `deserializer` feature.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate mrusty;
+/// # use mrusty::Mruby;
+/// # use mrusty::MrubyImpl;
+/// use mrusty::{MrValue, Value};
+///
+/// # fn main() {
+/// let mruby = Mruby::new();
+///
+/// let value = unsafe {
+///     let mrb = mruby.borrow().mrb;
+///
+///     Value::new(mruby.clone(), MrValue::from_serde(mrb, &(1, vec!["a", "b"], None::<i32>)).unwrap())
+/// };
+///
+/// assert_eq!(value.to_s(), "[1, [\"a\", \"b\"], nil]");
+/// # }
+/// ```
+#[derive(Clone, Copy)]
+pub struct MrValueDeserializer {
+    mrb: *const MrState
+}
+
+impl MrValueDeserializer {
+    /// Creates an `MrValueDeserializer` that allocates on `mrb`.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must stay open while the deserializer is in use.
+    #[inline]
+    pub unsafe fn new(mrb: *const MrState) -> MrValueDeserializer {
+        MrValueDeserializer {
+            mrb
+        }
+    }
+
+    #[inline]
+    fn variant(&self, variant: &str, value: MrValue) -> MrValue {
+        unsafe {
+            MrValue::hash(self.mrb, vec![(MrValue::symbol(self.mrb, variant), value)])
+        }
+    }
+
+    #[inline]
+    fn array(self, len: Option<usize>, variant: Option<&'static str>) -> ArrayBuilder {
+        ArrayBuilder {
+            deserializer: self,
+            values: Vec::with_capacity(len.unwrap_or(0)),
+            variant
+        }
+    }
+
+    #[inline]
+    fn hash(self, len: Option<usize>, variant: Option<&'static str>) -> HashBuilder {
+        HashBuilder {
+            deserializer: self,
+            pairs: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+            variant
+        }
+    }
+}
+
+#[doc(hidden)]
+pub struct ArrayBuilder {
+    deserializer: MrValueDeserializer,
+    values: Vec<MrValue>,
+    variant: Option<&'static str>
+}
+
+impl ArrayBuilder {
+    #[inline]
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), MrubyError> {
+        self.values.push(value.serialize(self.deserializer)?);
+
+        Ok(())
+    }
+
+    #[inline]
+    fn finish(self) -> Result<MrValue, MrubyError> {
+        let array = unsafe { MrValue::array(self.deserializer.mrb, self.values) };
+
+        match self.variant {
+            Some(variant) => Ok(self.deserializer.variant(variant, array)),
+            None          => Ok(array)
+        }
+    }
+}
+
+impl ser::SerializeSeq for ArrayBuilder {
+    type Ok = MrValue;
+    type Error = MrubyError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), MrubyError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<MrValue, MrubyError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for ArrayBuilder {
+    type Ok = MrValue;
+    type Error = MrubyError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), MrubyError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<MrValue, MrubyError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for ArrayBuilder {
+    type Ok = MrValue;
+    type Error = MrubyError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), MrubyError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<MrValue, MrubyError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for ArrayBuilder {
+    type Ok = MrValue;
+    type Error = MrubyError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), MrubyError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<MrValue, MrubyError> {
+        self.finish()
+    }
+}
+
+#[doc(hidden)]
+pub struct HashBuilder {
+    deserializer: MrValueDeserializer,
+    pairs: Vec<(MrValue, MrValue)>,
+    key: Option<MrValue>,
+    variant: Option<&'static str>
+}
+
+impl HashBuilder {
+    #[inline]
+    fn field<T: Serialize + ?Sized>(&mut self, key: &'static str,
+                                    value: &T) -> Result<(), MrubyError> {
+        let key = unsafe { MrValue::symbol(self.deserializer.mrb, key) };
+
+        self.pairs.push((key, value.serialize(self.deserializer)?));
+
+        Ok(())
+    }
+
+    #[inline]
+    fn finish(self) -> Result<MrValue, MrubyError> {
+        let hash = unsafe { MrValue::hash(self.deserializer.mrb, self.pairs) };
+
+        match self.variant {
+            Some(variant) => Ok(self.deserializer.variant(variant, hash)),
+            None          => Ok(hash)
+        }
+    }
+}
+
+impl ser::SerializeMap for HashBuilder {
+    type Ok = MrValue;
+    type Error = MrubyError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), MrubyError> {
+        self.key = Some(key.serialize(self.deserializer)?);
+
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), MrubyError> {
+        let key = self.key.take()
+            .ok_or_else(|| MrubyError::runtime("Hash value serialized before its key".to_owned()))?;
+
+        self.pairs.push((key, value.serialize(self.deserializer)?));
+
+        Ok(())
+    }
+
+    fn end(self) -> Result<MrValue, MrubyError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for HashBuilder {
+    type Ok = MrValue;
+    type Error = MrubyError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str,
+                                              value: &T) -> Result<(), MrubyError> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<MrValue, MrubyError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for HashBuilder {
+    type Ok = MrValue;
+    type Error = MrubyError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str,
+                                              value: &T) -> Result<(), MrubyError> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<MrValue, MrubyError> {
+        self.finish()
+    }
+}
+
+impl ser::Serializer for MrValueDeserializer {
+    type Ok = MrValue;
+    type Error = MrubyError;
+
+    type SerializeSeq = ArrayBuilder;
+    type SerializeTuple = ArrayBuilder;
+    type SerializeTupleStruct = ArrayBuilder;
+    type SerializeTupleVariant = ArrayBuilder;
+    type SerializeMap = HashBuilder;
+    type SerializeStruct = HashBuilder;
+    type SerializeStructVariant = HashBuilder;
+
+    fn serialize_bool(self, v: bool) -> Result<MrValue, MrubyError> {
+        unsafe { Ok(MrValue::bool(v)) }
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<MrValue, MrubyError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<MrValue, MrubyError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<MrValue, MrubyError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<MrValue, MrubyError> {
+        unsafe { Ok(MrValue::fixnum_i64(v)) }
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<MrValue, MrubyError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<MrValue, MrubyError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<MrValue, MrubyError> {
+        unsafe { MrValue::fixnum_u32(v) }
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<MrValue, MrubyError> {
+        unsafe { MrValue::fixnum_u64(v) }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<MrValue, MrubyError> {
+        unsafe { Ok(MrValue::float_f32(self.mrb, v)) }
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<MrValue, MrubyError> {
+        unsafe { Ok(MrValue::float(self.mrb, v)) }
+    }
+
+    fn serialize_char(self, v: char) -> Result<MrValue, MrubyError> {
+        let mut buf = [0; 4];
+
+        self.serialize_str(v.encode_utf8(&mut buf))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<MrValue, MrubyError> {
+        unsafe { Ok(MrValue::string(self.mrb, v)) }
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<MrValue, MrubyError> {
+        unsafe { Ok(MrValue::bytes(self.mrb, v)) }
+    }
+
+    fn serialize_none(self) -> Result<MrValue, MrubyError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<MrValue, MrubyError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<MrValue, MrubyError> {
+        unsafe { Ok(MrValue::nil()) }
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<MrValue, MrubyError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32,
+                              variant: &'static str) -> Result<MrValue, MrubyError> {
+        unsafe { Ok(MrValue::symbol(self.mrb, variant)) }
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str,
+                                                       value: &T) -> Result<MrValue, MrubyError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name: &'static str, _index: u32,
+                                                        variant: &'static str,
+                                                        value: &T) -> Result<MrValue, MrubyError> {
+        let value = value.serialize(self)?;
+
+        Ok(self.variant(variant, value))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<ArrayBuilder, MrubyError> {
+        Ok(self.array(len, None))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<ArrayBuilder, MrubyError> {
+        Ok(self.array(Some(len), None))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str,
+                              len: usize) -> Result<ArrayBuilder, MrubyError> {
+        Ok(self.array(Some(len), None))
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, variant: &'static str,
+                               len: usize) -> Result<ArrayBuilder, MrubyError> {
+        Ok(self.array(Some(len), Some(variant)))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<HashBuilder, MrubyError> {
+        Ok(self.hash(len, None))
+    }
+
+    fn serialize_struct(self, _name: &'static str,
+                        len: usize) -> Result<HashBuilder, MrubyError> {
+        Ok(self.hash(Some(len), None))
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, variant: &'static str,
+                                len: usize) -> Result<HashBuilder, MrubyError> {
+        Ok(self.hash(Some(len), Some(variant)))
+    }
+}
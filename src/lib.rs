@@ -15,37 +15,70 @@
 //! It does all this in a safely neat way, while also bringing spec testing and a
 //! REPL to the table.
 
+#[cfg(feature = "datetime")]
+extern crate chrono;
+#[cfg(feature = "macros")]
+extern crate mrusty_macros;
 #[cfg(feature = "gnu-readline")]
 extern crate rl_sys;
+#[cfg(feature = "serde")]
+extern crate serde;
 
+mod convert;
+#[cfg(feature = "deserializer")]
+mod deserialize;
 mod macros;
 mod mruby;
 mod mruby_ffi;
 mod read_line;
 mod repl;
+#[cfg(feature = "serde")]
+mod serialize;
 mod spec;
+#[cfg(feature = "time")]
+mod time;
 
 /// Not meant to be called directly.
 #[doc(hidden)]
-pub use mruby_ffi::{MrValue, MrDataType, MrInt, MrFloat};
+pub use mruby_ffi::{MrValue, MrValueDebug, MrArrayIter, MrHashIter, MrClass, MrDataType, MrFunc, MrInt, MrFloat, MrState};
+/// Not meant to be called directly.
+#[doc(hidden)]
+pub use mruby_ffi::free_arc;
 /// Not meant to be called directly.
 #[doc(hidden)]
 pub use mruby_ffi::mrb_get_args;
 #[doc(hidden)]
 pub use mruby_ffi::{mrb_ext_value_sizeof, mrb_ext_data_type_sizeof, mrb_ext_int_sizeof, mrb_ext_float_sizeof};
 
+pub use convert::{FlagSet, FromValue, SymbolEnum, ToValue};
+pub use mruby::ArenaGuard;
+pub use mruby::ArrayIter;
 pub use mruby::Class;
 pub use mruby::ClassLike;
+pub use mruby::CollectMruby;
+pub use mruby::HashedValue;
+pub use mruby::IntRange;
 pub use mruby::Module;
 pub use mruby::Mruby;
 pub use mruby::MrubyError;
 pub use mruby::MrubyFile;
 pub use mruby::MrubyImpl;
 pub use mruby::MrubyType;
+pub use mruby::RaiseError;
+pub use mruby::ReturnValue;
+pub use mruby::Symbol;
 pub use mruby::Value;
+pub use mruby_ffi::{FromMrValue, IntoMrValue};
+pub use mruby_ffi::{MrAllocf, MrubyClass, MrubyContext, MrubyModule, MrubyVm, MrubyVmBuilder};
 pub use read_line::ReadLine;
 pub use repl::Repl;
 pub use spec::Spec;
 
 #[cfg(feature = "gnu-readline")]
 pub use read_line::GnuReadLine;
+#[cfg(feature = "deserializer")]
+pub use deserialize::MrValueDeserializer;
+#[cfg(feature = "macros")]
+pub use mrusty_macros::mruby_method;
+#[cfg(feature = "serde")]
+pub use serialize::MrValueSerializer;
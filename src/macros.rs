@@ -16,8 +16,19 @@
 /// * `(Vec<Value>)` (`Vec<Value>`; macro limtation)
 /// * `(&T)` (defined with `def_class`; macro limtation)
 /// * `Value`
+/// * any other `FromValue` type, e.g. `String`, `i64` or `(Option<i32>)` (parenthesized if it is
+///   more than one token)
 ///
-/// Any `panic!` call within the closure will get rescued in a `RustPanic` mruby `Exception`.
+/// Any `panic!` call within the closure will get rescued in a `RustPanic` mruby `RuntimeError`.
+///
+/// Closures declared with a `-> Result<T, MrubyError>` return type, where `T` is any `ToValue`
+/// type, can use `?`. An `Err` is raised in mruby. (see `ReturnValue`)
+///
+/// Trailing arguments can be given a default with `name: Type = default` (parenthesized if it is
+/// more than one token) which is used when the argument is omitted. A required argument after one
+/// with a default and a second `= default` are rejected at compile time. A `*name: Vec<Value>`
+/// argument after them collects all remaining arguments and a last `&name: Value` binds the block,
+/// which is `nil` when none was given. (see `Value::yield_block`)
 ///
 /// # Examples
 ///
@@ -155,19 +166,55 @@
 /// assert_eq!(result.to_i32().unwrap(), 3);
 /// # }
 /// ```
+/// <br/>
+///
+/// Required arguments cannot follow ones with defaults.
+///
+/// ```compile_fail
+/// # #[macro_use] extern crate mrusty;
+/// use mrusty::{Mruby, MrubyImpl};
+///
+/// # fn main() {
+/// let mruby = Mruby::new();
+///
+/// struct Cont;
+///
+/// mruby.def_class_for::<Cont>("Container");
+/// mruby.def_method_for::<Cont, _>("add", mrfn!(|mruby, _slf: Value, a: i32 = 1, b: i32| {
+///     mruby.fixnum(a + b)
+/// }));
+/// # }
+/// ```
+///
+/// ```compile_fail
+/// # #[macro_use] extern crate mrusty;
+/// use mrusty::{Mruby, MrubyImpl};
+///
+/// # fn main() {
+/// let mruby = Mruby::new();
+///
+/// struct Cont;
+///
+/// mruby.def_class_for::<Cont>("Container");
+/// mruby.def_method_for::<Cont, _>("add", mrfn!(|mruby, _slf: Value, a: i32 = 1 = 2| {
+///     mruby.fixnum(a)
+/// }));
+/// # }
+/// ```
 #[macro_export]
 macro_rules! mrfn {
     // init
     ( @init ) => ();
-    ( @init $name:ident, bool )          => (let $name = ::std::mem::MaybeUninit::<bool>::zeroed().assume_init(););
-    ( @init $name:ident, i32 )           => (let $name = ::std::mem::MaybeUninit::<MrInt>::uninit().assume_init(););
-    ( @init $name:ident, f64 )           => (let $name = ::std::mem::MaybeUninit::<MrFloat>::uninit().assume_init(););
-    ( @init $name:ident, (&str) )        => (let $name = ::std::mem::MaybeUninit::<*const ::std::os::raw::c_char>::uninit().assume_init(););
-    ( @init $name:ident, (Vec<Value>) )  => (let $name = ::std::mem::MaybeUninit::<$crate::MrValue>::uninit().assume_init(););
-    ( @init $name:ident, Class )         => (let $name = ::std::mem::MaybeUninit::<$crate::MrValue>::uninit().assume_init(););
-    ( @init $name:ident, Value )         => (let $name = ::std::mem::MaybeUninit::<$crate::MrValue>::uninit().assume_init(););
-    ( @init $name:ident, (&mut $_t:ty) ) => (let $name = ::std::mem::MaybeUninit::<$crate::MrValue>::uninit().assume_init(););
-    ( @init $name:ident, (&$_t:ty) )     => (let $name = ::std::mem::MaybeUninit::<$crate::MrValue>::uninit().assume_init(););
+    ( @init $name:ident, bool )          => (let mut $name = false;);
+    ( @init $name:ident, i32 )           => (let mut $name: MrInt = 0;);
+    ( @init $name:ident, f64 )           => (let mut $name: MrFloat = 0.0;);
+    ( @init $name:ident, (&str) )        => (let mut $name: *const ::std::os::raw::c_char = ::std::ptr::null(););
+    ( @init $name:ident, (Vec<Value>) )  => (let mut $name = $crate::MrValue::nil(););
+    ( @init $name:ident, Class )         => (let mut $name = $crate::MrValue::nil(););
+    ( @init $name:ident, Value )         => (let mut $name = $crate::MrValue::nil(););
+    ( @init $name:ident, (&mut $_t:ty) ) => (let mut $name = $crate::MrValue::nil(););
+    ( @init $name:ident, (&$_t:ty) )     => (let mut $name = $crate::MrValue::nil(););
+    ( @init $name:ident, $_t:tt )        => (let mut $name = $crate::MrValue::nil(););
     ( @init $($names:ident : $ts:tt),+ ) => ($( mrfn!(@init $names, $ts); )*);
 
     // sig
@@ -181,19 +228,25 @@ macro_rules! mrfn {
     ( @sig Value )         => ("o");
     ( @sig (&mut $_t:ty) ) => ("o");
     ( @sig (&$_t:ty) )     => ("o");
+    ( @sig $_t:tt )        => ("o");
     ( @sig $($ts:tt),+ ) => (concat!( $( mrfn!(@sig $ts) ),* ));
 
+    // sig_arg
+    ( @sig_arg $t:tt )                => (mrfn!(@sig $t));
+    ( @sig_arg $t:tt = $_default:tt ) => (concat!("|", mrfn!(@sig $t)));
+
     // args
     ( @args )                            => ();
-    ( @args $name:ident, bool )          => (&$name as *const bool);
-    ( @args $name:ident, i32 )           => (&$name as *const MrInt);
-    ( @args $name:ident, f64 )           => (&$name as *const MrFloat);
-    ( @args $name:ident, (&str) )        => (&$name as *const *const ::std::os::raw::c_char);
-    ( @args $name:ident, (Vec<Value>) )  => (&$name as *const $crate::MrValue);
-    ( @args $name:ident, Class )         => (&$name as *const $crate::MrValue);
-    ( @args $name:ident, Value )         => (&$name as *const $crate::MrValue);
-    ( @args $name:ident, (&mut $_t:ty) ) => (&$name as *const $crate::MrValue);
-    ( @args $name:ident, (&$_t:ty) )     => (&$name as *const $crate::MrValue);
+    ( @args $name:ident, bool )          => (&mut $name as *mut bool);
+    ( @args $name:ident, i32 )           => (&mut $name as *mut MrInt);
+    ( @args $name:ident, f64 )           => (&mut $name as *mut MrFloat);
+    ( @args $name:ident, (&str) )        => (&mut $name as *mut *const ::std::os::raw::c_char);
+    ( @args $name:ident, (Vec<Value>) )  => (&mut $name as *mut $crate::MrValue);
+    ( @args $name:ident, Class )         => (&mut $name as *mut $crate::MrValue);
+    ( @args $name:ident, Value )         => (&mut $name as *mut $crate::MrValue);
+    ( @args $name:ident, (&mut $_t:ty) ) => (&mut $name as *mut $crate::MrValue);
+    ( @args $name:ident, (&$_t:ty) )     => (&mut $name as *mut $crate::MrValue);
+    ( @args $name:ident, $_t:tt )        => (&mut $name as *mut $crate::MrValue);
     ( @args $name:ident : $t:tt )        => (mrfn!(@args $name, $t));
     ( @args $mrb:expr, $sig:expr)        => ();
     ( @args $mrb:expr, $sig:expr, $( $names:ident : $ts:tt ),+ ) => {
@@ -205,14 +258,14 @@ macro_rules! mrfn {
         {
             let mrb = $mruby.borrow().mrb;
 
-            let args = ::std::mem::MaybeUninit::<*mut $crate::MrValue>::uninit().assume_init();
-            let count = ::std::mem::MaybeUninit::<MrInt>::uninit().assume_init();
+            let mut args: *mut $crate::MrValue = ::std::ptr::null_mut();
+            let mut count: MrInt = 0;
 
             $crate::mrb_get_args(mrb, $sig, $( mrfn!(@args $names, $ts), )*
-                        &args as *const *mut $crate::MrValue, &count as *const MrInt);
+                        &mut args as *mut *mut $crate::MrValue, &mut count as *mut MrInt);
 
             let args = ::std::slice::from_raw_parts(args, count as usize);
-            args.iter().map(|arg| { $crate::Value::new($mruby.clone(), arg.clone()) }).collect::<Vec<_>>()
+            args.iter().map(|arg| { $crate::Value::new($mruby.clone(), *arg) }).collect::<Vec<_>>()
          }
     };
 
@@ -221,15 +274,15 @@ macro_rules! mrfn {
         {
             let mrb = $mruby.borrow().mrb;
 
-            let args = ::std::mem::MaybeUninit::<*mut $crate::MrValue>::uninit().assume_init();
-            let count = ::std::mem::MaybeUninit::<MrInt>::uninit().assume_init();
-            let blk = ::std::mem::MaybeUninit::<$crate::MrValue>::uninit().assume_init();
+            let mut args: *mut $crate::MrValue = ::std::ptr::null_mut();
+            let mut count: MrInt = 0;
+            let mut blk = $crate::MrValue::nil();
 
             $crate::mrb_get_args(mrb, $sig, $(mrfn!(@args $names, $ts), )*
-                        &args as *const *mut $crate::MrValue, &count as *const MrInt, &blk as *const $crate::MrValue);
+                        &mut args as *mut *mut $crate::MrValue, &mut count as *mut MrInt, &mut blk as *mut $crate::MrValue);
 
             let args = ::std::slice::from_raw_parts(args, count as usize);
-            let args = args.iter().map(|arg| { $crate::Value::new($mruby.clone(), arg.clone()) }).collect::<Vec<_>>();
+            let args = args.iter().map(|arg| { $crate::Value::new($mruby.clone(), *arg) }).collect::<Vec<_>>();
             let blk = $crate::Value::new($mruby.clone(), blk);
 
             (args, blk)
@@ -246,73 +299,208 @@ macro_rules! mrfn {
         let $name = $name as f64;
     };
     ( @conv $mruby:expr, $name:ident, (&str) )      => {
-        let $name = ::std::ffi::CStr::from_ptr($name).to_str().unwrap();
+        let $name = mrfn!(@try $mruby, ::std::ffi::CStr::from_ptr($name).to_str().map_err(|_| {
+            $crate::MrubyError::Cast("UTF-8 String".to_owned())
+        }));
     };
     ( @conv $mruby:expr, $name:ident, (Vec<Value>) ) => {
-        let $name = $crate::Value::new($mruby.clone(), $name).to_vec().unwrap();
+        let $name = mrfn!(@try $mruby, $crate::Value::new($mruby.clone(), $name).to_vec());
     };
     ( @conv $mruby:expr, $name:ident, Class )        => {
-        let $name = $crate::Value::new($mruby.clone(), $name).to_class().unwrap();
+        let $name = mrfn!(@try $mruby, $crate::Value::new($mruby.clone(), $name).to_class());
     };
     ( @conv $mruby:expr, $name:ident, Value )        => {
         let $name = $crate::Value::new($mruby.clone(), $name);
     };
     ( @conv $mruby:expr, $name:ident, (&mut $t:ty) ) => {
-        let $name = $crate::Value::new($mruby.clone(), $name).to_obj::<$t>().unwrap();
+        let $name = mrfn!(@try $mruby, $crate::Value::new($mruby.clone(), $name).to_obj::<$t>());
         let mut $name = $name.borrow_mut();
     };
     ( @conv $mruby:expr, $name:ident, (&$t:ty) )     => {
-        let $name = $crate::Value::new($mruby.clone(), $name).to_obj::<$t>().unwrap();
+        let $name = mrfn!(@try $mruby, $crate::Value::new($mruby.clone(), $name).to_obj::<$t>());
         let $name = $name.borrow();
     };
+    ( @conv $mruby:expr, $name:ident, ($t:ty) )      => (mrfn!(@conv $mruby, $name, $t););
+    ( @conv $mruby:expr, $name:ident, $t:tt )        => {
+        let $name = mrfn!(@try $mruby,
+                          <$t as $crate::FromValue>::from_value($crate::Value::new($mruby.clone(), $name)));
+    };
     ( @conv $mruby:expr, $($names:ident : $ts:tt),+ )=> ($( mrfn!(@conv $mruby, $names, $ts); )*);
 
+    // check_defaults
+    ( @check_defaults ) => ();
+    ( @check_defaults $name:ident : $t:tt = $default:tt
+      $( , $names:ident : $ts:tt $( = $defaults:tt )? )* ) => {
+        mrfn!(@check_defaulted $( $names : $ts $( = $defaults )? ),*)
+    };
+    ( @check_defaults $name:ident : $t:tt $( , $names:ident : $ts:tt $( = $defaults:tt )? )* ) => {
+        mrfn!(@check_defaults $( $names : $ts $( = $defaults )? ),*)
+    };
+    ( @check_defaulted ) => ();
+    ( @check_defaulted $name:ident : $t:tt = $default:tt
+      $( , $names:ident : $ts:tt $( = $defaults:tt )? )* ) => {
+        mrfn!(@check_defaulted $( $names : $ts $( = $defaults )? ),*)
+    };
+    ( @check_defaulted $name:ident : $t:tt $( , $names:ident : $ts:tt $( = $defaults:tt )? )* ) => {
+        compile_error!(concat!("required argument `", stringify!($name),
+                               "` cannot follow an argument with a default"))
+    };
+
+    // conv_arg
+    ( @conv_arg $mruby:expr, $argc:ident, $position:ident, $name:ident, $t:tt ) => {
+        mrfn!(@conv $mruby, $name, $t);
+
+        let $position = $position + 1;
+    };
+    ( @conv_arg $mruby:expr, $argc:ident, $position:ident, $name:ident, $t:tt = $default:tt ) => {
+        #[allow(unused_parens)]
+        let $name = if $argc > $position {
+            mrfn!(@conv $mruby, $name, $t);
+
+            $name
+        } else {
+            $default
+        };
+
+        let $position = $position + 1;
+    };
+
     // slf
-    ( @slf $slf:ident, bool )         => (let $slf = $slf.to_bool().unwrap(););
-    ( @slf $slf:ident, i32 )          => (let $slf = $slf.to_i32().unwrap(););
-    ( @slf $slf:ident, f64 )          => (let $slf = $slf.to_f64().unwrap(););
-    ( @slf $slf:ident, (&str) )       => (let $slf = $slf.to_str().unwrap(););
-    ( @slf $slf:ident, (Vec<Value>) ) => (let $slf = $slf.to_vec().unwrap(););
-    ( @slf $slf:ident, Class )        => (let $slf = $slf.to_class().unwrap(););
-    ( @slf $slf:ident, Value )        => ();
-    ( @slf $slf:ident, (&mut $t:ty) ) => {
-        let $slf = $slf.to_obj::<$t>().unwrap();
+    ( @slf $mruby:ident, $slf:ident, bool )         => (let $slf = mrfn!(@try $mruby, $slf.to_bool()););
+    ( @slf $mruby:ident, $slf:ident, i32 )          => (let $slf = mrfn!(@try $mruby, $slf.to_i32()););
+    ( @slf $mruby:ident, $slf:ident, f64 )          => (let $slf = mrfn!(@try $mruby, $slf.to_f64()););
+    ( @slf $mruby:ident, $slf:ident, (&str) )       => (let $slf = mrfn!(@try $mruby, $slf.to_str()););
+    ( @slf $mruby:ident, $slf:ident, (Vec<Value>) ) => (let $slf = mrfn!(@try $mruby, $slf.to_vec()););
+    ( @slf $mruby:ident, $slf:ident, Class )        => (let $slf = mrfn!(@try $mruby, $slf.to_class()););
+    ( @slf $mruby:ident, $slf:ident, Value )        => ();
+    ( @slf $mruby:ident, $slf:ident, (&mut $t:ty) ) => {
+        let $slf = mrfn!(@try $mruby, $slf.to_obj::<$t>());
         let mut $slf = $slf.borrow_mut();
     };
-    ( @slf $slf:ident, (&$t:ty) )     => {
-        let $slf = $slf.to_obj::<$t>().unwrap();
+    ( @slf $mruby:ident, $slf:ident, (&$t:ty) )     => {
+        let $slf = mrfn!(@try $mruby, $slf.to_obj::<$t>());
         let $slf = $slf.borrow();
     };
+    ( @slf $mruby:ident, $slf:ident, $t:tt )        => {
+        let $slf = mrfn!(@try $mruby, <$t as $crate::FromValue>::from_value($slf));
+    };
 
-    // mrfn
-    ( |$mruby:ident, $slf:ident : $st:tt| $block:expr ) => {
-        |$mruby, $slf| {
-            mrfn!(@slf $slf, $st);
+    // try
+    ( @try $mruby:expr, $result:expr ) => {
+        match $result {
+            Ok(value) => value,
+            Err(err)  => {
+                return $crate::ReturnValue::into_return_value(
+                    Err::<$crate::Value, $crate::MrubyError>(err), &$mruby);
+            }
+        }
+    };
 
-            $block
+    // ret
+    ( @ret $mruby:ident, $ret:ty, $block:block ) => {
+        {
+            // The closure scopes `?` and `return` in `$block` to `result`.
+            #[allow(clippy::redundant_closure_call)]
+            let result: $ret = (|| $block)();
+
+            $crate::ReturnValue::into_return_value(result, &$mruby)
         }
     };
-    ( |$mruby:ident, $slf:ident : $st:tt $( , $name:ident : $t:tt )*| $block:expr ) => {
+
+    // sig_rest
+    ( @sig_rest $_rest:ident ) => ("*");
+
+    // sig_blk
+    ( @sig_blk $_blk:ident ) => ("&");
+
+    // closure
+    ( @closure $mruby:ident, $slf:ident : $st:tt, [ $( $name:ident : $t:tt $( = $default:tt )? ),* ],
+      [ $( $rest:ident )* ], [ $( $blk:ident )* ]; -> $ret:ty $block:block ) => {
+        mrfn!(@closure $mruby, $slf : $st, [ $( $name : $t $( = $default )? ),* ], [ $( $rest )* ],
+              [ $( $blk )* ]; mrfn!(@ret $mruby, $ret, $block))
+    };
+    ( @closure $mruby:ident, $slf:ident : $st:tt, [ $( $name:ident : $t:tt $( = $default:tt )? ),* ],
+      [ $( $rest:ident )* ], [ $( $blk:ident )* ]; $block:expr ) => {
         |$mruby, $slf| {
+            mrfn!(@check_defaults $( $name : $t $( = $default )? ),*);
+
             unsafe {
-                mrfn!(@slf $slf, $st);
+                mrfn!(@slf $mruby, $slf, $st);
 
                 mrfn!(@init $( $name : $t ),*);
 
                 let mrb = $mruby.borrow().mrb;
-                let sig_str = ::std::ffi::CString::new(mrfn!(@sig $( $t ),*)).unwrap();
-
-                mrfn!(@args mrb, sig_str.as_ptr() $( , $name : $t )*);
-                mrfn!(@conv $mruby $( , $name : $t )*);
+                let sig_str = ::std::ffi::CString::new(
+                    concat!("" $( , mrfn!(@sig_arg $t $( = $default )?) )*
+                            $( , mrfn!(@sig_rest $rest) )* $( , mrfn!(@sig_blk $blk) )*)
+                ).unwrap();
+
+                $( let mut $rest: (*mut $crate::MrValue, $crate::MrInt) = (::std::ptr::null_mut(), 0); )*
+                $( let mut $blk = $crate::MrValue::nil(); )*
+
+                let _argc = $crate::mrb_get_args(mrb, sig_str.as_ptr() $( , mrfn!(@args $name : $t) )*
+                                                 $( , &mut $rest.0 as *mut *mut $crate::MrValue,
+                                                    &mut $rest.1 as *mut $crate::MrInt )*
+                                                 $( , &mut $blk as *mut $crate::MrValue )*);
+                let _position: $crate::MrInt = 0;
+
+                $( mrfn!(@conv_arg $mruby, _argc, _position, $name, $t $( = $default )?); )*
+
+                $(
+                    let $rest: Vec<$crate::Value> = if $rest.1 > 0 {
+                        ::std::slice::from_raw_parts($rest.0, $rest.1 as usize).iter().map(|arg| {
+                            $crate::Value::new($mruby.clone(), *arg)
+                        }).collect()
+                    } else {
+                        vec![]
+                    };
+                )*
+                $( let $blk = $crate::Value::new($mruby.clone(), $blk); )*
 
                 $block
             }
         }
     };
+
+    // mrfn
+    ( |$mruby:ident, $slf:ident : $st:tt| -> $ret:ty $block:block ) => {
+        mrfn!(|$mruby, $slf : $st| mrfn!(@ret $mruby, $ret, $block))
+    };
+    ( |$mruby:ident, $slf:ident : $st:tt $( , $name:ident : $t:tt )* ; &$blk:ident| -> $ret:ty $block:block ) => {
+        mrfn!(|$mruby, $slf : $st $( , $name : $t )* ; &$blk| mrfn!(@ret $mruby, $ret, $block))
+    };
+    ( |$mruby:ident, $slf:ident : $st:tt $( , $name:ident : $t:tt )* ; $args:ident| -> $ret:ty $block:block ) => {
+        mrfn!(|$mruby, $slf : $st $( , $name : $t )* ; $args| mrfn!(@ret $mruby, $ret, $block))
+    };
+    ( |$mruby:ident, $slf:ident : $st:tt $( , $name:ident : $t:tt )* ; $args:ident, &$blk:ident| -> $ret:ty $block:block ) => {
+        mrfn!(|$mruby, $slf : $st $( , $name : $t )* ; $args, &$blk| mrfn!(@ret $mruby, $ret, $block))
+    };
+    ( |$mruby:ident, $slf:ident : $st:tt| $block:expr ) => {
+        |$mruby, $slf| {
+            mrfn!(@slf $mruby, $slf, $st);
+
+            $block
+        }
+    };
+    ( |$mruby:ident, $slf:ident : $st:tt $( , $name:ident : $t:tt $( = $default:tt )? )* , * $rest:ident : Vec<Value> $( , &$blk:ident : Value )*| $( $tail:tt )* ) => {
+        mrfn!(|$mruby, $slf : $st $( , $name : $t $( = $default )? )* , * $rest : (Vec<Value>) $( , &$blk : Value )*| $( $tail )*)
+    };
+    ( |$mruby:ident, $slf:ident : $st:tt $( , $name:ident : $t:tt $( = $default:tt )? )* , * $rest:ident : (Vec<Value>) , &$blk:ident : Value| $( $tail:tt )* ) => {
+        mrfn!(@closure $mruby, $slf : $st, [ $( $name : $t $( = $default )? ),* ], [ $rest ], [ $blk ]; $( $tail )*)
+    };
+    ( |$mruby:ident, $slf:ident : $st:tt $( , $name:ident : $t:tt $( = $default:tt )? )* , * $rest:ident : (Vec<Value>)| $( $tail:tt )* ) => {
+        mrfn!(@closure $mruby, $slf : $st, [ $( $name : $t $( = $default )? ),* ], [ $rest ], []; $( $tail )*)
+    };
+    ( |$mruby:ident, $slf:ident : $st:tt $( , $name:ident : $t:tt $( = $default:tt )? )* , &$blk:ident : Value| $( $tail:tt )* ) => {
+        mrfn!(@closure $mruby, $slf : $st, [ $( $name : $t $( = $default )? ),* ], [], [ $blk ]; $( $tail )*)
+    };
+    ( |$mruby:ident, $slf:ident : $st:tt $( , $name:ident : $t:tt $( = $default:tt )? )*| $( $tail:tt )* ) => {
+        mrfn!(@closure $mruby, $slf : $st, [ $( $name : $t $( = $default )? ),* ], [], []; $( $tail )*)
+    };
     ( |$mruby:ident, $slf:ident : $st:tt $( , $name:ident : $t:tt )* ; &$blk:ident| $block:expr ) => {
         |$mruby, $slf| {
             unsafe {
-                mrfn!(@slf $slf, $st);
+                mrfn!(@slf $mruby, $slf, $st);
 
                 mrfn!(@init $( $name : $t, )* $blk : Value);
 
@@ -329,7 +517,7 @@ macro_rules! mrfn {
     ( |$mruby:ident, $slf:ident : $st:tt $( , $name:ident : $t:tt )* ; $args:ident| $block:expr ) => {
         |$mruby, $slf| {
             unsafe {
-                mrfn!(@slf $slf, $st);
+                mrfn!(@slf $mruby, $slf, $st);
 
                 mrfn!(@init $( $name : $t ),*);
 
@@ -345,7 +533,7 @@ macro_rules! mrfn {
     ( |$mruby:ident, $slf:ident : $st:tt $( , $name:ident : $t:tt )* ; $args:ident, &$blk:ident| $block:expr ) => {
         |$mruby, $slf| {
             unsafe {
-                mrfn!(@slf $slf, $st);
+                mrfn!(@slf $mruby, $slf, $st);
 
                 mrfn!(@init $( $name : $t ),*);
 
@@ -456,6 +644,20 @@ macro_rules! defines {
 
         defines!($mruby, $name, $( $rest )*);
     };
+
+    // class methods with an untyped slf
+    ( $mruby:expr, $name:ty, def_self!($method:expr, | $mrb:ident, $slf:ident $( , $n:ident : $t:tt )* | $block:expr ); $( $rest:tt )* ) => {
+        defines!($mruby, $name, def_self!($method, | $mrb, $slf: Value $( , $n : $t )* | $block); $( $rest )*);
+    };
+    ( $mruby:expr, $name:ty, def_self!($method:expr, | $mrb:ident, $slf:ident $( , $n:ident : $t:tt )* ; &$blk:ident | $block:expr ); $( $rest:tt )* ) => {
+        defines!($mruby, $name, def_self!($method, | $mrb, $slf: Value $( , $n : $t )* ; &$blk | $block); $( $rest )*);
+    };
+    ( $mruby:expr, $name:ty, def_self!($method:expr, | $mrb:ident, $slf:ident $( , $n:ident : $t:tt )* ; $args:ident | $block:expr ); $( $rest:tt )* ) => {
+        defines!($mruby, $name, def_self!($method, | $mrb, $slf: Value $( , $n : $t )* ; $args | $block); $( $rest )*);
+    };
+    ( $mruby:expr, $name:ty, def_self!($method:expr, | $mrb:ident, $slf:ident $( , $n:ident : $t:tt )* ; $args:ident, &$blk:ident | $block:expr ); $( $rest:tt )* ) => {
+        defines!($mruby, $name, def_self!($method, | $mrb, $slf: Value $( , $n : $t )* ; $args, &$blk | $block); $( $rest )*);
+    };
     
     // initialize block
     ( $mruby:expr, $name:ty, def!("initialize", | $( $n:ident : $t:tt ),* ; &$blk:ident | $block:expr ); $( $rest:tt )* ) => {
@@ -1008,7 +1210,8 @@ macro_rules! mruby_defines {
 ///
 /// Use `def_self!` to define mruby class methods.
 ///
-/// *Note:* `mruby` argument is optional.
+/// *Note:* `mruby` argument is optional. When it is given, the type of `slf` can be left out and
+/// defaults to `Value`, e.g. `def_self!("create", |mruby, slf| { ... })`.
 ///
 /// ```
 /// # #[macro_use] extern crate mrusty;
@@ -1115,7 +1318,8 @@ macro_rules! mrusty_class {
 ///
 /// Use `def_self!` to define mruby class methods.
 ///
-/// *Note:* `mruby` argument is optional.
+/// *Note:* `mruby` argument is optional. When it is given, the type of `slf` can be left out and
+/// defaults to `Value`, e.g. `def_self!("create", |mruby, slf| { ... })`.
 ///
 /// ```
 /// # #[macro_use] extern crate mrusty;
@@ -1186,6 +1390,148 @@ macro_rules! mruby_class {
     };
 }
 
+/// A `macro` that implements `SymbolEnum` for a field-less `enum`, mapping every variant to the
+/// name of its mruby `Symbol`.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate mrusty;
+/// use mrusty::{Mruby, MrubyImpl};
+/// use mrusty::FromValue;
+///
+/// # fn main() {
+/// #[derive(Debug, PartialEq)]
+/// enum Mode {
+///     Fast,
+///     Safe,
+///     Debug
+/// }
+///
+/// symbol_enum!(Mode {
+///     Fast  => "fast",
+///     Safe  => "safe",
+///     Debug => "debug"
+/// });
+///
+/// let mruby = Mruby::new();
+///
+/// assert_eq!(Mode::from_value(mruby.run(":debug").unwrap()).unwrap(), Mode::Debug);
+///
+/// let err = Mode::from_value(mruby.run(":turbo").unwrap()).unwrap_err();
+///
+/// assert_eq!(err.to_string(), "Cast error: expected one of :fast, :safe, :debug, found :turbo");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! symbol_enum {
+    ( $name:ident { $( $variant:ident => $symbol:tt ),+ } ) => {
+        impl $crate::SymbolEnum for $name {
+            fn to_symbol_name(&self) -> &'static str {
+                match *self {
+                    $( $name::$variant => $symbol ),+
+                }
+            }
+
+            fn from_symbol_name(name: &str) -> Option<$name> {
+                match name {
+                    $( $symbol => Some($name::$variant), )+
+                    _ => None
+                }
+            }
+
+            fn symbol_names() -> &'static [&'static str] {
+                &[ $( $symbol ),+ ]
+            }
+        }
+    };
+    ( $name:ident { $( $variant:ident => $symbol:tt ),+ , } ) => {
+        symbol_enum!($name { $( $variant => $symbol ),+ });
+    };
+}
+
+/// A `macro` that implements `FlagSet`, `FromValue` and `ToValue` for an existing `bitflags`
+/// type, i.e. one with a `const fn bits()`, `from_bits_truncate()` and associated flag constants.
+/// Every flag is mapped to the name of its mruby `Symbol`.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate mrusty;
+/// use mrusty::{Mruby, MrubyImpl};
+/// use mrusty::{FromValue, ToValue};
+///
+/// # fn main() {
+/// // What `bitflags!` generates, give or take.
+/// #[derive(Debug, PartialEq)]
+/// struct Perms { bits: u8 }
+///
+/// impl Perms {
+///     const READ: Perms = Perms { bits: 1 };
+///     const WRITE: Perms = Perms { bits: 2 };
+///
+///     const fn bits(&self) -> u8 { self.bits }
+///     fn from_bits_truncate(bits: u8) -> Perms { Perms { bits: bits & 3 } }
+/// }
+///
+/// flag_set!(Perms {
+///     READ  => "read",
+///     WRITE => "write"
+/// });
+///
+/// let mruby = Mruby::new();
+///
+/// assert_eq!(Perms::from_value(mruby.run("[:read, :write]").unwrap()).unwrap(),
+///            Perms { bits: 3 });
+/// assert_eq!(Perms::WRITE.to_value(&mruby).to_s(), "[:write]");
+///
+/// let err = Perms::from_value(mruby.run("[:read, :exec]").unwrap()).unwrap_err();
+///
+/// assert_eq!(err.to_string(), "Cast error: expected one of :read, :write, found :exec at [1]");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! flag_set {
+    ( $name:ident { $( $flag:ident => $symbol:tt ),+ } ) => {
+        impl $crate::FlagSet for $name {
+            #[inline]
+            fn to_flag_bits(&self) -> u64 {
+                self.bits() as u64
+            }
+
+            #[inline]
+            fn from_flag_bits(bits: u64) -> $name {
+                $name::from_bits_truncate(bits as _)
+            }
+
+            fn flag_names() -> &'static [(u64, &'static str)] {
+                const NAMES: &[(u64, &str)] = &[ $( ($name::$flag.bits() as u64, $symbol) ),+ ];
+
+                NAMES
+            }
+        }
+
+        impl $crate::FromValue for $name {
+            #[inline]
+            fn from_value(value: $crate::Value) -> Result<$name, $crate::MrubyError> {
+                <$name as $crate::FlagSet>::from_flags_value(value)
+            }
+        }
+
+        impl $crate::ToValue for $name {
+            #[inline]
+            fn to_value(self, mruby: &$crate::MrubyType) -> $crate::Value {
+                <$name as $crate::FlagSet>::to_flags_value(&self, mruby)
+            }
+        }
+    };
+    ( $name:ident { $( $flag:ident => $symbol:tt ),+ , } ) => {
+        flag_set!($name { $( $flag => $symbol ),+ });
+    };
+}
+
 #[path="tests/macros.rs"]
 #[cfg(test)]
+#[allow(clippy::assertions_on_constants, clippy::bool_assert_comparison,
+        clippy::macro_metavars_in_unsafe)]
 mod tests;
@@ -13,7 +13,10 @@
 #include <mruby/class.h>
 #include <mruby/data.h>
 #include <mruby/error.h>
+#include <mruby/hash.h>
 #include <mruby/proc.h>
+#include <mruby/range.h>
+#include <mruby/string.h>
 #include <mruby/value.h>
 #include <mruby/variable.h>
 #include <mruby/throw.h>
@@ -27,25 +30,32 @@ void mrb_ext_set_ud(struct mrb_state* mrb, void* ud) {
   mrb->ud = ud;
 }
 
-mrb_value mrb_ext_load_nstring_cxt_nothrow(mrb_state *mrb, const char *s, size_t len, mrbc_context *cxt) {
-  mrb_value value;
+// the nothrow shims below run their body under mrb_protect, which takes a raised exception off
+// the state and returns it; shims that leave the exception pending for Rust put it back
+static mrb_value keep_pending(struct mrb_state* mrb, mrb_value exc) {
+  mrb->exc = mrb_obj_ptr(exc);
 
-  struct mrb_jmpbuf c_jmp;
-  struct mrb_jmpbuf *pc_jmp_bak;
-  pc_jmp_bak = mrb->jmp;
+  return mrb_nil_value();
+}
 
-  MRB_TRY(&c_jmp) {
-    mrb->jmp = &c_jmp;
-    value = mrb_load_nstring_cxt(mrb, s, len, cxt);
-  }
-  MRB_CATCH(&c_jmp) {
-    value = mrb_nil_value();
-  }
-  MRB_END_EXC(&c_jmp);
+struct load_args {
+  const char* s;
+  size_t len;
+  mrbc_context* cxt;
+};
 
-  mrb->jmp = pc_jmp_bak;
+static mrb_value load_nstring_cxt(struct mrb_state* mrb, mrb_value data) {
+  struct load_args* args = (struct load_args*) mrb_cptr(data);
 
-  return value;
+  return mrb_load_nstring_cxt(mrb, args->s, args->len, args->cxt);
+}
+
+mrb_value mrb_ext_load_nstring_cxt_nothrow(mrb_state *mrb, const char *s, size_t len, mrbc_context *cxt) {
+  struct load_args args = { s, len, cxt };
+  mrb_bool raised;
+  mrb_value value = mrb_protect(mrb, load_nstring_cxt, mrb_cptr_value(mrb, &args), &raised);
+
+  return raised ? keep_pending(mrb, value) : value;
 }
 
 // from) load.c:read_binary_header()
@@ -123,6 +133,10 @@ mrb_value mrb_ext_nil() {
   return mrb_nil_value();
 }
 
+mrb_bool mrb_ext_nil_p(mrb_value value) {
+  return mrb_nil_p(value);
+}
+
 mrb_value mrb_ext_false() {
   return mrb_false_value();
 }
@@ -139,6 +153,12 @@ mrb_value mrb_ext_cdouble_to_float(struct mrb_state* mrb, mrb_float value) {
   return mrb_float_value(mrb, value);
 }
 
+const char* mrb_ext_str_ptr_len(mrb_value value, mrb_int* len) {
+  *len = RSTRING_LEN(value);
+
+  return RSTRING_PTR(value);
+}
+
 const char* mrb_ext_sym2name(struct mrb_state* mrb, mrb_value value) {
   return mrb_sym2name(mrb, mrb_symbol(value));
 }
@@ -186,6 +206,10 @@ mrb_value mrb_ext_class_value(struct RClass* klass) {
   return value;
 }
 
+enum mrb_vtype mrb_ext_class_type(struct RClass* klass) {
+  return klass->tt;
+}
+
 mrb_value mrb_ext_module_value(struct RClass* module) {
   mrb_value value;
 
@@ -208,10 +232,274 @@ void mrb_ext_set_instance_tt(struct RClass* class, enum mrb_vtype type) {
   MRB_SET_INSTANCE_TT(class, type);
 }
 
+void mrb_ext_gc_set_interval_ratio(struct mrb_state* mrb, int ratio) {
+  mrb->gc.interval_ratio = ratio;
+}
+
+void mrb_ext_gc_set_step_ratio(struct mrb_state* mrb, int ratio) {
+  mrb->gc.step_ratio = ratio;
+}
+
+mrb_bool mrb_ext_gc_enable(struct mrb_state* mrb) {
+  mrb_bool disabled = mrb->gc.disabled;
+
+  mrb->gc.disabled = FALSE;
+
+  return disabled;
+}
+
+mrb_bool mrb_ext_gc_disable(struct mrb_state* mrb) {
+  mrb_bool disabled = mrb->gc.disabled;
+
+  mrb->gc.disabled = TRUE;
+
+  return disabled;
+}
+
+int mrb_ext_gc_arena_save(struct mrb_state* mrb) {
+  return mrb_gc_arena_save(mrb);
+}
+
+void mrb_ext_gc_arena_restore(struct mrb_state* mrb, int idx) {
+  mrb_gc_arena_restore(mrb, idx);
+}
+
+static mrb_value cfunc_proc_call(struct mrb_state* mrb, mrb_value self) {
+  mrb_value* argv;
+  mrb_int argc;
+
+  mrb_get_args(mrb, "*", &argv, &argc);
+
+  return mrb_yield_argv(mrb, self, argc, argv);
+}
+
+mrb_value mrb_ext_proc_new_cfunc_with_env(struct mrb_state* mrb, mrb_func_t func,
+  mrb_int argc, const mrb_value* argv) {
+  struct RProc* proc = mrb_proc_new_cfunc_with_env(mrb, func, argc, argv);
+
+  // Proc#call runs through OP_CALL, which cannot return to a C caller from a C function proc,
+  // so these procs get singleton call and [] that yield to them instead.
+  mrb_define_singleton_method(mrb, (struct RObject*) proc, "call", cfunc_proc_call,
+    MRB_ARGS_ANY());
+  mrb_define_singleton_method(mrb, (struct RObject*) proc, "[]", cfunc_proc_call,
+    MRB_ARGS_ANY());
+
+  return mrb_obj_value(proc);
+}
+
+static mrb_value fiber_new(struct mrb_state* mrb, mrb_value block) {
+  struct RClass* fiber_class = mrb_class_get(mrb, "Fiber");
+
+  return mrb_funcall_with_block(mrb, mrb_obj_value(fiber_class), mrb_intern_lit(mrb, "new"), 0,
+    NULL, block);
+}
+
+mrb_value mrb_ext_fiber_new_nothrow(struct mrb_state* mrb, mrb_value block, mrb_bool* raised) {
+  return mrb_protect(mrb, fiber_new, block, raised);
+}
+
+// the trampoline sending Fiber#resume, rooted as a hidden global like mrb_gc_register's table
+#define FIBER_RESUME_NAME "_mrusty_fiber_resume_"
+
+// mruby 2.1's mrb_fiber_resume drops the block arguments of the first resume and the values
+// returned by Fiber.yield afterwards, since it runs the fiber in a nested VM. Fiber#resume only
+// behaves when sent from Ruby code; sent from a C function, e.g. one made with
+// mrb_proc_new_cfunc, it takes that same nested VM path. The Ruby proc sending it is compiled
+// once per state and kept in a slot owned by the state.
+static mrb_value fiber_resume_proc(struct mrb_state* mrb) {
+  mrb_sym name = mrb_intern_lit(mrb, FIBER_RESUME_NAME);
+  mrb_value resume = mrb_gv_get(mrb, name);
+
+  if (mrb_nil_p(resume)) {
+    resume = mrb_load_string(mrb, "proc { |fiber, args| fiber.resume(*args) }");
+
+    mrb_gv_set(mrb, name, resume);
+  }
+
+  return resume;
+}
+
+struct fiber_resume_args {
+  mrb_value fiber;
+  mrb_int argc;
+  const mrb_value* argv;
+};
+
+static mrb_value fiber_resume(struct mrb_state* mrb, mrb_value data) {
+  struct fiber_resume_args* args = (struct fiber_resume_args*) mrb_cptr(data);
+
+  if (!mrb_test(mrb_fiber_alive_p(mrb, args->fiber))) {
+    mrb_raise(mrb, mrb_class_get(mrb, "StopIteration"), "fiber is exhausted");
+  }
+
+  mrb_value argv[2] = { args->fiber, mrb_ary_new_from_values(mrb, args->argc, args->argv) };
+
+  return mrb_yield_argv(mrb, fiber_resume_proc(mrb), 2, argv);
+}
+
+mrb_value mrb_ext_fiber_resume_nothrow(struct mrb_state* mrb, mrb_value fiber, mrb_int argc,
+  const mrb_value* argv, mrb_bool* raised) {
+  struct fiber_resume_args args = { fiber, argc, argv };
+
+  return mrb_protect(mrb, fiber_resume, mrb_cptr_value(mrb, &args), raised);
+}
+
 mrb_int mrb_ext_ary_len(struct mrb_state* mrb, mrb_value array) {
   return RARRAY_LEN(array);
 }
 
+struct ary_args {
+  mrb_value (*op)(struct mrb_state*, mrb_value, mrb_value);
+  mrb_value array;
+  mrb_value value;
+};
+
+static mrb_value ary_op(struct mrb_state* mrb, mrb_value data) {
+  struct ary_args* args = (struct ary_args*) mrb_cptr(data);
+
+  return args->op(mrb, args->array, args->value);
+}
+
+static mrb_value ary_nothrow(struct mrb_state* mrb,
+  mrb_value (*op)(struct mrb_state*, mrb_value, mrb_value), mrb_value array, mrb_value value,
+  mrb_bool* raised) {
+  struct ary_args args = { op, array, value };
+
+  return mrb_protect(mrb, ary_op, mrb_cptr_value(mrb, &args), raised);
+}
+
+static mrb_value ary_push(struct mrb_state* mrb, mrb_value array, mrb_value value) {
+  mrb_ary_push(mrb, array, value);
+
+  return mrb_nil_value();
+}
+
+static mrb_value ary_pop(struct mrb_state* mrb, mrb_value array, mrb_value value) {
+  return mrb_ary_pop(mrb, array);
+}
+
+static mrb_value ary_shift(struct mrb_state* mrb, mrb_value array, mrb_value value) {
+  return mrb_ary_shift(mrb, array);
+}
+
+static mrb_value ary_unshift(struct mrb_state* mrb, mrb_value array, mrb_value value) {
+  mrb_ary_unshift(mrb, array, value);
+
+  return mrb_nil_value();
+}
+
+static mrb_value ary_concat(struct mrb_state* mrb, mrb_value array, mrb_value other) {
+  mrb_ary_concat(mrb, array, other);
+
+  return array;
+}
+
+mrb_value mrb_ext_ary_push_nothrow(struct mrb_state* mrb, mrb_value array, mrb_value value,
+  mrb_bool* raised) {
+  return ary_nothrow(mrb, ary_push, array, value, raised);
+}
+
+mrb_value mrb_ext_ary_pop_nothrow(struct mrb_state* mrb, mrb_value array, mrb_bool* raised) {
+  return ary_nothrow(mrb, ary_pop, array, mrb_nil_value(), raised);
+}
+
+mrb_value mrb_ext_ary_shift_nothrow(struct mrb_state* mrb, mrb_value array, mrb_bool* raised) {
+  return ary_nothrow(mrb, ary_shift, array, mrb_nil_value(), raised);
+}
+
+mrb_value mrb_ext_ary_unshift_nothrow(struct mrb_state* mrb, mrb_value array, mrb_value value,
+  mrb_bool* raised) {
+  return ary_nothrow(mrb, ary_unshift, array, value, raised);
+}
+
+mrb_value mrb_ext_ary_concat_nothrow(struct mrb_state* mrb, mrb_value array, mrb_value other,
+  mrb_bool* raised) {
+  return ary_nothrow(mrb, ary_concat, array, other, raised);
+}
+
+struct hash_args {
+  mrb_value (*op)(struct mrb_state*, mrb_value, mrb_value, mrb_value);
+  mrb_value hash;
+  mrb_value key;
+  mrb_value value;
+};
+
+static mrb_value hash_op(struct mrb_state* mrb, mrb_value data) {
+  struct hash_args* args = (struct hash_args*) mrb_cptr(data);
+
+  return args->op(mrb, args->hash, args->key, args->value);
+}
+
+static mrb_value hash_nothrow(struct mrb_state* mrb,
+  mrb_value (*op)(struct mrb_state*, mrb_value, mrb_value, mrb_value), mrb_value hash,
+  mrb_value key, mrb_value value, mrb_bool* raised) {
+  struct hash_args args = { op, hash, key, value };
+
+  return mrb_protect(mrb, hash_op, mrb_cptr_value(mrb, &args), raised);
+}
+
+static mrb_value hash_get(struct mrb_state* mrb, mrb_value hash, mrb_value key,
+  mrb_value value) {
+  return mrb_hash_get(mrb, hash, key);
+}
+
+static mrb_value hash_set(struct mrb_state* mrb, mrb_value hash, mrb_value key,
+  mrb_value value) {
+  mrb_hash_set(mrb, hash, key, value);
+
+  return mrb_nil_value();
+}
+
+static mrb_value hash_delete(struct mrb_state* mrb, mrb_value hash, mrb_value key,
+  mrb_value value) {
+  return mrb_hash_delete_key(mrb, hash, key);
+}
+
+static mrb_value hash_keys(struct mrb_state* mrb, mrb_value hash, mrb_value key,
+  mrb_value value) {
+  return mrb_hash_keys(mrb, hash);
+}
+
+static mrb_value hash_values(struct mrb_state* mrb, mrb_value hash, mrb_value key,
+  mrb_value value) {
+  return mrb_hash_values(mrb, hash);
+}
+
+mrb_value mrb_ext_hash_get_nothrow(struct mrb_state* mrb, mrb_value hash, mrb_value key,
+  mrb_bool* raised) {
+  return hash_nothrow(mrb, hash_get, hash, key, mrb_nil_value(), raised);
+}
+
+mrb_value mrb_ext_hash_set_nothrow(struct mrb_state* mrb, mrb_value hash, mrb_value key,
+  mrb_value value, mrb_bool* raised) {
+  return hash_nothrow(mrb, hash_set, hash, key, value, raised);
+}
+
+mrb_value mrb_ext_hash_delete_nothrow(struct mrb_state* mrb, mrb_value hash, mrb_value key,
+  mrb_bool* raised) {
+  return hash_nothrow(mrb, hash_delete, hash, key, mrb_nil_value(), raised);
+}
+
+mrb_value mrb_ext_hash_keys_nothrow(struct mrb_state* mrb, mrb_value hash, mrb_bool* raised) {
+  return hash_nothrow(mrb, hash_keys, hash, mrb_nil_value(), mrb_nil_value(), raised);
+}
+
+mrb_value mrb_ext_hash_values_nothrow(struct mrb_state* mrb, mrb_value hash, mrb_bool* raised) {
+  return hash_nothrow(mrb, hash_values, hash, mrb_nil_value(), mrb_nil_value(), raised);
+}
+
+mrb_value mrb_ext_range_beg(struct mrb_state* mrb, mrb_value range) {
+  return mrb_range_beg(mrb, range);
+}
+
+mrb_value mrb_ext_range_end(struct mrb_state* mrb, mrb_value range) {
+  return mrb_range_end(mrb, range);
+}
+
+mrb_bool mrb_ext_range_excl(struct mrb_state* mrb, mrb_value range) {
+  return mrb_range_excl_p(mrb, range);
+}
+
 mrb_sym mrb_ext_get_mid(struct mrb_state* mrb) {
   mrb_sym mid = mrb_get_mid(mrb);
   if (mid == mrb_intern_lit(mrb, "new")) {
@@ -236,31 +524,137 @@ mrb_value mrb_ext_get_exc(struct mrb_state* mrb) {
   }
 }
 
+static mrb_value exc_str(struct mrb_state* mrb, mrb_value exc) {
+  return mrb_funcall(mrb, exc, "message", 0);
+}
+
+mrb_value mrb_ext_exc_str_nothrow(struct mrb_state* mrb, mrb_value exc) {
+  mrb_bool raised;
+  mrb_value message = mrb_protect(mrb, exc_str, exc, &raised);
+
+  return raised ? mrb_nil_value() : message;
+}
+
+mrb_value mrb_ext_exc_backtrace(struct mrb_state* mrb, mrb_value exc) {
+  return mrb_exc_backtrace(mrb, exc);
+}
+
+mrb_value mrb_ext_take_exc(struct mrb_state* mrb) {
+  if (mrb->exc) {
+    mrb_value exc = mrb_obj_value(mrb->exc);
+
+    mrb->exc = NULL;
+
+    return exc;
+  } else {
+    return mrb_nil_value();
+  }
+}
+
 void mrb_ext_raise_current(struct mrb_state* mrb) {
     if (mrb->exc) {
         mrb_exc_raise(mrb, mrb_obj_value(mrb->exc));
     }
 }
 
-mrb_value mrb_ext_exc_str(struct mrb_state* mrb, mrb_value exc) {
-    return mrb_funcall(mrb, exc, "inspect", 0);
+// the last exception turned into an MrubyError, rooted as a hidden global like mrb_gc_register
+#define KEPT_EXC_NAME "_mrusty_kept_exc_"
+
+void mrb_ext_keep_exc(struct mrb_state* mrb, mrb_value exc) {
+  mrb_gv_set(mrb, mrb_intern_lit(mrb, KEPT_EXC_NAME), exc);
 }
 
-mrb_noreturn void mrb_ext_raise_nothrow(struct mrb_state* mrb, const char* eclass,
-  const char* msg) {
+mrb_value mrb_ext_take_kept_exc(struct mrb_state* mrb) {
+  mrb_sym name = mrb_intern_lit(mrb, KEPT_EXC_NAME);
+  mrb_value exc = mrb_gv_get(mrb, name);
+
+  mrb_gv_remove(mrb, name);
+
+  return exc;
+}
 
-  struct mrb_jmpbuf c_jmp;
-  struct mrb_jmpbuf *pc_jmp_bak;
-  pc_jmp_bak = mrb->jmp;
+// resolves a class path such as "Outer::Error", raising NameError or TypeError on failure
+static struct RClass* class_path_get(struct mrb_state* mrb, const char* path) {
+  struct RClass* outer = mrb->object_class;
+  const char* sep;
 
-  MRB_TRY(&c_jmp) {
-    mrb->jmp = &c_jmp;
-    mrb_raise(mrb, mrb_class_get(mrb, eclass), msg);
+  while ((sep = strstr(path, "::")) != NULL) {
+    mrb_value value = mrb_const_get(mrb, mrb_obj_value(outer), mrb_intern(mrb, path, sep - path));
+
+    if (mrb_type(value) != MRB_TT_CLASS && mrb_type(value) != MRB_TT_MODULE) {
+      mrb_raisef(mrb, E_TYPE_ERROR, "%S is not a class/module", value);
+    }
+
+    outer = mrb_class_ptr(value);
+    path = sep + 2;
   }
-  MRB_CATCH(&c_jmp) {}
-  MRB_END_EXC(&c_jmp);
 
-  mrb->jmp = pc_jmp_bak;
+  return mrb_class_get_under(mrb, outer, path);
+}
+
+struct raise_args {
+  const char* eclass;
+  const char* msg;
+};
+
+static mrb_value raise_class(struct mrb_state* mrb, mrb_value data) {
+  struct raise_args* args = (struct raise_args*) mrb_cptr(data);
+
+  mrb_raise(mrb, class_path_get(mrb, args->eclass), args->msg);
+}
+
+void mrb_ext_raise_nothrow(struct mrb_state* mrb, const char* eclass, const char* msg) {
+  struct raise_args args = { eclass, msg };
+  mrb_bool raised;
+
+  keep_pending(mrb, mrb_protect(mrb, raise_class, mrb_cptr_value(mrb, &args), &raised));
+}
+
+static mrb_value exc_raise(struct mrb_state* mrb, mrb_value exc) {
+  mrb_exc_raise(mrb, exc);
+}
+
+void mrb_ext_exc_raise_nothrow(struct mrb_state* mrb, mrb_value exc) {
+  mrb_bool raised;
+
+  keep_pending(mrb, mrb_protect(mrb, exc_raise, exc, &raised));
+}
+
+struct equal_args {
+  mrb_value a;
+  mrb_value b;
+};
+
+static mrb_value equal(struct mrb_state* mrb, mrb_value data) {
+  struct equal_args* args = (struct equal_args*) mrb_cptr(data);
+
+  return mrb_bool_value(mrb_equal(mrb, args->a, args->b));
+}
+
+mrb_bool mrb_ext_equal_nothrow(struct mrb_state* mrb, mrb_value a, mrb_value b) {
+  struct equal_args args = { a, b };
+  mrb_bool raised;
+  mrb_value result = mrb_protect(mrb, equal, mrb_cptr_value(mrb, &args), &raised);
+
+  return !raised && mrb_test(result);
+}
+
+struct RClass* mrb_ext_const_class_get(struct mrb_state* mrb, mrb_sym sym,
+  enum mrb_vtype tt) {
+  mrb_value obj = mrb_obj_value(mrb->object_class);
+  mrb_value value;
+
+  if (!mrb_const_defined(mrb, obj, sym)) return NULL;
+
+  value = mrb_const_get(mrb, obj, sym);
+
+  if (mrb_type(value) != tt) return NULL;
+
+  return mrb_class_ptr(value);
+}
+
+mrb_bool mrb_ext_const_is(struct mrb_state* mrb, mrb_sym sym, mrb_value value) {
+  return mrb_obj_eq(mrb, mrb_iv_get(mrb, mrb_obj_value(mrb->object_class), sym), value);
 }
 
 mrb_bool mrb_ext_class_defined_under(struct mrb_state* mrb,
@@ -276,6 +670,10 @@ struct RClass* mrb_ext_class_ptr(mrb_value value) {
   return mrb_class_ptr(value);
 }
 
+mrb_bool mrb_ext_method_defined(struct mrb_state* mrb, struct RClass* class, mrb_sym mid) {
+  return mrb_obj_respond_to(mrb, class, mid);
+}
+
 struct RClass* mrb_ext_class(struct mrb_state* mrb, mrb_value value) {
   return mrb_class(mrb, value);
 }
@@ -8,16 +8,25 @@
 use std::any::{Any, TypeId};
 use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
 use std::error::Error;
+use std::borrow::Cow;
 use std::ffi::{CStr, CString};
 use std::fs::File;
+use std::hash::{BuildHasherDefault, Hash, Hasher};
 use std::io::{self, Read};
 use std::mem;
+use std::ops;
 use std::os::raw::{c_char, c_void};
-use std::panic::{self, AssertUnwindSafe};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::ptr;
 use std::rc::Rc;
+use std::slice;
+use std::time::Duration;
+#[cfg(feature = "time")]
+use std::time::SystemTime;
 
+use super::convert::{FromValue, ToValue};
 use super::mruby_ffi::*;
 
 /// A `type` wrapper around a `Rc<RefCell<Mruby>>`. Created with `Mruby::new()`.
@@ -42,12 +51,41 @@ pub struct Mruby {
     ctx:                 *const MrContext,
     filename:            Option<String>,
     classes:             HashMap<TypeId, (*const MrClass, Rc<MrDataType>, String, CString)>,
-    methods:             HashMap<TypeId, HashMap<u32, Rc<dyn Fn(MrubyType, Value) -> Value>>>,
-    class_methods:       HashMap<TypeId, HashMap<u32, Rc<dyn Fn(MrubyType, Value) -> Value>>>,
-    mruby_methods:       HashMap<String, HashMap<u32, Rc<dyn Fn(MrubyType, Value) -> Value>>>,
-    mruby_class_methods: HashMap<String, HashMap<u32, Rc<dyn Fn(MrubyType, Value) -> Value>>>,
+    methods:             MethodTable<TypeId>,
+    class_methods:       MethodTable<TypeId>,
+    mruby_methods:       MethodTable<String>,
+    mruby_class_methods: MethodTable<String>,
     files:               HashMap<String, Vec<fn(MrubyType)>>,
-    required:            HashSet<String>
+    required:            HashSet<String>,
+    symbols:             HashMap<Rc<str>, u32, BuildHasherDefault<FnvHasher>>,
+    class_cache:         HashMap<u32, MrValue>,
+    proc_type:           MrDataType
+}
+
+type ProcFn = Box<dyn FnMut(MrubyType, Vec<Value>) -> Value>;
+
+// methods defined with def_method & co., by class key and method symbol
+type MethodTable<K> = HashMap<K, HashMap<u32, Rc<dyn Fn(MrubyType, Value) -> Value>>>;
+
+// FNV-1a, much cheaper than SipHash on the short method names interned in hot loops
+struct FnvHasher(u64);
+
+impl Default for FnvHasher {
+    fn default() -> FnvHasher {
+        FnvHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x100_0000_01b3);
+        }
+    }
 }
 
 impl Mruby {
@@ -63,9 +101,17 @@ impl Mruby {
         unsafe {
             let mrb = mrb_open();
 
+            extern "C" fn free_proc(_mrb: *const MrState, ptr: *const u8) {
+                unsafe {
+                    drop(Box::from_raw(ptr as *mut RefCell<ProcFn>));
+                }
+            }
+
+            let proc_name = b"RustProc\0".as_ptr() as *const c_char;
+
             let mruby: MrubyType = Rc::new(RefCell::new(
                 Mruby {
-                    mrb:                 mrb,
+                    mrb,
                     ctx:                 mrbc_context_new(mrb),
                     filename:            None,
                     classes:             HashMap::new(),
@@ -74,7 +120,10 @@ impl Mruby {
                     mruby_methods:       HashMap::new(),
                     mruby_class_methods: HashMap::new(),
                     files:               HashMap::new(),
-                    required:            HashSet::new()
+                    required:            HashSet::new(),
+                    symbols:             HashMap::default(),
+                    class_cache:         HashMap::new(),
+                    proc_type:           mrb_ext_data_type(proc_name, free_proc)
                 }
             ));
 
@@ -86,11 +135,11 @@ impl Mruby {
                     let ptr = mrb_ext_get_ud(mrb);
                     let mruby: MrubyType = mem::transmute(ptr);
 
-                    let name = mem::MaybeUninit::<*const c_char>::uninit().assume_init();
+                    let mut name: *const c_char = ptr::null();
 
                     let sig_str = CString::new("z").unwrap();
 
-                    mrb_get_args(mrb, sig_str.as_ptr(), &name as *const *const c_char);
+                    mrb_get_args(mrb, sig_str.as_ptr(), &mut name as *mut *const c_char);
 
                     let name = CStr::from_ptr(name).to_str().unwrap();
 
@@ -98,79 +147,80 @@ impl Mruby {
                         mruby.borrow().required.contains(name)
                     };
 
-                    let result = if already_required {
-                        mruby.bool(false)
-                    } else {
-                        let reqs = {
-                            let borrow = mruby.borrow();
+                    let result = MrValue::catch_panic(mrb, || {
+                        let result = if already_required {
+                            mruby.bool(false)
+                        } else {
+                            let reqs = {
+                                let borrow = mruby.borrow();
 
-                            borrow.files.get(name).map(|reqs| reqs.clone())
-                        };
+                                borrow.files.get(name).cloned()
+                            };
 
-                        match reqs {
-                            Some(reqs) => {
-                                { mruby.borrow_mut().required.insert(name.to_owned()); }
+                            match reqs {
+                                Some(reqs) => {
+                                    { mruby.borrow_mut().required.insert(name.to_owned()); }
 
-                                for req in reqs {
-                                    req(mruby.clone());
-                                }
+                                    for req in reqs {
+                                        req(mruby.clone());
+                                    }
 
-                                mruby.bool(true)
-                            },
-                            None => {
-                                let filename = {
-                                    let borrow = mruby.borrow();
+                                    mruby.bool(true)
+                                },
+                                None => {
+                                    let filename = {
+                                        let borrow = mruby.borrow();
 
-                                    borrow.filename.clone()
-                                };
+                                        borrow.filename.clone()
+                                    };
 
-                                let execute = |path: &Path, name: String,
-                                               filename: Option<String>| {
-                                    { mruby.borrow_mut().required.insert(name); }
+                                    let execute = |path: &Path, name: String,
+                                                   filename: Option<String>| {
+                                        { mruby.borrow_mut().required.insert(name); }
 
-                                    let result = mruby.execute(path);
+                                        let result = mruby.execute(path);
 
-                                    match filename {
-                                        Some(filename) => mruby.filename(&filename),
-                                        None           => mruby.borrow_mut().filename = None
-                                    }
+                                        match filename {
+                                            Some(filename) => mruby.filename(&filename),
+                                            None           => mruby.borrow_mut().filename = None
+                                        }
 
-                                    match result {
-                                        Err(err) => {
-                                            Mruby::raise(mrb, "RuntimeError", &format!("{}", err));
+                                        if let Err(err) = result {
+                                            raise(mrb, "RuntimeError", &format!("{}", err));
                                         }
-                                        _ => ()
-                                    }
 
-                                    mruby.bool(true)
-                                };
-
-                                let path = Path::new(name);
-                                let rb = name.to_owned() + ".rb";
-                                let rb = Path::new(&rb);
-                                let mrbb = name.to_owned() + ".mrb";
-                                let mrbb = Path::new(&mrbb);
-
-                                if rb.is_file() {
-                                    execute(rb, name.to_owned(), filename)
-                                } else if mrbb.is_file() {
-                                    execute(mrbb, name.to_owned(), filename)
-                                } else if path.is_file() {
-                                    execute(path, name.to_owned(), filename)
-                                } else {
-                                    Mruby::raise(mrb, "RuntimeError",
-                                                 &format!("cannot load {}.rb or {}.mrb",
-                                                 name, name));
-
-                                    mruby.nil()
+                                        mruby.bool(true)
+                                    };
+
+                                    let path = Path::new(name);
+                                    let rb = name.to_owned() + ".rb";
+                                    let rb = Path::new(&rb);
+                                    let mrbb = name.to_owned() + ".mrb";
+                                    let mrbb = Path::new(&mrbb);
+
+                                    if rb.is_file() {
+                                        execute(rb, name.to_owned(), filename)
+                                    } else if mrbb.is_file() {
+                                        execute(mrbb, name.to_owned(), filename)
+                                    } else if path.is_file() {
+                                        execute(path, name.to_owned(), filename)
+                                    } else {
+                                        raise(mrb, "RuntimeError",
+                                                     &format!("cannot load {}.rb or {}.mrb",
+                                                     name, name));
+
+                                        mruby.nil()
+                                    }
                                 }
                             }
-                        }
-                    };
+                        };
+
+                        result.value
+                    });
 
                     mem::forget(mruby);
 
-                    result.value
+                    result
                 }
             }
 
@@ -184,7 +234,7 @@ impl Mruby {
             let mruby: MrubyType = mem::transmute(ptr);
 
             mruby.run_unchecked("
-              class RustPanic < StandardError
+              class RustPanic < RuntimeError
                 def initialize(message)
                   super message
                 end
@@ -195,18 +245,6 @@ impl Mruby {
         }
     }
 
-    #[inline]
-    fn raise(mrb: *const MrState, eclass: &str, message: &str) -> MrValue {
-        unsafe {
-            let eclass_str = CString::new(eclass).unwrap();
-            let message_str = CString::new(message).unwrap();
-
-            mrb_ext_raise_nothrow(mrb, eclass_str.as_ptr(), message_str.as_ptr());
-
-            MrValue::nil()
-        }
-    }
-
     fn close(&self) {
         unsafe {
             mrbc_context_free(self.mrb, self.ctx);
@@ -222,8 +260,28 @@ pub enum MrubyError {
     Cast(String),
     /// undefined type error
     Undef,
-    /// mruby runtime error
-    Runtime(String),
+    /// mruby runtime error, e.g. an exception raised by a script passed to `run`
+    Runtime {
+        class:     String,
+        message:   String,
+        backtrace: Vec<String>
+    },
+    /// mruby `Exception` raised by a method or proc call
+    Exception {
+        class:     String,
+        message:   String,
+        backtrace: Option<Vec<String>>
+    },
+    /// integer out of range error
+    Overflow(String),
+    /// missing block error of `Value::yield_block`, raised as a `LocalJumpError`
+    NoBlock,
+    /// `Cast` or `Overflow` error of an element nested in `Array`s, `Hash`es or tuples, with the
+    /// indices and inspected keys leading to it, outermost first
+    Nested {
+        path:  Vec<String>,
+        error: Box<MrubyError>
+    },
     /// unrecognized file type error
     Filetype,
     /// Rust `Io` error
@@ -239,8 +297,28 @@ impl fmt::Display for MrubyError {
             MrubyError::Undef => {
                 write!(f, "Undefined error: type is not defined")
             },
-            MrubyError::Runtime(ref err) => {
-                write!(f, "Runtime error: {}", err)
+            MrubyError::Runtime { ref class, ref message, .. } |
+            MrubyError::Exception { ref class, ref message, .. } => {
+                if message.is_empty() {
+                    write!(f, "Runtime error: {}", class)
+                } else {
+                    write!(f, "Runtime error: {} ({})", message, class)
+                }
+            },
+            MrubyError::Overflow(ref err) => {
+                write!(f, "Overflow error: {}", err)
+            },
+            MrubyError::NoBlock => {
+                write!(f, "No block error: no block given (yield)")
+            },
+            MrubyError::Nested { ref path, ref error } => {
+                write!(f, "{} at ", error)?;
+
+                for segment in path {
+                    write!(f, "[{}]", segment)?;
+                }
+
+                Ok(())
             },
             MrubyError::Filetype => {
                 write!(f, "Filetype error: script needs a compatible (.rb, .mrb) extension")
@@ -251,13 +329,21 @@ impl fmt::Display for MrubyError {
 }
 
 impl Error for MrubyError {
-    fn description(&self) -> &str {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
         match *self {
-            MrubyError::Cast(_)     => "mruby value cast error",
-            MrubyError::Undef       => "mruby undefined error",
-            MrubyError::Runtime(_)  => "mruby runtime error",
-            MrubyError::Filetype    => "filetype mistmatch",
-            MrubyError::Io(ref err) => err.description()
+            MrubyError::Io(ref err) => Some(err),
+            _                       => None
+        }
+    }
+}
+
+impl MrubyError {
+    /// Creates an `MrubyError::Runtime` raised as a plain `RuntimeError` without a backtrace.
+    pub(crate) fn runtime(message: String) -> MrubyError {
+        MrubyError::Runtime {
+            class:     "RuntimeError".to_owned(),
+            message,
+            backtrace: vec![]
         }
     }
 }
@@ -268,6 +354,157 @@ impl From<io::Error> for MrubyError {
     }
 }
 
+/// A `trait` for the return types of Rust methods declared with `-> Type` in `mrfn!`.
+///
+/// A `Value` is returned as is. The `Ok` of a `Result<T, E>` is converted with `ToValue`, while its
+/// `Err` is raised as the mruby exception picked by `E`'s `RaiseError` implementation.
+/// `MrubyError::Cast` raises an `ArgumentError`, `MrubyError::Overflow` a `RangeError`,
+/// `MrubyError::Undef` a `TypeError`, `MrubyError::NoBlock` a `LocalJumpError`,
+/// `MrubyError::Nested` the class of the error it wraps and the other variants a `RuntimeError`.
+///
+/// Arguments and `slf` that fail to convert to their declared types are raised the same way.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate mrusty;
+/// use mrusty::{Mruby, MrubyError, MrubyImpl, Value};
+///
+/// # fn main() {
+/// let mruby = Mruby::new();
+///
+/// struct Cont;
+///
+/// mruby.def_class_for::<Cont>("Container");
+/// mruby.def_class_method_for::<Cont, _>("double", mrfn!(|mruby, _slf: Value, a: Value|
+///                                                       -> Result<Value, MrubyError> {
+///     Ok(mruby.fixnum(a.to_i32()? * 2))
+/// }));
+///
+/// let result = mruby.run("
+///   begin
+///     Container.double 'a'
+///   rescue ArgumentError => e
+///     e.message
+///   end
+/// ").unwrap();
+///
+/// assert_eq!(result.to_str().unwrap(), "Cast error: expected Fixnum");
+/// # }
+/// ```
+pub trait ReturnValue {
+    fn into_return_value(self, mruby: &MrubyType) -> Value;
+}
+
+impl ReturnValue for Value {
+    #[inline]
+    fn into_return_value(self, _mruby: &MrubyType) -> Value {
+        self
+    }
+}
+
+impl<T: ToValue, E: RaiseError> ReturnValue for Result<T, E> {
+    fn into_return_value(self, mruby: &MrubyType) -> Value {
+        match self {
+            Ok(value) => value.to_value(mruby),
+            Err(err)  => {
+                let mrb = mruby.borrow().mrb;
+
+                Value::new(mruby.clone(),
+                           raise(mrb, err.exception_class(), &err.exception_message()))
+            }
+        }
+    }
+}
+
+/// A `trait` mapping the `Err` of a `Result` returned by an `mrfn!` method to the mruby exception
+/// it raises. The error's `Display` output is used as message by default.
+///
+/// An `MrubyError` coming from an mruby exception re-raises that exception with its original
+/// class and message.
+///
+/// Implement it for your own error type to pick a different exception class.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate mrusty;
+/// use std::fmt;
+///
+/// use mrusty::{Mruby, MrubyImpl, RaiseError, Value};
+///
+/// # fn main() {
+/// struct Missing(String);
+///
+/// impl fmt::Display for Missing {
+///     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+///         write!(f, "key not found: {}", self.0)
+///     }
+/// }
+///
+/// impl RaiseError for Missing {
+///     fn exception_class(&self) -> &str {
+///         "KeyError"
+///     }
+/// }
+///
+/// let mruby = Mruby::new();
+///
+/// struct Cont;
+///
+/// mruby.def_class_for::<Cont>("Container");
+/// mruby.def_class_method_for::<Cont, _>("fetch", mrfn!(|_mruby, _slf: Value, key: (&str)|
+///                                                      -> Result<Value, Missing> {
+///     Err(Missing(key.to_owned()))
+/// }));
+///
+/// let result = mruby.run("
+///   begin
+///     Container.fetch 'a'
+///   rescue KeyError => e
+///     e.message
+///   end
+/// ").unwrap();
+///
+/// assert_eq!(result.to_str().unwrap(), "key not found: a");
+/// # }
+/// ```
+pub trait RaiseError: fmt::Display {
+    /// Returns the name of the mruby exception class to raise. Defaults to `RuntimeError`.
+    fn exception_class(&self) -> &str {
+        "RuntimeError"
+    }
+
+    /// Returns the message of the exception to raise. Defaults to the error's `Display` output.
+    fn exception_message(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl RaiseError for MrubyError {
+    fn exception_class(&self) -> &str {
+        match *self {
+            MrubyError::Cast(_)          => "ArgumentError",
+            MrubyError::Overflow(_)      => "RangeError",
+            MrubyError::Undef            => "TypeError",
+            MrubyError::NoBlock          => "LocalJumpError",
+            MrubyError::Nested { ref error, .. } => error.exception_class(),
+            MrubyError::Runtime { ref class, .. }   |
+            MrubyError::Exception { ref class, .. } => class,
+            MrubyError::Filetype         |
+            MrubyError::Io(_)            => "RuntimeError"
+        }
+    }
+
+    fn exception_message(&self) -> String {
+        match *self {
+            MrubyError::Runtime { ref message, .. }   |
+            MrubyError::Exception { ref message, .. } => message.clone(),
+            _ => self.to_string()
+        }
+    }
+}
+
 /// A `trait` useful for organising Rust types into dynamic mruby files.
 ///
 /// # Examples
@@ -311,8 +548,9 @@ pub trait MrubyImpl {
     /// let result = mruby.run("1.nope");
     ///
     /// match result {
-    ///     Err(MrubyError::Runtime(err)) => {
-    ///         assert_eq!(err, "undefined method \'nope\' (NoMethodError)");
+    ///     Err(MrubyError::Runtime { class, message, .. }) => {
+    ///         assert_eq!(class, "NoMethodError");
+    ///         assert_eq!(message, "undefined method \'nope\'");
     /// },
     ///     _ => assert!(false)
     /// }
@@ -320,7 +558,8 @@ pub trait MrubyImpl {
     fn filename(&self, filename: &str);
 
     /// Runs mruby `script` on a state and context and returns a `Value` in an `Ok`
-    /// or an `Err` containing an mruby `Exception`'s message.
+    /// or an `Err` containing an `MrubyError::Runtime` with the raised exception's class,
+    /// message and backtrace.
     ///
     /// # Examples
     ///
@@ -341,8 +580,9 @@ pub trait MrubyImpl {
     /// let result = mruby.run("'' + 1");
     ///
     /// match result {
-    ///     Err(MrubyError::Runtime(err)) => {
-    ///         assert_eq!(err, "Fixnum cannot be converted to String (TypeError)");
+    ///     Err(MrubyError::Runtime { class, message, .. }) => {
+    ///         assert_eq!(class, "TypeError");
+    ///         assert_eq!(message, "Fixnum cannot be converted to String");
     /// },
     ///     _ => assert!(false)
     /// }
@@ -352,9 +592,9 @@ pub trait MrubyImpl {
     /// Runs mruby `script` on a state and context and returns a `Value`. If an mruby Exception is
     /// raised, mruby will be left to handle it.
     ///
-    /// The method is unsafe because running it within a Rust context will interrupt drops,
-    /// potentially leading to memory leaks.
+    /// # Safety
     ///
+    /// Running it within a Rust context will interrupt drops, potentially leading to memory leaks.
     ///
     /// # Examples
     ///
@@ -452,7 +692,10 @@ pub trait MrubyImpl {
     /// ```
     fn is_defined_under<T: ClassLike>(&self, name: &str, outer: &T) -> bool;
 
-    /// Returns the mruby `Class` named `name` in a `Some` or `None` if it is not defined.
+    /// Returns the mruby `Class` named `name` in a `Some` or `None` if it is not defined or not a
+    /// `Class`. Found `Class`es are cached by name and kept from the GC until
+    /// `clear_class_cache` is called; a cached `Class` is only returned while the constant
+    /// `name` still refers to it.
     ///
     /// # Examples
     ///
@@ -489,7 +732,8 @@ pub trait MrubyImpl {
     /// ```
     fn get_class_under<T: ClassLike>(&self, name: &str, outer: &T) -> Result<Class, MrubyError>;
 
-    /// Returns the mruby `Module` named `name` in a `Some` or `None` if it is not defined.
+    /// Returns the mruby `Module` named `name` in a `Some` or `None` if it is not defined or not a
+    /// `Module`. Found `Module`s share the cache of `get_class`.
     ///
     /// # Examples
     ///
@@ -524,6 +768,24 @@ pub trait MrubyImpl {
     /// ```
     fn get_module_under<T: ClassLike>(&self, name: &str, outer: &T) -> Result<Module, MrubyError>;
 
+    /// Forgets the `Class`es and `Module`s cached by `get_class` and `get_module`, letting the GC
+    /// collect them once they are no longer referenced.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.run("Shape = Class.new").unwrap();
+    /// let shape = mruby.get_class("Shape").unwrap();
+    ///
+    /// mruby.clear_class_cache();
+    /// assert_eq!(mruby.get_class("Shape").unwrap(), shape);
+    /// ```
+    fn clear_class_cache(&self);
+
     /// Defines a dynamic file that can be `require`d containing the Rust type `T` and runs its
     /// `MrubyFile`-inherited `require` method.
     ///
@@ -636,6 +898,62 @@ pub trait MrubyImpl {
     /// ```
     fn def_class_under_for<T: Any, U: ClassLike>(&self, name: &str, outer: &U) -> Class;
 
+    /// Defines an mruby exception `Class` named `name` inheriting from `superclass` or from
+    /// `StandardError` when `superclass` is `None`. Returns the `TypeError` raised by mruby if
+    /// `name` is already bound to something other than a `Class` with the same superclass.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let config_error = mruby.def_exception("ConfigError", None).unwrap();
+    /// mruby.def_exception("MissingKeyError", Some(config_error)).unwrap();
+    ///
+    /// let result = mruby.run("MissingKeyError.ancestors.include?(StandardError)").unwrap();
+    ///
+    /// assert!(result.to_bool().unwrap());
+    /// ```
+    fn def_exception(&self, name: &str, superclass: Option<Class>) -> Result<Class, MrubyError>;
+
+    /// Raises an instance of exception `class` with `message` from a Rust method. The returned
+    /// `nil` `Value` should be returned from the method right away.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate mrusty;
+    /// use mrusty::{Mruby, MrubyImpl, Value};
+    ///
+    /// # fn main() {
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.def_exception("ConfigError", None).unwrap();
+    ///
+    /// struct Config;
+    ///
+    /// mruby.def_class_for::<Config>("Config");
+    /// mruby.def_class_method_for::<Config, _>("load", mrfn!(|mruby, _slf: Value| {
+    ///     let class = mruby.get_class("ConfigError").unwrap();
+    ///
+    ///     mruby.raise(class, "config.yml is missing")
+    /// }));
+    ///
+    /// let result = mruby.run("
+    ///   begin
+    ///     Config.load
+    ///   rescue ConfigError => e
+    ///     e.message
+    ///   end
+    /// ").unwrap();
+    ///
+    /// assert_eq!(result.to_str().unwrap(), "config.yml is missing");
+    /// # }
+    /// ```
+    fn raise(&self, class: Class, message: &str) -> Value;
+
     /// Defines an mruby `Module` named `name`.
     ///
     /// # Examples
@@ -848,6 +1166,40 @@ pub trait MrubyImpl {
     /// ```
     fn fixnum(&self, value: i32) -> Value;
 
+    /// Creates mruby `Value` of `Class` `Fixnum` from an `i64`, preserving the full range of
+    /// mruby's 64-bit integers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let fixn = mruby.fixnum_i64(1 << 40);
+    ///
+    /// assert_eq!(fixn.to_i64().unwrap(), 1 << 40);
+    /// assert!(fixn.to_i32().is_err());
+    /// ```
+    fn fixnum_i64(&self, value: i64) -> Value;
+
+    /// Creates mruby `Value` of `Class` `Fixnum` from a `usize`, returning an `Err` if it does
+    /// not fit in mruby's integer type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let fixn = mruby.fixnum_from_usize(3).unwrap();
+    ///
+    /// assert_eq!(fixn.to_usize().unwrap(), 3);
+    /// assert!(mruby.fixnum_from_usize(usize::max_value()).is_err());
+    /// ```
+    fn fixnum_from_usize(&self, value: usize) -> Result<Value, MrubyError>;
+
     /// Creates mruby `Value` of `Class` `Float`.
     ///
     /// # Examples
@@ -876,6 +1228,53 @@ pub trait MrubyImpl {
     /// ```
     fn string(&self, value: &str) -> Value;
 
+    /// Creates mruby `Value` of `Class` `String` from raw bytes which do not need to be UTF-8.
+    ///
+    /// # Examples
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let s = mruby.bytes(b"\xff\0hi");
+    ///
+    /// assert_eq!(s.to_bytes().unwrap(), b"\xff\0hi");
+    /// ```
+    fn bytes(&self, value: &[u8]) -> Value;
+
+    /// Creates mruby `Value` of `Class` `String` from a path. On Unix the raw bytes of the path are
+    /// used, so non-UTF-8 paths survive; elsewhere invalid UTF-8 is replaced with `U+FFFD`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// use std::path::Path;
+    ///
+    /// let mruby = Mruby::new();
+    ///
+    /// let path = mruby.path(Path::new("scripts/main.rb"));
+    ///
+    /// assert_eq!(path.to_str().unwrap(), "scripts/main.rb");
+    /// assert_eq!(path.to_path().unwrap(), Path::new("scripts/main.rb"));
+    /// ```
+    fn path(&self, value: &Path) -> Value;
+
+    /// Creates mruby `Value` of `Class` `String` containing a single `char`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let c = mruby.char('é');
+    ///
+    /// assert_eq!(c.to_str().unwrap(), "é");
+    /// assert_eq!(c.to_char().unwrap(), 'é');
+    /// ```
+    fn char(&self, value: char) -> Value;
+
     /// Creates mruby `Value` of `Class` `Symbol`.
     ///
     /// # Examples
@@ -887,6 +1286,7 @@ pub trait MrubyImpl {
     /// let s = mruby.symbol("hi");
     ///
     /// assert_eq!(s.to_str().unwrap(), "hi");
+    /// assert_eq!(s.to_sym().unwrap().to_str(), "hi");
     /// ```
     fn symbol(&self, value: &str) -> Value;
 
@@ -940,6 +1340,22 @@ pub trait MrubyImpl {
     /// ```
     fn option<T: Any>(&self, obj: Option<T>) -> Value;
 
+    /// Creates mruby `Value` from any Rust type implementing `ToValue`. `None` becomes `nil`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.obj_from(Some(3)).to_i32().unwrap(), 3);
+    /// assert_eq!(mruby.obj_from(None::<i32>), mruby.nil());
+    /// assert_eq!(mruby.obj_from(vec![Some("a"), None]).to_vec().unwrap(),
+    ///            vec![mruby.string("a"), mruby.nil()]);
+    /// ```
+    fn obj_from<T: ToValue>(&self, value: T) -> Value;
+
     /// Creates mruby `Value` of `Class` `Array`.
     ///
     /// # Examples
@@ -961,20 +1377,351 @@ pub trait MrubyImpl {
     /// ]);
     /// ```
     fn array(&self, value: Vec<Value>) -> Value;
-}
 
-#[inline]
-fn get_class<F>(mruby: &MrubyType, name: &str, class: Result<Class, MrubyError>, get: F) -> Class
-    where F: Fn(*const MrState, *const c_char, *const MrClass) -> *const MrClass {
+    /// Creates mruby `Value` of `Class` `Array` by filling it straight from an iterator, without
+    /// collecting into a `Vec` first.
+    ///
+    /// # Examples
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let array = mruby.array_from_iter((1..4).map(|i| mruby.fixnum(i * i)));
+    ///
+    /// assert_eq!(array.to_vec().unwrap(), vec![
+    ///     mruby.fixnum(1),
+    ///     mruby.fixnum(4),
+    ///     mruby.fixnum(9)
+    /// ]);
+    /// ```
+    fn array_from_iter<I: IntoIterator<Item = Value>>(&self, iter: I) -> Value;
 
-    unsafe {
-        let class = if let Ok(class) = class {
-            class
-        } else {
-            let name = name.to_owned();
+    /// Creates mruby `Value` of `Class` `Hash`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let hash = mruby.hash(vec![
+    ///     (mruby.symbol("one"), mruby.fixnum(1)),
+    ///     (mruby.symbol("two"), mruby.fixnum(2))
+    /// ]);
+    ///
+    /// assert_eq!(hash.to_hash().unwrap(), vec![
+    ///     (mruby.symbol("one"), mruby.fixnum(1)),
+    ///     (mruby.symbol("two"), mruby.fixnum(2))
+    /// ]);
+    /// ```
+    fn hash(&self, value: Vec<(Value, Value)>) -> Value;
 
-            let c_name = CString::new(name.clone()).unwrap();
-            let object = CString::new("Object").unwrap();
+    /// Creates mruby `Value` of `Class` `Hash` by inserting the pairs of an iterator in order, so
+    /// the `Hash` iterates in the same order as `pairs`. A repeated key keeps its first position
+    /// and its last value.
+    ///
+    /// # Examples
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let hash = mruby.hash_from_pairs(vec!["zeta", "alpha", "mu"].into_iter().enumerate()
+    ///     .map(|(i, key)| (mruby.string(key), mruby.fixnum(i as i32))));
+    ///
+    /// assert_eq!(hash.to_s(), "{\"zeta\"=>0, \"alpha\"=>1, \"mu\"=>2}");
+    /// ```
+    fn hash_from_pairs<I: IntoIterator<Item = (Value, Value)>>(&self, pairs: I) -> Value;
+
+    /// Creates mruby `Value` of `Class` `Range`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let range = mruby.range(mruby.fixnum(1), mruby.fixnum(3), false);
+    ///
+    /// assert_eq!(range.call("to_a", vec![]).unwrap().to_vec().unwrap(), vec![
+    ///     mruby.fixnum(1),
+    ///     mruby.fixnum(2),
+    ///     mruby.fixnum(3)
+    /// ]);
+    /// ```
+    fn range(&self, start: Value, end: Value, exclusive: bool) -> Value;
+
+    /// Creates mruby `Value` of `Class` `Time` with `Time.at`, truncating `time` to microseconds.
+    /// Returns `MrubyError::Runtime` if mruby was built without the mruby-time gem.
+    ///
+    /// Only available with the `time` feature.
+    ///
+    /// # Examples
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let mruby = Mruby::new();
+    ///
+    /// let time = mruby.time(UNIX_EPOCH + Duration::from_secs(60)).unwrap();
+    ///
+    /// assert_eq!(time.call("to_i", vec![]).unwrap().to_i32().unwrap(), 60);
+    /// ```
+    #[cfg(feature = "time")]
+    fn time(&self, time: SystemTime) -> Result<Value, MrubyError>;
+
+    /// Creates mruby `Value` of `Class` `Range` with `Fixnum` ends from a Rust range.
+    ///
+    /// # Examples
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let exclusive = mruby.int_range(1..10);
+    /// let inclusive = mruby.int_range(1..=10);
+    ///
+    /// assert_eq!(exclusive.call("size", vec![]).unwrap().to_i32().unwrap(), 9);
+    /// assert_eq!(inclusive.call("size", vec![]).unwrap().to_i32().unwrap(), 10);
+    /// ```
+    fn int_range<R: Into<IntRange>>(&self, range: R) -> Value;
+
+    /// Creates mruby `Value` of `Class` `Proc` from a Rust closure. The closure receives the
+    /// arguments the `Proc` is called with.
+    ///
+    /// The closure is owned by the `Proc` and is dropped when the `Proc` is garbage collected or
+    /// when `Mruby` is dropped. It can therefore outlive the call it was passed to, e.g. when the
+    /// block gets stored by the callee. Use the `MrubyType` argument instead of capturing a clone
+    /// of `Mruby`, which would create a reference cycle. Calling the `Proc` again while its
+    /// closure is running, e.g. from mruby code the closure runs, raises a `RuntimeError`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let block = mruby.proc(|mruby, args| {
+    ///     mruby.fixnum(args[0].to_i32().unwrap() + args[1].to_i32().unwrap())
+    /// });
+    ///
+    /// let array = mruby.run("[1, 2, 3]").unwrap();
+    /// let sum = array.call_with_block("inject", vec![], block).unwrap();
+    ///
+    /// assert_eq!(sum.to_i32().unwrap(), 6);
+    /// ```
+    fn proc<F>(&self, block: F) -> Value
+        where F: FnMut(MrubyType, Vec<Value>) -> Value + 'static;
+
+    /// Runs a full garbage collection, dropping Rust objects that are no longer referenced by
+    /// mruby.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.run("100.times { 'garbage' }").unwrap();
+    /// mruby.full_gc();
+    /// ```
+    fn full_gc(&self);
+
+    /// Runs one incremental garbage collection step.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.run("100.times { 'garbage' }").unwrap();
+    /// mruby.incremental_gc();
+    /// ```
+    fn incremental_gc(&self);
+
+    /// Enables garbage collection, returning `true` if it was previously disabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.gc_disable();
+    ///
+    /// assert!(mruby.gc_enable());
+    /// assert!(!mruby.gc_enable());
+    /// ```
+    fn gc_enable(&self) -> bool;
+
+    /// Disables garbage collection, returning `true` if it was already disabled. Allocations made
+    /// while disabled are only collected after [`gc_enable`](#tymethod.gc_enable).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert!(!mruby.gc_disable());
+    ///
+    /// mruby.run("1000.times { 'batch' }").unwrap();
+    ///
+    /// assert!(mruby.gc_disable());
+    /// assert!(mruby.run("GC.enable").unwrap().to_bool().unwrap());
+    /// ```
+    fn gc_disable(&self) -> bool;
+
+    /// Returns an `ArenaGuard` which saves the GC arena index and restores it when dropped.
+    ///
+    /// Every `Value` created from Rust is kept alive by the GC arena until the arena index is
+    /// restored. Long-running loops that create temporary `Value`s should hold a guard for each
+    /// iteration so that the arena does not keep growing.
+    ///
+    /// # Safety
+    ///
+    /// `Value`s created while the guard is alive are no longer protected once it is dropped and
+    /// must not be used afterwards, since the GC is free to collect them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// for i in 0..1000 {
+    ///     let _guard = unsafe { mruby.arena_guard() };
+    ///
+    ///     mruby.string(&i.to_string());
+    /// }
+    /// ```
+    unsafe fn arena_guard(&self) -> ArenaGuard;
+
+    /// Interns `name` and returns a `Symbol` handle which can be passed to
+    /// [`call_sym`](../mrusty/struct.Value.html#method.call_sym) and friends without interning
+    /// it again. Handles are cached per `Mruby` and must not be used with another `Mruby`. Cache
+    /// hits share the cached name instead of allocating a new one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let plus = mruby.intern("+");
+    /// let mut sum = mruby.fixnum(0);
+    ///
+    /// for i in 1..5 {
+    ///     sum = sum.call_sym(&plus, vec![mruby.fixnum(i)]).unwrap();
+    /// }
+    ///
+    /// assert_eq!(sum.to_i32().unwrap(), 10);
+    /// assert_eq!(plus, mruby.symbol("+").to_sym().unwrap());
+    /// ```
+    fn intern(&self, name: &str) -> Symbol;
+
+    /// Returns the value of the global variable `name` or `nil` if it is not defined.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.run("$answer = 42").unwrap();
+    ///
+    /// assert_eq!(mruby.get_gv("$answer").to_i32().unwrap(), 42);
+    /// assert!(mruby.get_gv("$question").is_nil());
+    /// ```
+    fn get_gv(&self, name: &str) -> Value;
+
+    /// Returns the value of top-level constant `name` in a `Some` or `None` if it is not defined.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.run("VERSION = '2.1'").unwrap();
+    ///
+    /// assert_eq!(mruby.get_const("VERSION").unwrap().to_str().unwrap(), "2.1");
+    /// assert!(mruby.get_const("RELEASE").is_none());
+    /// ```
+    fn get_const(&self, name: &str) -> Option<Value>;
+
+    /// Sets the value of the global variable `name` to `value`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.set_gv("$config", mruby.string("debug"));
+    ///
+    /// let result = mruby.run("$config == 'debug'").unwrap();
+    ///
+    /// assert!(result.to_bool().unwrap());
+    /// ```
+    fn set_gv(&self, name: &str, value: Value);
+}
+
+// looks up the top-level constant `name` of type `typ`, caching it by symbol; a cached value is
+// rooted with mrb_gc_register and checked against the constant in case it was reassigned
+fn const_class_get(mruby: &MrubyType, name: &str, typ: MrType) -> *const MrClass {
+    let sym = mruby.intern(name);
+    let mrb = mruby.borrow().mrb;
+
+    unsafe {
+        let cached = mruby.borrow().class_cache.get(&sym.id).cloned();
+
+        if let Some(value) = cached {
+            if value.typ() == typ && mrb_ext_const_is(mrb, sym.id, value) {
+                return mrb_ext_class_ptr(value);
+            }
+        }
+
+        let class = mrb_ext_const_class_get(mrb, sym.id, typ);
+
+        if !class.is_null() {
+            let value = mrb_ext_class_value(class);
+
+            mrb_gc_register(mrb, value);
+
+            if let Some(old) = mruby.borrow_mut().class_cache.insert(sym.id, value) {
+                mrb_gc_unregister(mrb, old);
+            }
+        }
+
+        class
+    }
+}
+
+#[inline]
+fn get_class<F>(mruby: &MrubyType, name: &str, class: Result<Class, MrubyError>, get: F) -> Class
+    where F: Fn(*const MrState, *const c_char, *const MrClass) -> *const MrClass {
+
+    unsafe {
+        let class = if let Ok(class) = class {
+            class
+        } else {
+            let name = name.to_owned();
+
+            let c_name = CString::new(name.clone()).unwrap();
+            let object = CString::new("Object").unwrap();
             let object = mrb_class_get(mruby.borrow().mrb, object.as_ptr());
 
             let class = get(mruby.borrow().mrb, c_name.as_ptr(), object);
@@ -1005,13 +1752,7 @@ fn get_class_for<T: Any, F>(mruby: &MrubyType, name: &str, get: F) -> Class
 
         mrb_ext_set_instance_tt(class, MrType::MRB_TT_DATA);
 
-        extern "C" fn free<T>(_mrb: *const MrState, ptr: *const u8) {
-            unsafe {
-                mem::transmute::<*const u8, Rc<RefCell<T>>>(ptr);
-            }
-        }
-
-        let data_type = mrb_ext_data_type(c_name.as_ptr(), free::<T>);
+        let data_type = mrb_ext_data_type(c_name.as_ptr(), free_rc::<T>);
 
         mruby.borrow_mut().classes.insert(TypeId::of::<T>(), (class, Rc::new(data_type), name, c_name));
         mruby.borrow_mut().methods.insert(TypeId::of::<T>(), HashMap::new());
@@ -1030,11 +1771,7 @@ fn get_class_for<T: Any, F>(mruby: &MrubyType, name: &str, get: F) -> Class
 macro_rules! insert_method {
     ( $mruby:expr, $name:expr, $method:expr, $methods:ident, $key:expr ) => {
         {
-            let sym = unsafe {
-                let name_str = CString::new($name).unwrap();
-
-                mrb_intern($mruby.borrow().mrb, name_str.as_ptr(), $name.len())
-            };
+            let sym = $mruby.intern($name).id;
 
             let mut borrow = $mruby.borrow_mut();
 
@@ -1069,23 +1806,9 @@ macro_rules! callback {
                     };
 
                     if let Some(Some(method)) = method {
-                        match panic::catch_unwind(AssertUnwindSafe(|| method(mruby.clone(),
-                                                                             value).value)) {
-                            Ok(value)  => value,
-                            Err(error) => {
-                                let message = match error.downcast_ref::<&'static str>() {
-                                    Some(s) => *s,
-                                    None    => match error.downcast_ref::<String>() {
-                                        Some(s) => &s[..],
-                                        None    => ""
-                                    }
-                                };
-
-                                Mruby::raise(mrb, "RustPanic", message)
-                            }
-                        }
+                        MrValue::catch_panic(mrb, || method(mruby.clone(), value).value)
                     } else {
-                        Mruby::raise(mrb, "TypeError", "Class not found.")
+                        raise(mrb, "TypeError", "Class not found.")
                     }
                 };
 
@@ -1120,23 +1843,9 @@ macro_rules! mruby_callback {
                     };
 
                     if let Some(Some(method)) = method {
-                        match panic::catch_unwind(AssertUnwindSafe(|| method(mruby.clone(),
-                                                                             value).value)) {
-                            Ok(value)  => value,
-                            Err(error) => {
-                                let message = match error.downcast_ref::<&'static str>() {
-                                    Some(s) => *s,
-                                    None    => match error.downcast_ref::<String>() {
-                                        Some(s) => &s[..],
-                                        None    => ""
-                                    }
-                                };
-
-                                Mruby::raise(mrb, "RustPanic", message)
-                            }
-                        }
+                        MrValue::catch_panic(mrb, || method(mruby.clone(), value).value)
                     } else {
-                        Mruby::raise(mrb, "TypeError", "Class not found.")
+                        raise(mrb, "TypeError", "Class not found.")
                     }
                 };
 
@@ -1167,7 +1876,7 @@ impl MrubyImpl for MrubyType {
                 let ptr = data.to_ptr().unwrap();
                 let args = *mem::transmute::<*const u8, *const [*const u8; 3]>(ptr);
 
-                let script_len: &usize = mem::transmute(args[1]);
+                let script_len = &*(args[1] as *const usize);
                 let ctx: *const MrContext = mem::transmute(args[2]);
 
                 let result = mrb_ext_load_nstring_cxt_nothrow(mrb, args[0], *script_len, ctx);
@@ -1187,21 +1896,19 @@ impl MrubyImpl for MrubyType {
 
             let script_ptr = script.as_ptr();
             let script_len = script.len();
-            let script_len_ptr: *const u8 = mem::transmute(&script_len);
+            let script_len_ptr = &script_len as *const usize as *const u8;
             let ctx_ptr: *const u8 = mem::transmute(ctx);
 
             let args = [script_ptr, script_len_ptr, ctx_ptr];
-            let args_ptr: *const u8 = mem::transmute(&args);
+            let args_ptr = &args as *const [*const u8; 3] as *const u8;
             let data = MrValue::ptr(mrb, args_ptr);
 
-            let state = mem::MaybeUninit::<bool>::zeroed().assume_init();
+            let mut state = false;
 
-            let value = mrb_protect(mrb, run_protected, data, &state as *const bool);
+            let value = mrb_protect(mrb, run_protected, data, &mut state as *const bool);
 
             if state {
-                let str = mrb_ext_exc_str(mrb, value).to_str(mrb).unwrap();
-
-                Err(MrubyError::Runtime(str.to_owned()))
+                Err(runtime(mrb, value))
             } else {
                 Ok(Value::new(self.clone(), value))
             }
@@ -1249,17 +1956,15 @@ impl MrubyImpl for MrubyType {
             let ctx_ptr: *const u8 = mem::transmute(ctx);
 
             let args = [script_ptr, ctx_ptr];
-            let args_ptr: *const u8 = mem::transmute(&args);
+            let args_ptr = &args as *const [*const u8; 2] as *const u8;
             let data = MrValue::ptr(mrb, args_ptr);
 
-            let state = mem::MaybeUninit::<bool>::zeroed().assume_init();
+            let mut state = false;
 
-            let value = mrb_protect(mrb, runb_protected, data, &state as *const bool);
+            let value = mrb_protect(mrb, runb_protected, data, &mut state as *const bool);
 
             if state {
-                let str = mrb_ext_exc_str(mrb, value).to_str(mrb).unwrap();
-
-                Err(MrubyError::Runtime(str.to_owned()))
+                Err(runtime(mrb, value))
             } else {
                 Ok(Value::new(self.clone(), value))
             }
@@ -1316,16 +2021,12 @@ impl MrubyImpl for MrubyType {
 
     #[inline]
     fn get_class(&self, name: &str) -> Result<Class, MrubyError> {
-        unsafe {
-            let name_str = CString::new(name).unwrap();
+        let class = const_class_get(self, name, MrType::MRB_TT_CLASS);
 
-            if mrb_class_defined(self.borrow().mrb, name_str.as_ptr()) {
-                let class = mrb_class_get(self.borrow().mrb, name_str.as_ptr());
-
-                Ok(Class::new(self.clone(), class))
-            } else {
-                Err(MrubyError::Undef)
-            }
+        if class.is_null() {
+            Err(MrubyError::Undef)
+        } else {
+            Ok(Class::new(self.clone(), class))
         }
     }
 
@@ -1347,16 +2048,12 @@ impl MrubyImpl for MrubyType {
 
     #[inline]
     fn get_module(&self, name: &str) -> Result<Module, MrubyError> {
-        unsafe {
-            let name_str = CString::new(name).unwrap();
+        let module = const_class_get(self, name, MrType::MRB_TT_MODULE);
 
-            if mrb_class_defined(self.borrow().mrb, name_str.as_ptr()) {
-                let class = mrb_module_get(self.borrow().mrb, name_str.as_ptr());
-
-                Ok(Module::new(self.clone(), class))
-            } else {
-                Err(MrubyError::Undef)
-            }
+        if module.is_null() {
+            Err(MrubyError::Undef)
+        } else {
+            Ok(Module::new(self.clone(), module))
         }
     }
 
@@ -1376,6 +2073,17 @@ impl MrubyImpl for MrubyType {
         }
     }
 
+    fn clear_class_cache(&self) {
+        let mrb = self.borrow().mrb;
+        let cache = mem::take(&mut self.borrow_mut().class_cache);
+
+        for (_, value) in cache {
+            unsafe {
+                mrb_gc_unregister(mrb, value);
+            }
+        }
+    }
+
     fn def_file<T: MrubyFile>(&self, name: &str) {
         let mut borrow = self.borrow_mut();
 
@@ -1417,6 +2125,33 @@ impl MrubyImpl for MrubyType {
         })
     }
 
+    fn def_exception(&self, name: &str, superclass: Option<Class>) -> Result<Class, MrubyError> {
+        let superclass = match superclass {
+            Some(superclass) => superclass,
+            None             => self.get_class("StandardError")?
+        };
+
+        unsafe {
+            let class = MrubyClass::new(self.borrow().mrb, superclass.class())
+                .define_subclass(name)?;
+
+            Ok(Class::new(self.clone(), class.as_ptr()))
+        }
+    }
+
+    fn raise(&self, class: Class, message: &str) -> Value {
+        unsafe {
+            let mrb = self.borrow().mrb;
+
+            let exc = mrb_exc_new(mrb, class.class(), message.as_ptr() as *const c_char,
+                                  message.len());
+
+            mrb_ext_exc_raise_nothrow(mrb, exc);
+
+            self.nil()
+        }
+    }
+
     fn def_module(&self, name: &str) -> Module {
         unsafe {
             let name_str = CString::new(name).unwrap();
@@ -1542,6 +2277,20 @@ impl MrubyImpl for MrubyType {
         }
     }
 
+    #[inline]
+    fn fixnum_i64(&self, value: i64) -> Value {
+        unsafe {
+            Value::new(self.clone(), MrValue::fixnum_i64(value))
+        }
+    }
+
+    #[inline]
+    fn fixnum_from_usize(&self, value: usize) -> Result<Value, MrubyError> {
+        unsafe {
+            Ok(Value::new(self.clone(), MrValue::fixnum_usize(value)?))
+        }
+    }
+
     #[inline]
     fn float(&self, value: f64) -> Value {
         unsafe {
@@ -1557,9 +2306,28 @@ impl MrubyImpl for MrubyType {
     }
 
     #[inline]
-    fn symbol(&self, value: &str) -> Value {
+    fn bytes(&self, value: &[u8]) -> Value {
         unsafe {
-            Value::new(self.clone(), MrValue::symbol(self.borrow().mrb, value))
+            Value::new(self.clone(), MrValue::bytes(self.borrow().mrb, value))
+        }
+    }
+
+    #[inline]
+    fn path(&self, value: &Path) -> Value {
+        self.bytes(&path_to_bytes(value))
+    }
+
+    #[inline]
+    fn char(&self, value: char) -> Value {
+        let mut buf = [0; 4];
+
+        self.string(value.encode_utf8(&mut buf))
+    }
+
+    #[inline]
+    fn symbol(&self, value: &str) -> Value {
+        unsafe {
+            Value::new(self.clone(), MrValue::symbol(self.borrow().mrb, value))
         }
     }
 
@@ -1573,7 +2341,7 @@ impl MrubyImpl for MrubyType {
         };
 
         unsafe {
-            Value::new(self.clone(), MrValue::obj(borrow.mrb, class.0 as *const MrClass, obj,
+            Value::new(self.clone(), MrValue::obj(borrow.mrb, class.0, obj,
                                                   &class.1))
         }
     }
@@ -1586,6 +2354,11 @@ impl MrubyImpl for MrubyType {
         }
     }
 
+    #[inline]
+    fn obj_from<T: ToValue>(&self, value: T) -> Value {
+        value.to_value(self)
+    }
+
     #[inline]
     fn array(&self, value: Vec<Value>) -> Value {
         let array: Vec<MrValue> = value.iter().map(|value| {
@@ -1596,6 +2369,210 @@ impl MrubyImpl for MrubyType {
             Value::new(self.clone(), MrValue::array(self.borrow().mrb, array))
         }
     }
+
+    #[inline]
+    fn array_from_iter<I: IntoIterator<Item = Value>>(&self, iter: I) -> Value {
+        let iter = iter.into_iter();
+
+        unsafe {
+            let mrb = self.borrow().mrb;
+            let array = mrb_ary_new_capa(mrb, iter.size_hint().0 as MrInt);
+
+            for (i, value) in iter.enumerate() {
+                mrb_ary_set(mrb, array, i as MrInt, value.value);
+            }
+
+            Value::new(self.clone(), array)
+        }
+    }
+
+    #[inline]
+    fn hash(&self, value: Vec<(Value, Value)>) -> Value {
+        let hash: Vec<(MrValue, MrValue)> = value.iter().map(|(key, value)| {
+            (key.value, value.value)
+        }).collect();
+
+        unsafe {
+            Value::new(self.clone(), MrValue::hash(self.borrow().mrb, hash))
+        }
+    }
+
+    fn hash_from_pairs<I: IntoIterator<Item = (Value, Value)>>(&self, pairs: I) -> Value {
+        let pairs = pairs.into_iter();
+
+        unsafe {
+            let mrb = self.borrow().mrb;
+            let hash = mrb_hash_new_capa(mrb, pairs.size_hint().0 as MrInt);
+
+            for (key, value) in pairs {
+                mrb_hash_set(mrb, hash, key.value, value.value);
+            }
+
+            Value::new(self.clone(), hash)
+        }
+    }
+
+    #[cfg(feature = "time")]
+    #[inline]
+    fn time(&self, time: SystemTime) -> Result<Value, MrubyError> {
+        super::time::time(self, time)
+    }
+
+    #[inline]
+    fn range(&self, start: Value, end: Value, exclusive: bool) -> Value {
+        unsafe {
+            Value::new(self.clone(), MrValue::range(self.borrow().mrb, start.value, end.value,
+                                                    exclusive))
+        }
+    }
+
+    #[inline]
+    fn int_range<R: Into<IntRange>>(&self, range: R) -> Value {
+        match range.into() {
+            IntRange::Exclusive(range) => {
+                self.range(self.fixnum_i64(range.start), self.fixnum_i64(range.end), true)
+            },
+            IntRange::Inclusive(range) => {
+                self.range(self.fixnum_i64(*range.start()), self.fixnum_i64(*range.end()), false)
+            }
+        }
+    }
+
+    #[inline]
+    fn full_gc(&self) {
+        unsafe {
+            mrb_full_gc(self.borrow().mrb);
+        }
+    }
+
+    #[inline]
+    fn incremental_gc(&self) {
+        unsafe {
+            mrb_incremental_gc(self.borrow().mrb);
+        }
+    }
+
+    #[inline]
+    fn gc_enable(&self) -> bool {
+        unsafe {
+            mrb_ext_gc_enable(self.borrow().mrb)
+        }
+    }
+
+    #[inline]
+    fn gc_disable(&self) -> bool {
+        unsafe {
+            mrb_ext_gc_disable(self.borrow().mrb)
+        }
+    }
+
+    fn intern(&self, name: &str) -> Symbol {
+        if let Some((name, &id)) = self.borrow().symbols.get_key_value(name) {
+            return Symbol {
+                name: name.clone(),
+                id
+            };
+        }
+
+        let id = unsafe {
+            mrb_intern(self.borrow().mrb, name.as_ptr() as *const c_char, name.len())
+        };
+        let name: Rc<str> = Rc::from(name);
+
+        self.borrow_mut().symbols.insert(name.clone(), id);
+
+        Symbol {
+            name,
+            id
+        }
+    }
+
+    #[inline]
+    fn get_gv(&self, name: &str) -> Value {
+        let sym = self.intern(name);
+
+        unsafe {
+            Value::new(self.clone(), mrb_gv_get(self.borrow().mrb, sym.id))
+        }
+    }
+
+    #[inline]
+    fn get_const(&self, name: &str) -> Option<Value> {
+        self.get_class("Object").unwrap().get_const(name)
+    }
+
+    #[inline]
+    fn set_gv(&self, name: &str, value: Value) {
+        let sym = self.intern(name);
+
+        unsafe {
+            mrb_gv_set(self.borrow().mrb, sym.id, value.value);
+        }
+    }
+
+    #[inline]
+    unsafe fn arena_guard(&self) -> ArenaGuard {
+        let idx = mrb_ext_gc_arena_save(self.borrow().mrb);
+
+        ArenaGuard {
+            mruby: self.clone(),
+            idx
+        }
+    }
+
+    #[inline]
+    fn proc<F>(&self, block: F) -> Value
+        where F: FnMut(MrubyType, Vec<Value>) -> Value + 'static {
+
+        extern "C" fn call_proc(mrb: *const MrState, _slf: MrValue) -> MrValue {
+            unsafe {
+                let ptr = mrb_ext_get_ud(mrb);
+                let mruby: MrubyType = mem::transmute(ptr);
+
+                let result = {
+                    let data = mrb_proc_cfunc_env_get(mrb, 0);
+                    let block = &*(mrb_ext_data_ptr(data) as *const RefCell<ProcFn>);
+
+                    let argc = mrb_get_argc(mrb) as usize;
+                    let args = if argc == 0 {
+                        vec![]
+                    } else {
+                        slice::from_raw_parts(mrb_get_argv(mrb), argc).iter().map(|value| {
+                            Value::new(mruby.clone(), *value)
+                        }).collect()
+                    };
+
+                    // a Proc calling itself, even through mruby, cannot borrow its closure again
+                    match block.try_borrow_mut() {
+                        Ok(mut block) => {
+                            MrValue::catch_panic(mrb, || block(mruby.clone(), args).value)
+                        }
+                        Err(_) => raise(mrb, "RuntimeError", "Proc called while already running")
+                    }
+                };
+
+                mem::forget(mruby);
+
+                result
+            }
+        }
+
+        unsafe {
+            let mrb = self.borrow().mrb;
+
+            let block: ProcFn = Box::new(block);
+            let ptr = Box::into_raw(Box::new(RefCell::new(block))) as *const u8;
+
+            let object_str = CString::new("Object").unwrap();
+            let object = mrb_class_get(mrb, object_str.as_ptr());
+
+            let data = mrb_data_object_alloc(mrb, object, ptr,
+                                             &self.borrow().proc_type as *const MrDataType);
+
+            Value::new(self.clone(), MrValue::proc(mrb, call_proc,
+                                                   vec![mrb_ext_data_value(data)]))
+        }
+    }
 }
 
 impl Drop for Mruby {
@@ -1611,11 +2588,18 @@ impl Drop for Mruby {
 /// * [`nil`](../mrusty/trait.MrubyImpl.html#tymethod.nil)
 /// * [`bool`](../mrusty/trait.MrubyImpl.html#tymethod.bool)
 /// * [`fixnum`](../mrusty/trait.MrubyImpl.html#tymethod.fixnum)
+/// * [`fixnum_i64`](../mrusty/trait.MrubyImpl.html#tymethod.fixnum_i64)
+/// * [`fixnum_from_usize`](../mrusty/trait.MrubyImpl.html#tymethod.fixnum_from_usize)
 /// * [`float`](../mrusty/trait.MrubyImpl.html#tymethod.float)
 /// * [`string`](../mrusty/trait.MrubyImpl.html#tymethod.string)
 /// * [`obj`](../mrusty/trait.MrubyImpl.html#tymethod.obj)
 /// * [`option`](../mrusty/trait.MrubyImpl.html#tymethod.option)
+/// * [`obj_from`](../mrusty/trait.MrubyImpl.html#tymethod.obj_from)
 /// * [`array`](../mrusty/trait.MrubyImpl.html#tymethod.array)
+/// * [`hash`](../mrusty/trait.MrubyImpl.html#tymethod.hash)
+/// * [`range`](../mrusty/trait.MrubyImpl.html#tymethod.range)
+/// * [`int_range`](../mrusty/trait.MrubyImpl.html#tymethod.int_range)
+/// * [`proc`](../mrusty/trait.MrubyImpl.html#tymethod.proc)
 ///
 /// # Examples
 ///
@@ -1629,8 +2613,8 @@ impl Drop for Mruby {
 /// assert_eq!(result.to_bool().unwrap(), true);
 /// ```
 pub struct Value {
-    mruby: MrubyType,
-    value: MrValue
+    pub(crate) mruby: MrubyType,
+    pub(crate) value: MrValue
 }
 
 impl Value {
@@ -1638,8 +2622,8 @@ impl Value {
     #[doc(hidden)]
     pub fn new(mruby: MrubyType, value: MrValue) -> Value {
         Value {
-            mruby: mruby,
-            value: value
+            mruby,
+            value
         }
     }
 
@@ -1710,61 +2694,140 @@ impl Value {
     /// assert_eq!(result.to_i32().unwrap(), 3);
     /// ```
     pub fn call(&self, name: &str, args: Vec<Value>) -> Result<Value, MrubyError> {
-        extern "C" fn call_protected(mrb: *const MrState, data: MrValue) -> MrValue {
-            unsafe {
-                let ptr = data.to_ptr().unwrap();
-                let args = *mem::transmute::<*const u8, *const [*const u8; 4]>(ptr);
-
-                let value: &MrValue = mem::transmute(args[0]);
-                let sym: &u32 = mem::transmute(args[1]);
-                let argc: &MrInt = mem::transmute(args[2]);
-                let argv: *const MrValue = mem::transmute(args[3]);
+        self.call_with_block(name, args, self.mruby.nil())
+    }
 
-                let result = mrb_funcall_argv(mrb, *value, *sym, *argc, argv);
+    /// Calls method `name` on a `Value` passing `args` and `block`. The `block` is usually
+    /// created with [`proc`](../mrusty/trait.MrubyImpl.html#tymethod.proc).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let array = mruby.run("[1, 2, 3]").unwrap();
+    /// let block = mruby.proc(|mruby, args| {
+    ///     mruby.fixnum(args[0].to_i32().unwrap() * 2)
+    /// });
+    ///
+    /// let result = array.call_with_block("map", vec![], block).unwrap();
+    ///
+    /// assert_eq!(result.to_vec().unwrap(), vec![
+    ///     mruby.fixnum(2),
+    ///     mruby.fixnum(4),
+    ///     mruby.fixnum(6)
+    /// ]);
+    /// ```
+    pub fn call_with_block(&self, name: &str, args: Vec<Value>,
+                           block: Value) -> Result<Value, MrubyError> {
+        let sym = self.mruby.intern(name);
 
-                mrb_ext_raise_current(mrb);
+        self.call_sym_with_block(&sym, args, block)
+    }
 
-                result
-            }
-        }
+    /// Calls the method named by the interned `sym` on a `Value` passing `args`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let upcase = mruby.intern("upcase");
+    /// let result = mruby.string("hi").call_sym(&upcase, vec![]).unwrap();
+    ///
+    /// assert_eq!(result.to_str().unwrap(), "HI");
+    /// ```
+    pub fn call_sym(&self, sym: &Symbol, args: Vec<Value>) -> Result<Value, MrubyError> {
+        self.call_sym_with_block(sym, args, self.mruby.nil())
+    }
 
+    /// Calls the method named by the interned `sym` on a `Value` passing `args` and `block`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let map = mruby.intern("map");
+    /// let block = mruby.proc(|mruby, args| {
+    ///     mruby.fixnum(args[0].to_i32().unwrap() + 1)
+    /// });
+    ///
+    /// let result = mruby.run("[1, 2]").unwrap().call_sym_with_block(&map, vec![], block);
+    ///
+    /// assert_eq!(result.unwrap().to_vec().unwrap(), vec![mruby.fixnum(2), mruby.fixnum(3)]);
+    /// ```
+    pub fn call_sym_with_block(&self, sym: &Symbol, args: Vec<Value>,
+                               block: Value) -> Result<Value, MrubyError> {
         unsafe {
             let mrb = self.mruby.borrow().mrb;
 
-            let name_str = CString::new(name).unwrap();
-            let sym = mrb_intern(mrb, name_str.as_ptr(), name.len());
-
             let args: Vec<MrValue> = args.iter().map(|value| value.value).collect();
 
-            let value_ptr: *const u8 = mem::transmute(&self.value);
-            let sym_ptr: *const u8 = mem::transmute(&sym);
-            let argc = args.len() as MrInt;
-            let argc_ptr: *const u8 = mem::transmute(&argc);
-            let argv_ptr: *const u8 = mem::transmute(args.as_ptr());
-
-            let args = [value_ptr, sym_ptr, argc_ptr, argv_ptr];
-            let args_ptr: *const u8 = mem::transmute(&args);
-            let data = MrValue::ptr(mrb, args_ptr);
-
-            let state = mem::MaybeUninit::<bool>::zeroed().assume_init();
+            let (value, id, block) = (self.value, sym.id, block.value);
 
-            let value = mrb_protect(mrb, call_protected, data, &state as *const bool);
+            let result = protect(mrb, |mrb| {
+                mrb_funcall_with_block(mrb, value, id, args.len() as MrInt, args.as_ptr(), block)
+            });
 
-            if state {
-                let str = mrb_ext_exc_str(mrb, value).to_str(mrb).unwrap();
-
-                Err(MrubyError::Runtime(str.to_owned()))
-            } else {
-                Ok(Value::new(self.mruby.clone(), value))
+            match result {
+                Ok(value) => Ok(Value::new(self.mruby.clone(), value)),
+                Err(exc)  => Err(exception(mrb, exc))
             }
         }
     }
 
+    /// Calls a block `Value`, e.g. one bound with `&block: Value` in `mrfn!`, passing `args`.
+    /// Returns an `Err` of `MrubyError::NoBlock` if no block was given, i.e. if the `Value` is
+    /// `nil`, which gets raised as a `LocalJumpError` if it is returned from an `mrfn!` method.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate mrusty;
+    /// use mrusty::{Mruby, MrubyError, MrubyImpl, Value};
+    ///
+    /// # fn main() {
+    /// let mruby = Mruby::new();
+    ///
+    /// struct Cont;
+    ///
+    /// mruby.def_class_for::<Cont>("Container");
+    /// mruby.def_class_method_for::<Cont, _>("twice", mrfn!(|mruby, _slf: Value, &block: Value|
+    ///                                                      -> Result<Value, MrubyError> {
+    ///     block.yield_block(vec![mruby.fixnum(1)])?;
+    ///     block.yield_block(vec![mruby.fixnum(2)])
+    /// }));
+    ///
+    /// let result = mruby.run("Container.twice { |i| i * 10 }").unwrap();
+    ///
+    /// assert_eq!(result.to_i32().unwrap(), 20);
+    ///
+    /// let result = mruby.run("begin; Container.twice; rescue LocalJumpError; :none; end");
+    ///
+    /// assert_eq!(result.unwrap().to_str().unwrap(), "none");
+    /// # }
+    /// ```
+    pub fn yield_block(&self, args: Vec<Value>) -> Result<Value, MrubyError> {
+        if self.is_nil() {
+            return Err(MrubyError::NoBlock);
+        }
+
+        self.call("call", args)
+    }
+
     /// Calls method `name` on a `Value` passing `args`. If call fails, mruby will be left to
     /// handle the exception.
     ///
-    /// The method is unsafe because running it within a Rust context will interrupt drops,
-    /// potentially leading to memory leaks.
+    /// # Safety
+    ///
+    /// Running it within a Rust context will interrupt drops, potentially leading to memory leaks.
     ///
     /// # Examples
     ///
@@ -1779,13 +2842,11 @@ impl Value {
     /// assert_eq!(result.to_i32().unwrap(), 3);
     /// ```
     pub unsafe fn call_unchecked(&self, name: &str, args: Vec<Value>) -> Value {
-        let name_str = CString::new(name).unwrap();
-
-        let sym = mrb_intern(self.mruby.borrow().mrb, name_str.as_ptr(), name.len());
+        let sym = self.mruby.intern(name);
 
         let args: Vec<MrValue> = args.iter().map(|value| value.value).collect();
 
-        let result = mrb_funcall_argv(self.mruby.borrow().mrb, self.value, sym,
+        let result = mrb_funcall_argv(self.mruby.borrow().mrb, self.value, sym.id,
                                       args.len() as MrInt, args.as_ptr());
 
         Value::new(self.mruby.clone(), result)
@@ -1808,12 +2869,10 @@ impl Value {
     /// ```
     #[inline]
     pub fn has_var(&self, name: &str) -> bool {
-        unsafe {
-            let name_str = CString::new(name).unwrap();
+        let sym = self.mruby.intern(name);
 
-            let sym = mrb_intern(self.mruby.borrow().mrb, name_str.as_ptr(), name.len());
-
-            mrb_iv_defined(self.mruby.borrow().mrb, self.value, sym)
+        unsafe {
+            mrb_iv_defined(self.mruby.borrow().mrb, self.value, sym.id)
         }
     }
 
@@ -1838,14 +2897,35 @@ impl Value {
     /// ```
     #[inline]
     pub fn get_var(&self, name: &str) -> Option<Value> {
-        unsafe {
-            let name_str = CString::new(name).unwrap();
-
-            let sym = mrb_intern(self.mruby.borrow().mrb, name_str.as_ptr(), name.len());
+        self.get_ivar_sym(&self.mruby.intern(name))
+    }
 
-            if mrb_iv_defined(self.mruby.borrow().mrb, self.value, sym) {
+    /// Returns the value of the instance variable named by the interned `sym` in a `Some` or
+    /// `None` if it is not defined.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.def_class("Container");
+    ///
+    /// let cont = mruby.run("Container.new").unwrap();
+    /// let value = mruby.intern("value");
+    ///
+    /// cont.set_ivar_sym(&value, mruby.fixnum(2));
+    ///
+    /// assert_eq!(cont.get_ivar_sym(&value).unwrap().to_i32().unwrap(), 2);
+    /// assert!(cont.get_ivar_sym(&mruby.intern("valup")).is_none());
+    /// ```
+    #[inline]
+    pub fn get_ivar_sym(&self, sym: &Symbol) -> Option<Value> {
+        unsafe {
+            if mrb_iv_defined(self.mruby.borrow().mrb, self.value, sym.id) {
                 Some(Value::new(self.mruby.clone(),
-                                mrb_iv_get(self.mruby.borrow().mrb, self.value, sym)))
+                                mrb_iv_get(self.mruby.borrow().mrb, self.value, sym.id)))
             } else {
                 None
             }
@@ -1885,25 +2965,10 @@ impl Value {
     /// ```
     #[inline]
     pub fn set_var(&self, name: &str, value: Value) {
-        match self.value.typ() {
-            MrType::MRB_TT_OBJECT |
-            MrType::MRB_TT_CLASS |
-            MrType::MRB_TT_MODULE |
-            MrType::MRB_TT_SCLASS |
-            MrType::MRB_TT_HASH |
-            MrType::MRB_TT_DATA |
-            MrType::MRB_TT_EXCEPTION => unsafe {
-                let name_str = CString::new(name).unwrap();
-
-                let sym = mrb_intern(self.mruby.borrow().mrb, name_str.as_ptr(), name.len());
-
-                mrb_iv_set(self.mruby.borrow().mrb, self.value, sym, value.value)
-            },
-            _ => panic!("Cannot set instance variable on non-object.")
-        }
+        self.set_ivar_sym(&self.mruby.intern(name), value)
     }
 
-    /// Returns the `Class` of an mruby `Value`.
+    /// Sets the value of the instance variable named by the interned `sym` to `value`.
     ///
     /// # Examples
     ///
@@ -1912,18 +2977,251 @@ impl Value {
     /// # use mrusty::MrubyImpl;
     /// let mruby = Mruby::new();
     ///
-    /// let one = mruby.run("1").unwrap();
-    /// assert_eq!(one.class().to_str(), "Fixnum");
-    /// ```
-    #[inline]
-    pub fn class(&self) -> Class {
-        unsafe {
-            let class = mrb_ext_class(self.mruby.borrow().mrb, self.value);
+    /// mruby.def_class("Container");
+    ///
+    /// let cont = mruby.run("Container.new").unwrap();
+    /// let value = mruby.intern("value");
+    ///
+    /// cont.set_ivar_sym(&value, mruby.fixnum(2));
+    ///
+    /// assert_eq!(cont.get_var("value").unwrap().to_i32().unwrap(), 2);
+    /// ```
+    /// <br/>
+    ///
+    /// Method panics if called on non-objects.
+    #[inline]
+    pub fn set_ivar_sym(&self, sym: &Symbol, value: Value) {
+        match self.value.typ() {
+            MrType::MRB_TT_OBJECT |
+            MrType::MRB_TT_CLASS |
+            MrType::MRB_TT_MODULE |
+            MrType::MRB_TT_SCLASS |
+            MrType::MRB_TT_HASH |
+            MrType::MRB_TT_DATA |
+            MrType::MRB_TT_EXCEPTION => unsafe {
+                mrb_iv_set(self.mruby.borrow().mrb, self.value, sym.id, value.value)
+            },
+            _ => panic!("Cannot set instance variable on non-object.")
+        }
+    }
+
+    /// Returns the `Class` of an mruby `Value`, skipping singleton classes like Ruby's `class`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let one = mruby.run("1").unwrap();
+    /// assert_eq!(one.class().to_str(), "Fixnum");
+    /// ```
+    #[inline]
+    pub fn class(&self) -> Class {
+        unsafe {
+            let class = mrb_obj_class(self.mruby.borrow().mrb, self.value);
 
             Class::new(self.mruby.clone(), class)
         }
     }
 
+    /// Returns the name of the `Class` of an mruby `Value`. Shortcut for `class().name()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.run("'a'").unwrap().class_name(), "String");
+    /// assert_eq!(mruby.nil().class_name(), "NilClass");
+    /// ```
+    #[inline]
+    pub fn class_name(&self) -> String {
+        self.class().name().to_owned()
+    }
+
+    /// Returns whether a `Value` is an instance of `class` or of one of its subclasses, or
+    /// includes it as a `Module`, like Ruby's `is_a?`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let list = mruby.run("
+    ///   class List < Array; end
+    ///
+    ///   List.new
+    /// ").unwrap();
+    ///
+    /// assert!(list.is_a(mruby.get_class("Array").unwrap()));
+    /// assert!(mruby.fixnum(1).is_a(mruby.get_class("Numeric").unwrap()));
+    /// assert!(!mruby.fixnum(1).is_a(mruby.get_class("Array").unwrap()));
+    /// ```
+    #[inline]
+    pub fn is_a(&self, class: Class) -> bool {
+        unsafe {
+            mrb_obj_is_kind_of(self.mruby.borrow().mrb, self.value, class.class)
+        }
+    }
+
+    /// Returns whether a `Value` is an instance of the `Class` defined for Rust type `T` or of one
+    /// of its subclasses, like `is_a`. Returns `false` if no `Class` is defined for `T`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// struct Cont;
+    /// struct Other;
+    ///
+    /// mruby.def_class_for::<Cont>("Container");
+    ///
+    /// let cont = mruby.obj(Cont);
+    ///
+    /// assert!(cont.is_instance_of::<Cont>());
+    /// assert!(!cont.is_instance_of::<Other>());
+    /// assert!(!mruby.fixnum(1).is_instance_of::<Cont>());
+    /// ```
+    pub fn is_instance_of<T: Any>(&self) -> bool {
+        let borrow = self.mruby.borrow();
+
+        match borrow.classes.get(&TypeId::of::<T>()) {
+            Some(class) => unsafe { mrb_obj_is_kind_of(borrow.mrb, self.value, class.0) },
+            None        => false
+        }
+    }
+
+    /// Calls `hash` on a `Value` and returns the result in an `Ok` or an `Err` if `hash` raises or
+    /// does not return a `Fixnum`. Values that are equal according to Ruby's `eql?` have the same
+    /// hash.
+    ///
+    /// *Note:* Hashes are only stable within one `Mruby` session.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let a = mruby.string("key").ruby_hash().unwrap();
+    /// let b = mruby.run("'k' + 'ey'").unwrap().ruby_hash().unwrap();
+    ///
+    /// assert_eq!(a, b);
+    /// ```
+    #[inline]
+    pub fn ruby_hash(&self) -> Result<i64, MrubyError> {
+        let hash = self.mruby.intern("hash");
+
+        self.call_sym(&hash, vec![]).and_then(|hash| hash.to_i64())
+    }
+
+    /// Calls `to_s` on a `Value` and returns the result. Falls back to the `Class` name if `to_s`
+    /// raises or does not return a `String`. `Value`'s `Display` implementation uses it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.run("[1, [nil, :a]]").unwrap().to_s(), "[1, [nil, :a]]");
+    /// assert_eq!(mruby.nil().to_s(), "");
+    /// assert_eq!(format!("{}", mruby.bool(true)), "true");
+    /// ```
+    pub fn to_s(&self) -> String {
+        let to_s = self.mruby.intern("to_s");
+
+        match self.call_sym(&to_s, vec![]).and_then(|string| string.to_str_lossy()) {
+            Ok(string) => string,
+            Err(_)     => self.class().to_str().to_owned()
+        }
+    }
+
+    /// Calls `inspect` on a `Value` and returns the result. Falls back to the `Class` name if
+    /// `inspect` raises or does not return a `String`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.run("[1, 'a', :b]").unwrap().inspect(), "[1, \"a\", :b]");
+    /// assert_eq!(mruby.nil().inspect(), "nil");
+    /// assert_eq!(mruby.string("hi").inspect(), "\"hi\"");
+    /// ```
+    pub fn inspect(&self) -> String {
+        let inspect = self.mruby.intern("inspect");
+
+        match self.call_sym(&inspect, vec![]).and_then(|string| string.to_str_lossy()) {
+            Ok(string) => string,
+            Err(_)     => self.class().to_str().to_owned()
+        }
+    }
+
+    /// Returns whether a `Value` responds to `method`, including singleton methods, by calling
+    /// `respond_to?`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let duck = mruby.run("
+    ///   duck = Object.new
+    ///
+    ///   def duck.quack; end
+    ///
+    ///   duck
+    /// ").unwrap();
+    ///
+    /// assert!(duck.responds_to("quack"));
+    /// assert!(!duck.responds_to("bark"));
+    /// ```
+    #[inline]
+    pub fn responds_to(&self, method: &str) -> bool {
+        let respond_to = self.mruby.intern("respond_to?");
+        let method = self.mruby.symbol(method);
+
+        self.call_sym(&respond_to, vec![method]).and_then(|result| result.to_bool())
+            .unwrap_or(false)
+    }
+
+    /// Returns whether a `Value` is equal to `other` according to Ruby's `==`. An exception raised
+    /// by `==` counts as not equal. `Value`'s `PartialEq` implementation uses it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert!(mruby.string("foo").eq_ruby(&mruby.string("foo")));
+    /// assert!(mruby.fixnum(1).eq_ruby(&mruby.float(1.0)));
+    /// assert!(mruby.run("[1, 'a']").unwrap() == mruby.run("[1, 'a']").unwrap());
+    /// ```
+    #[inline]
+    pub fn eq_ruby(&self, other: &Value) -> bool {
+        unsafe {
+            mrb_ext_equal_nothrow(self.mruby.borrow().mrb, self.value, other.value)
+        }
+    }
+
     /// Casts a `Value` and returns a `bool` in an `Ok` or an `Err` if the types mismatch.
     ///
     /// # Example
@@ -1949,7 +3247,8 @@ impl Value {
         }
     }
 
-    /// Casts a `Value` and returns an `i32` in an `Ok` or an `Err` if the types mismatch.
+    /// Casts a `Value` and returns an `i32` in an `Ok` or an `Err` if the types mismatch or the
+    /// `Fixnum` does not fit in an `i32`.
     ///
     /// # Example
     ///
@@ -1968,66 +3267,760 @@ impl Value {
     /// assert_eq!(result.to_i32().unwrap(), 120);
     /// ```
     #[inline]
-    pub fn to_i32(&self) -> Result<i32, MrubyError> {
+    pub fn to_i32(&self) -> Result<i32, MrubyError> {
+        unsafe {
+            self.value.to_i32()
+        }
+    }
+
+    /// Casts a `Value` and returns an `i64` in an `Ok` or an `Err` if the types mismatch.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("
+    ///   2 ** 40
+    /// ").unwrap();
+    ///
+    /// assert_eq!(result.to_i64().unwrap(), 1099511627776);
+    /// ```
+    #[inline]
+    pub fn to_i64(&self) -> Result<i64, MrubyError> {
+        unsafe {
+            self.value.to_i64()
+        }
+    }
+
+    /// Casts a `Value` and returns an `i64` in an `Ok` or an `Err` if the types mismatch. Unlike
+    /// [`to_i64`](#method.to_i64), `Float`s holding whole numbers such as `4.0` are accepted,
+    /// while fractional, infinite and NaN `Float`s are rejected.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.run("4.0").unwrap().to_i64_exact().unwrap(), 4);
+    /// assert_eq!(mruby.run("4").unwrap().to_i64_exact().unwrap(), 4);
+    /// assert!(mruby.run("4.5").unwrap().to_i64_exact().is_err());
+    /// ```
+    #[inline]
+    pub fn to_i64_exact(&self) -> Result<i64, MrubyError> {
+        unsafe {
+            self.value.to_i64_exact()
+        }
+    }
+
+    /// Casts a `Value` and returns a `u32` in an `Ok` or an `Err` if the types mismatch or the
+    /// `Fixnum` does not fit in a `u32`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.run("2 ** 31").unwrap().to_u32().unwrap(), 2147483648);
+    /// assert!(mruby.run("-1").unwrap().to_u32().is_err());
+    /// ```
+    #[inline]
+    pub fn to_u32(&self) -> Result<u32, MrubyError> {
+        unsafe {
+            self.value.to_u32()
+        }
+    }
+
+    /// Casts a `Value` and returns a `u64` in an `Ok` or an `Err` if the types mismatch or the
+    /// `Fixnum` is negative.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.run("2 ** 40").unwrap().to_u64().unwrap(), 1099511627776);
+    /// assert!(mruby.run("-1").unwrap().to_u64().is_err());
+    /// ```
+    #[inline]
+    pub fn to_u64(&self) -> Result<u64, MrubyError> {
+        unsafe {
+            self.value.to_u64()
+        }
+    }
+
+    /// Casts a `Value` and returns a `usize` in an `Ok` or an `Err` if the types mismatch or the
+    /// `Fixnum` does not fit in a `usize`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("[1, 2, 3].size").unwrap();
+    ///
+    /// assert_eq!(result.to_usize().unwrap(), 3);
+    /// ```
+    #[inline]
+    pub fn to_usize(&self) -> Result<usize, MrubyError> {
+        unsafe {
+            self.value.to_usize()
+        }
+    }
+
+    /// Casts a `Value` and returns an `isize` in an `Ok` or an `Err` if the types mismatch or the
+    /// `Fixnum` does not fit in an `isize`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("-[1, 2, 3].size").unwrap();
+    ///
+    /// assert_eq!(result.to_isize().unwrap(), -3);
+    /// ```
+    #[inline]
+    pub fn to_isize(&self) -> Result<isize, MrubyError> {
+        unsafe {
+            self.value.to_isize()
+        }
+    }
+
+    /// Casts a `Value` and returns an `f64` in an `Ok` or an `Err` if the types mismatch. `Fixnum`s
+    /// are converted to `f64`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.run("3 / 2.0").unwrap().to_f64().unwrap(), 1.5);
+    /// assert_eq!(mruby.run("2 + 3").unwrap().to_f64().unwrap(), 5.0);
+    /// assert!(mruby.run("'5'").unwrap().to_f64().is_err());
+    /// ```
+    #[inline]
+    pub fn to_f64(&self) -> Result<f64, MrubyError> {
+        unsafe {
+            self.value.to_f64()
+        }
+    }
+
+    /// Casts a `Value` of `Class` `Float` and returns an `f64` in an `Ok` or an `Err` if the types
+    /// mismatch. Unlike [`to_f64`](#method.to_f64), `Fixnum`s are rejected.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.run("3 / 2.0").unwrap().to_f64_strict().unwrap(), 1.5);
+    /// assert!(mruby.run("2 + 3").unwrap().to_f64_strict().is_err());
+    /// ```
+    #[inline]
+    pub fn to_f64_strict(&self) -> Result<f64, MrubyError> {
+        unsafe {
+            self.value.to_f64_strict()
+        }
+    }
+
+    /// Casts a `Value` of seconds and returns a `Duration` in an `Ok` or an `Err` if the types
+    /// mismatch, if it is negative or `NaN`, or if it does not fit. `Fixnum`s and `Float`s are
+    /// both accepted and go through `f64` seconds, so fractions of a millisecond are kept.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// use std::time::Duration;
+    ///
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.run("0.25").unwrap().to_duration().unwrap(), Duration::from_millis(250));
+    /// assert_eq!(mruby.run("2").unwrap().to_duration().unwrap(), Duration::from_secs(2));
+    /// assert!(mruby.run("-1.0").unwrap().to_duration().is_err());
+    /// ```
+    pub fn to_duration(&self) -> Result<Duration, MrubyError> {
+        let secs = self.to_f64()?;
+
+        if secs.is_nan() || secs < 0.0 {
+            return Err(MrubyError::Cast(format!("non-negative seconds, found {}", secs)));
+        }
+
+        Duration::try_from_secs_f64(secs).map_err(|_| {
+            MrubyError::Overflow(format!("{} seconds does not fit in Duration", secs))
+        })
+    }
+
+    /// Casts a `Value` of `Class` `String` or `Symbol` and returns an owned `String` in an `Ok` or
+    /// an `Err` if the types mismatch or the `String` is not valid UTF-8.
+    ///
+    /// The bytes are copied out of mruby, since the garbage collector can free or move them at any
+    /// point a `Value` does not keep them alive.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("
+    ///   [1, 2, 3].map(&:to_s).join
+    /// ").unwrap();
+    ///
+    /// assert_eq!(result.to_str().unwrap(), "123");
+    /// ```
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run(":symbol").unwrap();
+    ///
+    /// assert_eq!(result.to_str().unwrap(), "symbol");
+    /// ```
+    #[inline]
+    pub fn to_str(&self) -> Result<String, MrubyError> {
+        unsafe {
+            self.value.to_str(self.mruby.borrow().mrb).map(|s| s.to_owned())
+        }
+    }
+
+    /// Casts a `Value` of `Class` `String` or `Symbol` and returns a `String` in an `Ok` or an
+    /// `Err` if the types mismatch. Invalid UTF-8 is replaced with `U+FFFD` instead of failing like
+    /// [`to_str`](#method.to_str).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("\"caf\\xff\"").unwrap();
+    ///
+    /// assert!(result.to_str().is_err());
+    /// assert_eq!(result.to_str_lossy().unwrap(), "caf\u{fffd}");
+    /// ```
+    #[inline]
+    pub fn to_str_lossy(&self) -> Result<String, MrubyError> {
+        unsafe {
+            self.value.to_string_lossy(self.mruby.borrow().mrb).map(|s| s.into_owned())
+        }
+    }
+
+    /// Casts a `Value` of `Class` `String` containing exactly one Unicode scalar and returns a
+    /// `char` in an `Ok` or an `Err` if the types mismatch or the `String` is empty or longer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.run("'ü'").unwrap().to_char().unwrap(), 'ü');
+    /// assert!(mruby.run("''").unwrap().to_char().is_err());
+    /// assert!(mruby.run("'ab'").unwrap().to_char().is_err());
+    /// assert!(mruby.run(":a").unwrap().to_char().is_err());
+    /// ```
+    #[inline]
+    pub fn to_char(&self) -> Result<char, MrubyError> {
+        let string = self.to_str_strict()?;
+        let mut chars = string.chars();
+
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(MrubyError::Cast("String of length 1".to_owned()))
+        }
+    }
+
+    /// Casts a `Value` of `Class` `Fixnum` holding a Unicode codepoint and returns a `char` in an
+    /// `Ok` or an `Err` if the types mismatch or the codepoint is not a Unicode scalar.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.run("'a'.ord").unwrap().to_char_from_codepoint().unwrap(), 'a');
+    /// assert!(mruby.fixnum(0xd800).to_char_from_codepoint().is_err());
+    /// assert!(mruby.fixnum(-1).to_char_from_codepoint().is_err());
+    /// ```
+    #[inline]
+    pub fn to_char_from_codepoint(&self) -> Result<char, MrubyError> {
+        let codepoint = self.to_i64()?;
+
+        u32::try_from(codepoint).ok().and_then(char::from_u32)
+            .ok_or_else(|| MrubyError::Cast("Unicode codepoint".to_owned()))
+    }
+
+    /// Casts a `Value` of `Class` `String` and returns an owned `String` in an `Ok` or an `Err` if
+    /// the types mismatch. Unlike [`to_str`](#method.to_str), `Symbol`s are rejected.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.run("'foo'").unwrap().to_str_strict().unwrap(), "foo");
+    /// assert!(mruby.run(":foo").unwrap().to_str_strict().is_err());
+    /// ```
+    #[inline]
+    pub fn to_str_strict(&self) -> Result<String, MrubyError> {
+        unsafe {
+            self.value.to_str_strict(self.mruby.borrow().mrb).map(|s| s.to_owned())
+        }
+    }
+
+    /// Casts a `Value` of `Class` `String` and returns its raw bytes in an `Ok` or an `Err` if the
+    /// types mismatch. Interior NULs and invalid UTF-8 are kept as is.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("\"a\\0b\\xff\"").unwrap();
+    ///
+    /// assert_eq!(result.to_bytes().unwrap(), vec![b'a', 0, b'b', 0xff]);
+    /// ```
+    #[inline]
+    pub fn to_bytes(&self) -> Result<Vec<u8>, MrubyError> {
+        unsafe {
+            self.value.to_bytes(self.mruby.borrow().mrb).map(|bytes| bytes.to_vec())
+        }
+    }
+
+    /// Casts a `Value` of `Class` `String` and returns a `PathBuf` in an `Ok` or an `Err` if the
+    /// types mismatch or if the bytes cannot form a path on this platform. On Unix any bytes but
+    /// NUL are accepted; elsewhere the `String` must also be valid UTF-8.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// use std::path::Path;
+    ///
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.run("'lib/' + 'a.rb'").unwrap().to_path().unwrap(), Path::new("lib/a.rb"));
+    /// assert!(mruby.run("\"a\\0b\"").unwrap().to_path().is_err());
+    /// ```
+    pub fn to_path(&self) -> Result<PathBuf, MrubyError> {
+        let bytes = self.to_bytes()?;
+
+        if bytes.contains(&0) {
+            return Err(MrubyError::Cast("path without NUL bytes".to_owned()));
+        }
+
+        path_from_bytes(bytes)
+    }
+
+    /// Casts a `Value` of `Class` `String` and borrows its raw bytes without copying them in an
+    /// `Ok` or returns an `Err` if the types mismatch.
+    ///
+    /// The slice points straight into the mruby string. It is invalidated once mruby code mutates
+    /// the string or the string gets garbage collected, so do not hold on to it across calls into
+    /// mruby.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("'ab' * 3").unwrap();
+    ///
+    /// assert_eq!(result.as_bytes().unwrap(), b"ababab");
+    /// ```
+    #[inline]
+    pub fn as_bytes(&self) -> Result<&[u8], MrubyError> {
+        unsafe {
+            self.value.to_bytes(self.mruby.borrow().mrb)
+        }
+    }
+
+    /// Casts a `Value` of `Class` `Symbol` and returns a `Symbol` in an `Ok` or an `Err` if the
+    /// types mismatch.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let sym = mruby.run(":foo").unwrap().to_sym().unwrap();
+    ///
+    /// assert_eq!(sym.to_str(), "foo");
+    /// assert!(mruby.run("'foo'").unwrap().to_sym().is_err());
+    /// ```
+    #[inline]
+    pub fn to_sym(&self) -> Result<Symbol, MrubyError> {
+        unsafe {
+            let name = self.value.to_sym(self.mruby.borrow().mrb)?;
+
+            Ok(Symbol {
+                name: Rc::from(name),
+                id:   mrb_ext_symbol_to_cuint(self.value)
+            })
+        }
+    }
+
+    /// Casts mruby `Value` of `Class` `name` to Rust type `Rc<T>`.
+    ///
+    /// *Note:* `T` must be defined on the current `Mruby` with `def_class`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// struct Cont {
+    ///     value: i32
+    /// }
+    ///
+    /// mruby.def_class_for::<Cont>("Container");
+    ///
+    /// let value = mruby.obj(Cont { value: 3 });
+    /// let cont = value.to_obj::<Cont>().unwrap();
+    /// let cont = cont.borrow();
+    ///
+    /// assert_eq!(cont.value, 3);
+    /// ```
+    #[inline]
+    pub fn to_obj<T: Any>(&self) -> Result<Rc<RefCell<T>>, MrubyError> {
+        unsafe {
+            let borrow = self.mruby.borrow();
+
+            let class = match borrow.classes.get(&TypeId::of::<T>()) {
+                Some(class) => class,
+                None        => {
+                    return Err(MrubyError::Undef)
+                }
+            };
+
+            let self_class = self.class();
+
+            if self_class.to_str() != class.2 {
+                return Err(MrubyError::Undef)
+            }
+
+            self.value.to_obj::<T>(borrow.mrb, &class.1)
+        }
+    }
+
+    /// Casts mruby `Value` of `Class` `name` to Rust `Option` of `Rc<T>`.
+    ///
+    /// *Note:* `T` must be defined on the current `Mruby` with `def_class`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// struct Cont {
+    ///     value: i32
+    /// }
+    ///
+    /// mruby.def_class_for::<Cont>("Container");
+    ///
+    /// let value = mruby.obj(Cont { value: 3 });
+    /// let cont = value.to_option::<Cont>().unwrap().unwrap();
+    /// let cont = cont.borrow();
+    ///
+    /// assert_eq!(cont.value, 3);
+    /// assert!(mruby.nil().to_option::<Cont>().unwrap().is_none());
+    /// ```
+    #[inline]
+    pub fn to_option<T: Any>(&self) -> Result<Option<Rc<RefCell<T>>>, MrubyError> {
+        if self.value.typ() == MrType::MRB_TT_DATA {
+            self.to_obj::<T>().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Casts mruby `Value` of `Class` `Array` to Rust type `Vec<Value>`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("
+    ///   [1, 2, 3].map(&:to_s)
+    /// ").unwrap();
+    ///
+    /// assert_eq!(result.to_vec().unwrap(), vec![
+    ///     mruby.string("1"),
+    ///     mruby.string("2"),
+    ///     mruby.string("3")
+    /// ]);
+    /// ```
+    #[inline]
+    pub fn to_vec(&self) -> Result<Vec<Value>, MrubyError> {
+        unsafe {
+            self.value.to_vec(self.mruby.borrow().mrb).map(|vec| {
+                vec.iter().map(|mrvalue| {
+                    Value::new(self.mruby.clone(), *mrvalue)
+                }).collect()
+            })
+        }
+    }
+
+    /// Returns an `ArrayIter` that lazily walks a `Value` of `Class` `Array` without copying it
+    /// into a `Vec`. The length is re-read on every step, so the `Array` may change while
+    /// iterating. Any other `Value` yields an empty iterator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("[1, 2, 3]").unwrap();
+    ///
+    /// let sum: i32 = result.iter().map(|value| value.to_i32().unwrap()).sum();
+    ///
+    /// assert_eq!(sum, 6);
+    /// assert_eq!(mruby.fixnum(1).iter().count(), 0);
+    /// ```
+    #[inline]
+    pub fn iter(&self) -> ArrayIter {
+        ArrayIter {
+            array: self.clone(),
+            index: 0
+        }
+    }
+
+    /// Returns whether a `Value` is `nil`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert!(mruby.nil().is_nil());
+    /// assert!(!mruby.bool(false).is_nil());
+    /// ```
+    #[inline]
+    pub fn is_nil(&self) -> bool {
+        self.value.is_nil()
+    }
+
+    /// Returns whether a `Value` is truthy in Ruby terms, i.e. neither `nil` nor `false`. Unlike
+    /// `to_bool` it never fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert!(mruby.fixnum(0).is_truthy());
+    /// assert!(mruby.string("").is_truthy());
+    /// assert!(!mruby.nil().is_truthy());
+    /// assert!(!mruby.bool(false).is_truthy());
+    /// ```
+    #[inline]
+    pub fn is_truthy(&self) -> bool {
+        !self.is_falsey()
+    }
+
+    /// Returns whether a `Value` is falsey in Ruby terms, i.e. `nil` or `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert!(mruby.nil().is_falsey());
+    /// assert!(mruby.bool(false).is_falsey());
+    /// assert!(!mruby.fixnum(0).is_falsey());
+    /// ```
+    #[inline]
+    pub fn is_falsey(&self) -> bool {
+        // MRB_TT_FALSE is shared by nil and false
+        self.value.typ() == MrType::MRB_TT_FALSE
+    }
+
+    /// Casts a `Value` to `None` if it is `nil` or to `Some` of Rust type `T` with `FromValue`
+    /// otherwise. Only `nil` maps to `None`; `false` is cast like any other value.
+    ///
+    /// Use [`to_option`](#method.to_option) for Rust objects created with `obj`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.nil().to_option_from::<bool>().unwrap(), None);
+    /// assert_eq!(mruby.bool(false).to_option_from::<bool>().unwrap(), Some(false));
+    /// assert_eq!(mruby.fixnum(2).to_option_from::<i32>().unwrap(), Some(2));
+    /// assert!(mruby.fixnum(2).to_option_from::<String>().is_err());
+    /// ```
+    #[inline]
+    pub fn to_option_from<T: FromValue>(&self) -> Result<Option<T>, MrubyError> {
+        Option::<T>::from_value(self.clone())
+    }
+
+    /// Casts mruby `Value` of `Class` `Array` to a Rust tuple of up to 8 elements, casting every
+    /// element with `FromValue`. Returns an `Err` naming the failing index if an element does not
+    /// cast or if the lengths differ.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("['apples', 3, true]").unwrap();
+    ///
+    /// let (name, count, ok) = result.to_tuple::<(String, i32, bool)>().unwrap();
+    ///
+    /// assert_eq!(name, "apples");
+    /// assert_eq!(count, 3);
+    /// assert!(ok);
+    ///
+    /// assert!(result.to_tuple::<(String, i32)>().is_err());
+    /// assert!(result.to_tuple::<(String, String, bool)>().is_err());
+    /// ```
+    #[inline]
+    pub fn to_tuple<T: FromValue>(&self) -> Result<T, MrubyError> {
+        T::from_value(self.clone())
+    }
+
+    /// Casts mruby `Value` of `Class` `Hash` to Rust type `Vec<(Value, Value)>`. Pairs are
+    /// returned in the `Hash`'s insertion order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("
+    ///   { 'a' => 1, 'b' => 2 }
+    /// ").unwrap();
+    ///
+    /// assert_eq!(result.to_hash().unwrap(), vec![
+    ///     (mruby.string("a"), mruby.fixnum(1)),
+    ///     (mruby.string("b"), mruby.fixnum(2))
+    /// ]);
+    /// ```
+    #[inline]
+    pub fn to_hash(&self) -> Result<Vec<(Value, Value)>, MrubyError> {
         unsafe {
-            self.value.to_i32()
+            self.value.to_hash(self.mruby.borrow().mrb).map(|vec| {
+                vec.iter().map(|&(key, value)| {
+                    (Value::new(self.mruby.clone(), key), Value::new(self.mruby.clone(), value))
+                }).collect()
+            })
         }
     }
 
-    /// Casts a `Value` and returns an `f64` in an `Ok` or an `Err` if the types mismatch.
+    /// Casts mruby `Value` of `Class` `Hash` to Rust type `Vec<(Value, Value)>` in the `Hash`'s
+    /// iteration order, which is its insertion order. Unlike casting to a `HashMap`, this keeps the
+    /// order around for code that depends on it; `hash_from_pairs` builds the `Hash` back.
     ///
-    /// # Example
+    /// # Examples
     ///
     /// ```
     /// # use mrusty::Mruby;
     /// # use mrusty::MrubyImpl;
     /// let mruby = Mruby::new();
     /// let result = mruby.run("
-    ///   3 / 2.0
+    ///   { zeta: 1, alpha: 2, mu: 3 }
     /// ").unwrap();
     ///
-    /// assert_eq!(result.to_f64().unwrap(), 1.5);
+    /// let pairs = result.to_pairs().unwrap();
+    /// let keys: Vec<String> = pairs.iter().map(|&(ref key, _)| key.to_str().unwrap()).collect();
+    ///
+    /// assert_eq!(keys, vec!["zeta", "alpha", "mu"]);
+    /// assert_eq!(mruby.hash_from_pairs(pairs), result);
     /// ```
     #[inline]
-    pub fn to_f64(&self) -> Result<f64, MrubyError> {
-        unsafe {
-            self.value.to_f64()
-        }
+    pub fn to_pairs(&self) -> Result<Vec<(Value, Value)>, MrubyError> {
+        self.to_hash()
     }
 
-    /// Casts a `Value` and returns a `&str` in an `Ok` or an `Err` if the types mismatch.
+    /// Casts mruby `Value` of `Class` `Hash` to Rust type `HashMap<K, V>`, casting every key and
+    /// value with `FromValue`. Same as `HashMap::<K, V>::from_value`.
     ///
-    /// # Example
+    /// The cast fails if any key or value cannot be cast, or if two mruby keys end up equal once
+    /// cast to `K`.
+    ///
+    /// # Examples
     ///
     /// ```
     /// # use mrusty::Mruby;
     /// # use mrusty::MrubyImpl;
+    /// # use std::collections::HashMap;
     /// let mruby = Mruby::new();
     /// let result = mruby.run("
-    ///   [1, 2, 3].map(&:to_s).join
+    ///   { 'a' => 1, 'b' => 2 }
     /// ").unwrap();
     ///
-    /// assert_eq!(result.to_str().unwrap(), "123");
+    /// let map: HashMap<String, i32> = result.to_hashmap().unwrap();
+    ///
+    /// assert_eq!(map["a"], 1);
+    /// assert_eq!(map["b"], 2);
     /// ```
     ///
     /// ```
     /// # use mrusty::Mruby;
     /// # use mrusty::MrubyImpl;
+    /// # use std::collections::HashMap;
     /// let mruby = Mruby::new();
-    /// let result = mruby.run(":symbol").unwrap();
+    /// let result = mruby.run("
+    ///   { a: 1, 'a' => 2 }
+    /// ").unwrap();
     ///
-    /// assert_eq!(result.to_str().unwrap(), "symbol");
+    /// assert!(result.to_hashmap::<String, i32>().is_err());
     /// ```
-    #[inline]
-    pub fn to_str<'a>(&self) -> Result<&'a str, MrubyError> {
-        unsafe {
-            self.value.to_str(self.mruby.borrow().mrb)
-        }
+    pub fn to_hashmap<K, V>(&self) -> Result<HashMap<K, V>, MrubyError>
+        where K: FromValue + Eq + Hash, V: FromValue {
+
+        HashMap::from_value(self.clone())
     }
 
-    /// Casts mruby `Value` of `Class` `name` to Rust type `Rc<T>`.
-    ///
-    /// *Note:* `T` must be defined on the current `Mruby` with `def_class`.
+    /// Casts mruby `Value` of `Class` `Hash` with `String` or `Symbol` keys to Rust type
+    /// `HashMap<String, T>`, so `{ a: 1 }` and `{ 'a' => 1 }` read the same. Returns an `Err` naming
+    /// the key if a key has any other type, if a value does not cast or if a `String` and a
+    /// `Symbol` key collide. Same as `HashMap::<String, T>::from_value`.
     ///
     /// # Examples
     ///
@@ -2035,44 +4028,66 @@ impl Value {
     /// # use mrusty::Mruby;
     /// # use mrusty::MrubyImpl;
     /// let mruby = Mruby::new();
+    /// let result = mruby.run("
+    ///   { verbose: true, 'color' => false }
+    /// ").unwrap();
     ///
-    /// struct Cont {
-    ///     value: i32
-    /// }
+    /// let options = result.to_string_map::<bool>().unwrap();
     ///
-    /// mruby.def_class_for::<Cont>("Container");
+    /// assert_eq!(options["verbose"], true);
+    /// assert_eq!(options["color"], false);
     ///
-    /// let value = mruby.obj(Cont { value: 3 });
-    /// let cont = value.to_obj::<Cont>().unwrap();
-    /// let cont = cont.borrow();
+    /// assert!(mruby.run("{ 1 => true }").unwrap().to_string_map::<bool>().is_err());
+    /// ```
+    pub fn to_string_map<T: FromValue>(&self) -> Result<HashMap<String, T>, MrubyError> {
+        HashMap::from_value(self.clone())
+    }
+
+    /// Casts mruby `Value` of `Class` `Range` with `Fixnum` ends to Rust type `IntRange`.
     ///
-    /// assert_eq!(cont.value, 3);
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// use mrusty::IntRange;
+    ///
+    /// let mruby = Mruby::new();
+    ///
+    /// let exclusive = mruby.run("1...10").unwrap();
+    /// let inclusive = mruby.run("1..10").unwrap();
+    ///
+    /// assert_eq!(exclusive.to_range().unwrap(), IntRange::Exclusive(1..10));
+    /// assert_eq!(inclusive.to_range().unwrap(), IntRange::Inclusive(1..=10));
     /// ```
     #[inline]
-    pub fn to_obj<T: Any>(&self) -> Result<Rc<RefCell<T>>, MrubyError> {
+    pub fn to_range(&self) -> Result<IntRange, MrubyError> {
         unsafe {
-            let borrow = self.mruby.borrow();
+            let (start, end, exclusive) = self.value.to_range(self.mruby.borrow().mrb)?;
 
-            let class = match borrow.classes.get(&TypeId::of::<T>()) {
-                Some(class) => class,
-                None        => {
-                    return Err(MrubyError::Undef)
+            let start = Value::new(self.mruby.clone(), start);
+            let end = Value::new(self.mruby.clone(), end);
+
+            for value in &[&start, &end] {
+                if value.value.typ() != MrType::MRB_TT_FIXNUM {
+                    return Err(MrubyError::Cast(format!("Range of Fixnum, found {}",
+                                                        value.class().to_str())))
                 }
-            };
+            }
 
-            let self_class = self.class();
+            let start = start.to_i64()?;
+            let end = end.to_i64()?;
 
-            if self_class.to_str() != class.2 {
-                return Err(MrubyError::Undef)
+            if exclusive {
+                Ok(IntRange::Exclusive(start..end))
+            } else {
+                Ok(IntRange::Inclusive(start..=end))
             }
-
-            self.value.to_obj::<T>(borrow.mrb, &class.1)
         }
     }
 
-    /// Casts mruby `Value` of `Class` `name` to Rust `Option` of `Rc<T>`.
-    ///
-    /// *Note:* `T` must be defined on the current `Mruby` with `def_class`.
+    /// Casts mruby `Value` of `Class` `Range` with `Fixnum` ends to an exclusive Rust
+    /// `Range<i32>`. Inclusive ranges have their end incremented.
     ///
     /// # Examples
     ///
@@ -2081,29 +4096,47 @@ impl Value {
     /// # use mrusty::MrubyImpl;
     /// let mruby = Mruby::new();
     ///
-    /// struct Cont {
-    ///     value: i32
-    /// }
+    /// let exclusive = mruby.run("1...10").unwrap();
+    /// let inclusive = mruby.run("1..10").unwrap();
     ///
-    /// mruby.def_class_for::<Cont>("Container");
+    /// assert_eq!(exclusive.to_range_i32().unwrap(), 1..10);
+    /// assert_eq!(inclusive.to_range_i32().unwrap(), 1..11);
+    /// ```
+    #[inline]
+    pub fn to_range_i32(&self) -> Result<ops::Range<i32>, MrubyError> {
+        let (start, end) = match self.to_range()? {
+            IntRange::Exclusive(range) => (range.start, Some(range.end)),
+            IntRange::Inclusive(range) => (*range.start(), range.end().checked_add(1))
+        };
+
+        match (i32::try_from(start), end.map(i32::try_from)) {
+            (Ok(start), Some(Ok(end))) => Ok(start..end),
+            _ => Err(MrubyError::Overflow(format!("{:?} does not fit in Range<i32>",
+                                                  self.to_range()?)))
+        }
+    }
+
+    /// Returns the `Value` itself in an `Ok` if it is of `Class` `Proc` or an `Err` otherwise.
     ///
-    /// let value = mruby.obj(Cont { value: 3 });
-    /// let cont = value.to_option::<Cont>().unwrap().unwrap();
-    /// let cont = cont.borrow();
+    /// # Examples
     ///
-    /// assert_eq!(cont.value, 3);
-    /// assert!(mruby.nil().to_option::<Cont>().unwrap().is_none());
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert!(mruby.run("proc { |x| x }").unwrap().to_proc().is_ok());
+    /// assert!(mruby.run("1").unwrap().to_proc().is_err());
     /// ```
     #[inline]
-    pub fn to_option<T: Any>(&self) -> Result<Option<Rc<RefCell<T>>>, MrubyError> {
-        if self.value.typ() == MrType::MRB_TT_DATA {
-            self.to_obj::<T>().map(|obj| Some(obj))
-        } else {
-            Ok(None)
+    pub fn to_proc(&self) -> Result<Value, MrubyError> {
+        match self.value.typ() {
+            MrType::MRB_TT_PROC => Ok(self.clone()),
+            _ => Err(MrubyError::Cast("Proc".to_owned()))
         }
     }
 
-    /// Casts mruby `Value` of `Class` `Array` to Rust type `Vec<Value>`.
+    /// Calls a `Value` of `Class` `Proc` passing `args`.
     ///
     /// # Examples
     ///
@@ -2111,24 +4144,20 @@ impl Value {
     /// # use mrusty::Mruby;
     /// # use mrusty::MrubyImpl;
     /// let mruby = Mruby::new();
-    /// let result = mruby.run("
-    ///   [1, 2, 3].map(&:to_s)
-    /// ").unwrap();
     ///
-    /// assert_eq!(result.to_vec().unwrap(), vec![
-    ///     mruby.string("1"),
-    ///     mruby.string("2"),
-    ///     mruby.string("3")
-    /// ]);
+    /// let double = mruby.proc(|mruby, args| {
+    ///     mruby.fixnum(args[0].to_i32().unwrap() * 2)
+    /// });
+    ///
+    /// assert_eq!(double.call_proc(vec![mruby.fixnum(3)]).unwrap().to_i32().unwrap(), 6);
     /// ```
-    #[inline]
-    pub fn to_vec(&self) -> Result<Vec<Value>, MrubyError> {
+    pub fn call_proc(&self, args: Vec<Value>) -> Result<Value, MrubyError> {
         unsafe {
-            self.value.to_vec(self.mruby.borrow().mrb).map(|vec| {
-                vec.iter().map(|mrvalue| {
-                    Value::new(self.mruby.clone(), *mrvalue)
-                }).collect()
-            })
+            let args: Vec<MrValue> = args.iter().map(|value| value.value).collect();
+
+            let value = MrValue::call_proc(self.mruby.borrow().mrb, self.value, &args)?;
+
+            Ok(Value::new(self.mruby.clone(), value))
         }
     }
 
@@ -2188,21 +4217,324 @@ impl Clone for Value {
             }
         }
 
-        Value::new(self.mruby.clone(), self.value.clone())
+        Value::new(self.mruby.clone(), self.value)
+    }
+}
+
+impl PartialEq<Value> for Value {
+    fn eq(&self, other: &Value) -> bool {
+        self.eq_ruby(other)
+    }
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mruby.try_borrow() {
+            Ok(mruby) => write!(f, "Value {{ {:?} }}", unsafe { self.value.debug_with(mruby.mrb) }),
+            Err(_)    => write!(f, "Value {{ {:?} }}", self.value)
+        }
+    }
+}
+
+#[cfg(unix)]
+#[inline]
+fn path_to_bytes(path: &Path) -> Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+
+    Cow::Borrowed(path.as_os_str().as_bytes())
+}
+
+#[cfg(not(unix))]
+#[inline]
+fn path_to_bytes(path: &Path) -> Cow<'_, [u8]> {
+    match path.to_string_lossy() {
+        Cow::Borrowed(path) => Cow::Borrowed(path.as_bytes()),
+        Cow::Owned(path)    => Cow::Owned(path.into_bytes())
+    }
+}
+
+#[cfg(unix)]
+#[inline]
+fn path_from_bytes(bytes: Vec<u8>) -> Result<PathBuf, MrubyError> {
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+
+    Ok(PathBuf::from(OsString::from_vec(bytes)))
+}
+
+#[cfg(not(unix))]
+#[inline]
+fn path_from_bytes(bytes: Vec<u8>) -> Result<PathBuf, MrubyError> {
+    String::from_utf8(bytes).map(PathBuf::from).map_err(|_| {
+        MrubyError::Cast("UTF-8 path".to_owned())
+    })
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_s())
+    }
+}
+
+/// A `struct` returned by `Value::iter` that yields the elements of an mruby `Array` one by one.
+/// Non-`Array` `Value`s yield nothing.
+///
+/// # Examples
+///
+/// ```
+/// # use mrusty::Mruby;
+/// # use mrusty::MrubyImpl;
+/// let mruby = Mruby::new();
+/// let result = mruby.run("['a', 'b']").unwrap();
+///
+/// let mut letters = String::new();
+///
+/// for value in result {
+///     letters.push_str(&value.to_str().unwrap());
+/// }
+///
+/// assert_eq!(letters, "ab");
+/// ```
+pub struct ArrayIter {
+    array: Value,
+    index: MrInt
+}
+
+impl Iterator for ArrayIter {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        if self.array.value.typ() != MrType::MRB_TT_ARRAY {
+            return None;
+        }
+
+        unsafe {
+            let mrb = self.array.mruby.borrow().mrb;
+
+            if self.index >= mrb_ext_ary_len(mrb, self.array.value) {
+                return None;
+            }
+
+            let value = mrb_ary_ref(mrb, self.array.value, self.index);
+
+            self.index += 1;
+
+            Some(Value::new(self.array.mruby.clone(), value))
+        }
+    }
+}
+
+impl IntoIterator for Value {
+    type Item = Value;
+    type IntoIter = ArrayIter;
+
+    fn into_iter(self) -> ArrayIter {
+        ArrayIter {
+            array: self,
+            index: 0
+        }
+    }
+}
+
+impl IntoIterator for &Value {
+    type Item = Value;
+    type IntoIter = ArrayIter;
+
+    fn into_iter(self) -> ArrayIter {
+        self.iter()
+    }
+}
+
+/// A `trait` that collects an iterator of `Value`s directly into an mruby `Array`.
+///
+/// # Examples
+///
+/// ```
+/// # use mrusty::Mruby;
+/// # use mrusty::MrubyImpl;
+/// use mrusty::CollectMruby;
+///
+/// let mruby = Mruby::new();
+///
+/// let words = vec!["a", "b"].into_iter().map(|s| mruby.string(s)).collect_into_mruby(&mruby);
+///
+/// assert_eq!(words.to_s(), "[\"a\", \"b\"]");
+/// ```
+pub trait CollectMruby: Iterator<Item = Value> + Sized {
+    /// Collects `self` into an mruby `Array` with `MrubyImpl::array_from_iter`.
+    fn collect_into_mruby(self, mruby: &MrubyType) -> Value {
+        mruby.array_from_iter(self)
+    }
+}
+
+impl<I: Iterator<Item = Value>> CollectMruby for I {}
+
+/// A `struct` wrapping a `Value` so that it can be used as a key in Rust collections. It hashes
+/// with Ruby's `hash` and compares with Ruby's `eql?`, like mruby `Hash` keys, so `1` and `1.0`
+/// are different keys.
+///
+/// *Note:* Hashes are only stable within one `Mruby` session, so keys must not be mixed between
+/// sessions.
+///
+/// # Panics
+///
+/// Hashing or comparing panics if the key's `hash` or `eql?` raises.
+///
+/// # Examples
+///
+/// ```
+/// # use mrusty::Mruby;
+/// # use mrusty::MrubyImpl;
+/// use std::collections::HashMap;
+///
+/// use mrusty::HashedValue;
+///
+/// let mruby = Mruby::new();
+///
+/// let mut counts = HashMap::new();
+///
+/// for key in mruby.run("[:a, 'b', :a, 'b', 'b', 1, 1.0]").unwrap().to_vec().unwrap() {
+///     *counts.entry(HashedValue(key)).or_insert(0) += 1;
+/// }
+///
+/// assert_eq!(counts[&HashedValue(mruby.symbol("a"))], 2);
+/// assert_eq!(counts[&HashedValue(mruby.string("b"))], 3);
+/// assert_eq!(counts[&HashedValue(mruby.fixnum(1))], 1);
+/// ```
+#[derive(Clone, Debug)]
+pub struct HashedValue(pub Value);
+
+impl Hash for HashedValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self.0.ruby_hash() {
+            Ok(hash) => hash.hash(state),
+            Err(err) => panic!("HashedValue key cannot be hashed: {}", err)
+        }
+    }
+}
+
+impl PartialEq for HashedValue {
+    fn eq(&self, other: &HashedValue) -> bool {
+        let eql = self.0.mruby.intern("eql?");
+
+        match self.0.call_sym(&eql, vec![other.0.clone()]) {
+            Ok(result) => result.value.is_true(),
+            Err(err)   => panic!("HashedValue keys cannot be compared: {}", err)
+        }
+    }
+}
+
+impl Eq for HashedValue {}
+
+/// An `enum` containing an mruby `Range` of `Fixnum`s. Created with `Value::to_range()`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IntRange {
+    /// exclusive `Range` (`1...10`)
+    Exclusive(ops::Range<i64>),
+    /// inclusive `Range` (`1..10`)
+    Inclusive(ops::RangeInclusive<i64>)
+}
+
+impl From<ops::Range<i32>> for IntRange {
+    fn from(range: ops::Range<i32>) -> IntRange {
+        IntRange::Exclusive(range.start as i64..range.end as i64)
+    }
+}
+
+impl From<ops::RangeInclusive<i32>> for IntRange {
+    fn from(range: ops::RangeInclusive<i32>) -> IntRange {
+        IntRange::Inclusive(*range.start() as i64..=*range.end() as i64)
+    }
+}
+
+impl From<ops::Range<i64>> for IntRange {
+    fn from(range: ops::Range<i64>) -> IntRange {
+        IntRange::Exclusive(range)
+    }
+}
+
+impl From<ops::RangeInclusive<i64>> for IntRange {
+    fn from(range: ops::RangeInclusive<i64>) -> IntRange {
+        IntRange::Inclusive(range)
+    }
+}
+
+/// A `struct` containing the name and interned id of an mruby `Symbol`. Created with
+/// `Value::to_sym()` or `MrubyImpl::intern()`.
+///
+/// A `Symbol` can be used as a `HashMap` key. Since ids are not portable between `Mruby`s, a
+/// `Symbol` should only be used with the `Mruby` it came from.
+///
+/// # Examples
+///
+/// ```
+/// # use mrusty::Mruby;
+/// # use mrusty::MrubyImpl;
+/// use std::collections::HashMap;
+///
+/// let mruby = Mruby::new();
+///
+/// let mut counts = HashMap::new();
+///
+/// for value in mruby.run("[:a, :b, :a]").unwrap().to_vec().unwrap() {
+///     *counts.entry(value.to_sym().unwrap()).or_insert(0) += 1;
+/// }
+///
+/// assert_eq!(counts[&mruby.symbol("a").to_sym().unwrap()], 2);
+/// ```
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Symbol {
+    name: Rc<str>,
+    id:   u32
+}
+
+impl Symbol {
+    /// Returns a `&str` with the `Symbol` name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.symbol("foo").to_sym().unwrap().to_str(), "foo");
+    /// ```
+    #[inline]
+    pub fn to_str(&self) -> &str {
+        &self.name
     }
 }
 
-impl PartialEq<Value> for Value {
-    fn eq(&self, other: &Value) -> bool {
-        let result = self.call("==", vec![other.clone()]).unwrap();
-
-        result.to_bool().unwrap()
-    }
+/// A `struct` that restores the mruby GC arena index saved on its creation when dropped.
+/// Created with the `unsafe` `MrubyImpl::arena_guard()`, since `Value`s created while the guard
+/// is alive must not outlive it.
+///
+/// # Examples
+///
+/// ```
+/// # use mrusty::Mruby;
+/// # use mrusty::MrubyImpl;
+/// let mruby = Mruby::new();
+///
+/// let sum = {
+///     let _guard = unsafe { mruby.arena_guard() };
+///
+///     mruby.run("[1, 2, 3].inject(:+)").unwrap().to_i32().unwrap()
+/// };
+///
+/// assert_eq!(sum, 6);
+/// ```
+pub struct ArenaGuard {
+    mruby: MrubyType,
+    idx:   i32
 }
 
-impl fmt::Debug for Value {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Value {{ {:?} }}", self.value)
+impl Drop for ArenaGuard {
+    fn drop(&mut self) {
+        unsafe {
+            mrb_ext_gc_arena_restore(self.mruby.borrow().mrb, self.idx);
+        }
     }
 }
 
@@ -2235,6 +4567,7 @@ pub struct Class {
 impl Class {
     /// Not meant to be called directly.
     #[doc(hidden)]
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
     pub fn new(mruby: MrubyType, class: *const MrClass) -> Class {
         let name = unsafe {
             let name = mrb_class_name(mruby.borrow().mrb, class);
@@ -2243,8 +4576,8 @@ impl Class {
         };
 
         Class {
-            mruby:  mruby,
-            class:  class,
+            mruby,
+            class,
             name:   name.to_owned()
         }
     }
@@ -2310,6 +4643,216 @@ impl Class {
         }
     }
 
+    /// Returns the value of constant `name` on a `Class` in a `Some` or `None` if it is not
+    /// defined.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.run("
+    ///   class Container; VERSION = '1.0'; end
+    /// ").unwrap();
+    ///
+    /// let cont = mruby.get_class("Container").unwrap();
+    ///
+    /// assert_eq!(cont.get_const("VERSION").unwrap().to_str().unwrap(), "1.0");
+    /// assert!(cont.get_const("RELEASE").is_none());
+    /// ```
+    pub fn get_const(&self, name: &str) -> Option<Value> {
+        unsafe {
+            let mrb = self.mruby.borrow().mrb;
+
+            if MrClass::const_defined(mrb, self.class, name) {
+                Some(Value::new(self.mruby.clone(), MrClass::const_get(mrb, self.class, name)))
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Sets constant `name` on a `Class` to `value`, replacing any previous value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.run("
+    ///   class Container; MODE = 'debug'; end
+    /// ").unwrap();
+    ///
+    /// let cont = mruby.get_class("Container").unwrap();
+    ///
+    /// cont.set_const("MODE", mruby.string("release"));
+    ///
+    /// let result = mruby.run("Container::MODE").unwrap();
+    ///
+    /// assert_eq!(result.to_str().unwrap(), "release");
+    /// ```
+    pub fn set_const(&self, name: &str, value: Value) {
+        let sym = self.mruby.intern(name);
+
+        unsafe {
+            mrb_const_set(self.mruby.borrow().mrb, mrb_ext_class_value(self.class), sym.id,
+                          value.value);
+        }
+    }
+
+    /// Returns whether instances of a `Class` have a method `name`, either defined on it or
+    /// inherited.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.run("
+    ///   class Container; def value; end; end
+    /// ").unwrap();
+    ///
+    /// let cont = mruby.get_class("Container").unwrap();
+    ///
+    /// assert!(cont.method_defined("value"));
+    /// assert!(cont.method_defined("to_s"));
+    /// assert!(!cont.method_defined("size"));
+    /// ```
+    #[inline]
+    pub fn method_defined(&self, name: &str) -> bool {
+        let sym = self.mruby.intern(name);
+
+        unsafe {
+            mrb_ext_method_defined(self.mruby.borrow().mrb, self.class, sym.id)
+        }
+    }
+
+    /// Returns whether a `Class` has a class (singleton) method `name`, either defined on it or
+    /// inherited.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.run("
+    ///   class Container; def self.create; end; end
+    /// ").unwrap();
+    ///
+    /// let cont = mruby.get_class("Container").unwrap();
+    ///
+    /// assert!(cont.class_method_defined("create"));
+    /// assert!(cont.class_method_defined("new"));
+    /// assert!(!cont.method_defined("create"));
+    /// ```
+    #[inline]
+    pub fn class_method_defined(&self, name: &str) -> bool {
+        let sym = self.mruby.intern(name);
+
+        unsafe {
+            let mrb = self.mruby.borrow().mrb;
+            let singleton = mrb_ext_class(mrb, mrb_ext_class_value(self.class));
+
+            mrb_ext_method_defined(mrb, singleton, sym.id)
+        }
+    }
+
+    /// Returns whether the class variable `name` is defined on a `Class`. `name` includes the `@@`
+    /// prefix.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.run("
+    ///   class Container; @@count = 0; end
+    /// ").unwrap();
+    ///
+    /// let cont = mruby.get_class("Container").unwrap();
+    ///
+    /// assert!(cont.has_cv("@@count"));
+    /// assert!(!cont.has_cv("@@total"));
+    /// ```
+    #[inline]
+    pub fn has_cv(&self, name: &str) -> bool {
+        unsafe {
+            MrClass::cvar_defined(self.mruby.borrow().mrb, self.class, name)
+        }
+    }
+
+    /// Returns the value of the class variable `name` in a `Some` or `None` if it is not defined.
+    /// `name` includes the `@@` prefix.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.run("
+    ///   class Container; @@count = 3; end
+    /// ").unwrap();
+    ///
+    /// let cont = mruby.get_class("Container").unwrap();
+    ///
+    /// assert_eq!(cont.get_cv("@@count").unwrap().to_i32().unwrap(), 3);
+    /// assert!(cont.get_cv("@@total").is_none());
+    /// ```
+    #[inline]
+    pub fn get_cv(&self, name: &str) -> Option<Value> {
+        unsafe {
+            let mrb = self.mruby.borrow().mrb;
+
+            if MrClass::cvar_defined(mrb, self.class, name) {
+                Some(Value::new(self.mruby.clone(), MrClass::cvar_get(mrb, self.class, name)))
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Sets the value of the class variable `name` to `value`. `name` includes the `@@` prefix.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.run("
+    ///   class Container
+    ///     def self.count; @@count; end
+    ///   end
+    /// ").unwrap();
+    ///
+    /// let cont = mruby.get_class("Container").unwrap();
+    ///
+    /// cont.set_cv("@@count", mruby.fixnum(5));
+    ///
+    /// let result = mruby.run("Container.count").unwrap();
+    ///
+    /// assert_eq!(result.to_i32().unwrap(), 5);
+    /// ```
+    #[inline]
+    pub fn set_cv(&self, name: &str, value: Value) {
+        unsafe {
+            MrClass::cvar_set(self.mruby.borrow().mrb, self.class, name, value.value);
+        }
+    }
+
     /// Returns a `&str` with the mruby `Class` name.
     ///
     /// # Examples
@@ -2330,6 +4873,22 @@ impl Class {
         &self.name
     }
 
+    /// Returns a `&str` with the mruby `Class` name. Same as `to_str`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.nil().class().name(), "NilClass");
+    /// ```
+    #[inline]
+    pub fn name(&self) -> &str {
+        self.to_str()
+    }
+
     /// Casts `Class` to `Value`.
     ///
     /// # Examples
@@ -2406,6 +4965,7 @@ pub struct Module {
 impl Module {
     /// Not meant to be called directly.
     #[doc(hidden)]
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
     pub fn new(mruby: MrubyType, module: *const MrClass) -> Module {
         let name = unsafe {
             let name = mrb_class_name(mruby.borrow().mrb, module);
@@ -2414,8 +4974,8 @@ impl Module {
         };
 
         Module {
-            mruby:  mruby,
-            module: module,
+            mruby,
+            module,
             name:   name.to_owned()
         }
     }
@@ -2485,6 +5045,67 @@ impl Module {
         }
     }
 
+    /// Returns the value of constant `name` on a `Module` in a `Some` or `None` if it is not
+    /// defined.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.run("
+    ///   module Container; VERSION = '1.0'; end
+    /// ").unwrap();
+    ///
+    /// let cont = mruby.get_module("Container").unwrap();
+    ///
+    /// assert_eq!(cont.get_const("VERSION").unwrap().to_str().unwrap(), "1.0");
+    /// assert!(cont.get_const("RELEASE").is_none());
+    /// ```
+    pub fn get_const(&self, name: &str) -> Option<Value> {
+        unsafe {
+            let mrb = self.mruby.borrow().mrb;
+
+            if MrClass::const_defined(mrb, self.module, name) {
+                Some(Value::new(self.mruby.clone(), MrClass::const_get(mrb, self.module, name)))
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Sets constant `name` on a `Module` to `value`, replacing any previous value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.run("
+    ///   module Container; MODE = 'debug'; end
+    /// ").unwrap();
+    ///
+    /// let cont = mruby.get_module("Container").unwrap();
+    ///
+    /// cont.set_const("MODE", mruby.string("release"));
+    ///
+    /// let result = mruby.run("Container::MODE").unwrap();
+    ///
+    /// assert_eq!(result.to_str().unwrap(), "release");
+    /// ```
+    pub fn set_const(&self, name: &str, value: Value) {
+        let sym = self.mruby.intern(name);
+
+        unsafe {
+            mrb_const_set(self.mruby.borrow().mrb, mrb_ext_class_value(self.module), sym.id,
+                          value.value);
+        }
+    }
+
     /// Returns a `&str` with the mruby `Module` name.
     ///
     /// # Examples
@@ -2503,6 +5124,22 @@ impl Module {
         &self.name
     }
 
+    /// Returns a `&str` with the mruby `Module` name. Same as `to_str`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.get_module("Kernel").unwrap().name(), "Kernel");
+    /// ```
+    #[inline]
+    pub fn name(&self) -> &str {
+        self.to_str()
+    }
+
     /// Casts `Module` to `Value`.
     ///
     /// # Examples
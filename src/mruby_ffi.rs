@@ -6,13 +6,24 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 use std::any::Any;
+use std::borrow::Cow;
 use std::cell::RefCell;
-use std::ffi::CStr;
+use std::convert::TryFrom;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
 use std::mem;
 use std::os::raw::{c_char, c_uchar};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+use std::slice;
+use std::str;
 use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 
-use super::MrubyError;
+use super::{MrubyError, RaiseError};
 
 pub enum MrState {}
 pub enum MrContext {}
@@ -22,216 +33,2440 @@ pub enum MrData {}
 
 pub type MrFunc = extern "C" fn(*const MrState, MrValue) -> MrValue;
 pub type MrDfree = extern "C" fn(*const MrState, *const u8);
+pub type MrAllocf = extern "C" fn(*const MrState, *mut u8, usize, *const u8) -> *mut u8;
 
 pub type MrFloat = f64;
 pub type MrInt = i64;
 
-#[repr(C)]
-pub struct MrDataType {
-    _buf: [u8; 16]
+#[repr(C)]
+pub struct MrDataType {
+    _buf: [u8; 16]
+}
+
+/// Not meant to be called directly.
+///
+/// *Note:* `PartialEq` compares the boxed representation byte-wise, so it only means "same
+/// immediate value or same object". Two equal `String`s are different objects and compare unequal.
+/// Use `Value::eq_ruby` and `Value::ruby_hash` (or `HashedValue`) for Ruby semantics.
+#[doc(hidden)]
+#[repr(C)]
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct MrValue {
+    _buf: [u8; 16]
+}
+
+impl MrValue {
+    /// # Safety
+    ///
+    /// The returned value must only be used with an open mruby state.
+    #[inline]
+    pub unsafe fn nil() -> MrValue {
+        mrb_ext_nil()
+    }
+
+    /// # Safety
+    ///
+    /// The returned value must only be used with an open mruby state.
+    #[inline]
+    pub unsafe fn bool(value: bool) -> MrValue {
+        if value {
+            mrb_ext_true()
+        } else {
+            mrb_ext_false()
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The returned value must only be used with an open mruby state.
+    #[inline]
+    pub unsafe fn fixnum(value: i32) -> MrValue {
+        mrb_ext_cint_to_fixnum(value as MrInt)
+    }
+
+    /// # Safety
+    ///
+    /// The returned value must only be used with an open mruby state.
+    #[inline]
+    pub unsafe fn fixnum_i64(value: i64) -> MrValue {
+        mrb_ext_cint_to_fixnum(value as MrInt)
+    }
+
+    /// # Safety
+    ///
+    /// The returned value must only be used with an open mruby state.
+    #[inline]
+    pub unsafe fn fixnum_u32(value: u32) -> Result<MrValue, MrubyError> {
+        MrValue::fixnum_u64(value as u64)
+    }
+
+    /// # Safety
+    ///
+    /// The returned value must only be used with an open mruby state.
+    #[inline]
+    pub unsafe fn fixnum_u64(value: u64) -> Result<MrValue, MrubyError> {
+        match MrInt::try_from(value) {
+            Ok(value) => Ok(mrb_ext_cint_to_fixnum(value)),
+            Err(_)    => Err(overflow(value, "Fixnum"))
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The returned value must only be used with an open mruby state.
+    #[inline]
+    pub unsafe fn fixnum_usize(value: usize) -> Result<MrValue, MrubyError> {
+        match MrInt::try_from(value) {
+            Ok(value) => Ok(mrb_ext_cint_to_fixnum(value)),
+            Err(_)    => Err(overflow(value, "Fixnum"))
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state.
+    #[inline]
+    pub unsafe fn float(mrb: *const MrState, value: f64) -> MrValue {
+        mrb_ext_cdouble_to_float(mrb, value as MrFloat)
+    }
+
+    /// Widens `value` to mruby's `f64` `Float`, which is exact.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state.
+    #[inline]
+    pub unsafe fn float_f32(mrb: *const MrState, value: f32) -> MrValue {
+        mrb_ext_cdouble_to_float(mrb, value as f64 as MrFloat)
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state.
+    #[inline]
+    pub unsafe fn string(mrb: *const MrState, value: &str) -> MrValue {
+        mrb_str_new(mrb, value.as_ptr(), value.len())
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state.
+    #[inline]
+    pub unsafe fn bytes(mrb: *const MrState, value: &[u8]) -> MrValue {
+        mrb_str_new(mrb, value.as_ptr(), value.len())
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state.
+    #[inline]
+    #[deprecated(note = "renamed to `MrValue::bytes`")]
+    pub unsafe fn string_bytes(mrb: *const MrState, value: &[u8]) -> MrValue {
+        MrValue::bytes(mrb, value)
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state.
+    #[inline]
+    pub unsafe fn symbol(mrb: *const MrState, value: &str) -> MrValue {
+        mrb_ext_sym_new(mrb, value.as_ptr(), value.len())
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `class` a class of it whose instances use `typ`.
+    #[inline]
+    pub unsafe fn obj<T: Any>(mrb: *const MrState, class: *const MrClass,
+                              obj: T, typ: &MrDataType) -> MrValue {
+        let rc = Rc::new(RefCell::new(obj));
+        let ptr: *const u8 = mem::transmute(rc);
+        let data = mrb_data_object_alloc(mrb, class, ptr, typ as *const MrDataType);
+
+        mrb_ext_data_value(data)
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `class` a class of it whose instances use `typ`.
+    #[inline]
+    pub unsafe fn obj_arc<T: Any + Send>(mrb: *const MrState, class: *const MrClass,
+                                         obj: T, typ: &MrDataType) -> MrValue {
+        let ptr = Arc::into_raw(Arc::new(Mutex::new(obj))) as *const u8;
+        let data = mrb_data_object_alloc(mrb, class, ptr, typ as *const MrDataType);
+
+        mrb_ext_data_value(data)
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and the elements of `value` live values of it.
+    #[inline]
+    pub unsafe fn array(mrb: *const MrState, value: Vec<MrValue>) -> MrValue {
+        let array = mrb_ary_new_capa(mrb, value.len() as MrInt);
+
+        for (i, value) in value.iter().enumerate() {
+            mrb_ary_set(mrb, array, i as MrInt, *value);
+        }
+
+        array
+    }
+
+    /// Appends `value` to the end of `array`. Fails if `array` is not an `Array` or if it is
+    /// frozen.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state, and `array` and `value` live values of it.
+    #[inline]
+    pub unsafe fn array_push(mrb: *const MrState, array: MrValue,
+                             value: MrValue) -> Result<(), MrubyError> {
+        MrValue::array_op(mrb, array, |raised| {
+            mrb_ext_ary_push_nothrow(mrb, array, value, raised)
+        }).map(|_| ())
+    }
+
+    /// Removes and returns the last element of `array`, or `nil` if it is empty. Fails if `array`
+    /// is not an `Array` or if it is frozen.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `array` a live value of it.
+    #[inline]
+    pub unsafe fn array_pop(mrb: *const MrState, array: MrValue) -> Result<MrValue, MrubyError> {
+        MrValue::array_op(mrb, array, |raised| mrb_ext_ary_pop_nothrow(mrb, array, raised))
+    }
+
+    /// Removes and returns the first element of `array`, or `nil` if it is empty. Fails if `array`
+    /// is not an `Array` or if it is frozen.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `array` a live value of it.
+    #[inline]
+    pub unsafe fn array_shift(mrb: *const MrState, array: MrValue) -> Result<MrValue, MrubyError> {
+        MrValue::array_op(mrb, array, |raised| mrb_ext_ary_shift_nothrow(mrb, array, raised))
+    }
+
+    /// Prepends `value` to `array`. Fails if `array` is not an `Array` or if it is frozen.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state, and `array` and `value` live values of it.
+    #[inline]
+    pub unsafe fn array_unshift(mrb: *const MrState, array: MrValue,
+                                value: MrValue) -> Result<(), MrubyError> {
+        MrValue::array_op(mrb, array, |raised| {
+            mrb_ext_ary_unshift_nothrow(mrb, array, value, raised)
+        }).map(|_| ())
+    }
+
+    /// Appends the elements of `other` to `array` in place and returns `array`. Fails if either
+    /// is not an `Array` or if `array` is frozen.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state, and `array` and `other` live values of it.
+    #[inline]
+    pub unsafe fn array_concat(mrb: *const MrState, array: MrValue,
+                               other: MrValue) -> Result<MrValue, MrubyError> {
+        if other.typ() != MrType::MRB_TT_ARRAY {
+            return Err(MrubyError::Cast("Array".to_owned()));
+        }
+
+        MrValue::array_op(mrb, array, |raised| {
+            mrb_ext_ary_concat_nothrow(mrb, array, other, raised)
+        })
+    }
+
+    #[inline]
+    unsafe fn array_op<F>(mrb: *const MrState, array: MrValue,
+                          nothrow: F) -> Result<MrValue, MrubyError>
+        where F: FnOnce(*mut bool) -> MrValue {
+        if array.typ() != MrType::MRB_TT_ARRAY {
+            return Err(MrubyError::Cast("Array".to_owned()));
+        }
+
+        let mut raised = false;
+
+        let result = nothrow(&mut raised as *mut bool);
+
+        if raised {
+            Err(exception(mrb, result))
+        } else {
+            Ok(result)
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and the keys and values in `value` live values of it.
+    #[inline]
+    pub unsafe fn hash(mrb: *const MrState, value: Vec<(MrValue, MrValue)>) -> MrValue {
+        let hash = mrb_hash_new_capa(mrb, value.len() as MrInt);
+
+        for &(key, value) in value.iter() {
+            mrb_hash_set(mrb, hash, key, value);
+        }
+
+        hash
+    }
+
+    /// Returns the value stored at `key` in `hash`, or its default value if `key` is missing.
+    /// Fails if `hash` is not a `Hash` or if looking `key` up raises.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state, and `hash` and `key` live values of it.
+    #[inline]
+    pub unsafe fn hash_get(mrb: *const MrState, hash: MrValue,
+                           key: MrValue) -> Result<MrValue, MrubyError> {
+        MrValue::hash_op(mrb, hash, |raised| mrb_ext_hash_get_nothrow(mrb, hash, key, raised))
+    }
+
+    /// Stores `value` at `key` in `hash`. Fails if `hash` is not a `Hash`, if it is frozen or if
+    /// hashing `key` raises.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state, and `hash`, `key` and `value` live values of it.
+    #[inline]
+    pub unsafe fn hash_set(mrb: *const MrState, hash: MrValue, key: MrValue,
+                           value: MrValue) -> Result<(), MrubyError> {
+        MrValue::hash_op(mrb, hash, |raised| {
+            mrb_ext_hash_set_nothrow(mrb, hash, key, value, raised)
+        }).map(|_| ())
+    }
+
+    /// Removes `key` from `hash` and returns its value, or `nil` if `key` is missing. Fails if
+    /// `hash` is not a `Hash`, if it is frozen or if looking `key` up raises.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state, and `hash` and `key` live values of it.
+    #[inline]
+    pub unsafe fn hash_delete(mrb: *const MrState, hash: MrValue,
+                              key: MrValue) -> Result<MrValue, MrubyError> {
+        MrValue::hash_op(mrb, hash, |raised| {
+            mrb_ext_hash_delete_nothrow(mrb, hash, key, raised)
+        })
+    }
+
+    /// Returns the keys of `hash` as an `Array` in insertion order. Fails if `hash` is not a
+    /// `Hash`.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `hash` a live value of it.
+    #[inline]
+    pub unsafe fn hash_keys(mrb: *const MrState, hash: MrValue) -> Result<MrValue, MrubyError> {
+        MrValue::hash_op(mrb, hash, |raised| mrb_ext_hash_keys_nothrow(mrb, hash, raised))
+    }
+
+    /// Returns the values of `hash` as an `Array` in insertion order. Fails if `hash` is not a
+    /// `Hash`.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `hash` a live value of it.
+    #[inline]
+    pub unsafe fn hash_values(mrb: *const MrState, hash: MrValue) -> Result<MrValue, MrubyError> {
+        MrValue::hash_op(mrb, hash, |raised| mrb_ext_hash_values_nothrow(mrb, hash, raised))
+    }
+
+    #[inline]
+    unsafe fn hash_op<F>(mrb: *const MrState, hash: MrValue,
+                         nothrow: F) -> Result<MrValue, MrubyError>
+        where F: FnOnce(*mut bool) -> MrValue {
+        if hash.typ() != MrType::MRB_TT_HASH {
+            return Err(MrubyError::Cast("Hash".to_owned()));
+        }
+
+        let mut raised = false;
+
+        let result = nothrow(&mut raised as *mut bool);
+
+        if raised {
+            Err(exception(mrb, result))
+        } else {
+            Ok(result)
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state, and `start` and `end` live values of it.
+    #[inline]
+    pub unsafe fn range(mrb: *const MrState, start: MrValue, end: MrValue, exclusive: bool) -> MrValue {
+        mrb_range_new(mrb, start, end, exclusive)
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and the elements of `env` live values of it.
+    #[inline]
+    pub unsafe fn proc(mrb: *const MrState, fun: MrFunc, env: Vec<MrValue>) -> MrValue {
+        mrb_ext_proc_new_cfunc_with_env(mrb, fun, env.len() as MrInt, env.as_ptr())
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state.
+    #[inline]
+    pub unsafe fn proc_from_fn(mrb: *const MrState, fun: MrFunc) -> MrValue {
+        // mrb_yield_argv reads self from the first env slot, so it cannot be left empty.
+        MrValue::proc(mrb, fun, vec![MrValue::nil()])
+    }
+
+    /// Calls `proc` with `args`, returning raised exceptions as `MrubyError::Exception`.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state, and `proc` and `args` live values of it.
+    pub unsafe fn call_proc(mrb: *const MrState, proc: MrValue,
+                            args: &[MrValue]) -> Result<MrValue, MrubyError> {
+        if proc.typ() != MrType::MRB_TT_PROC {
+            return Err(MrubyError::Cast("Proc".to_owned()));
+        }
+
+        protect(mrb, |mrb| {
+            mrb_yield_argv(mrb, proc, args.len() as MrInt, args.as_ptr())
+        }).map_err(|exc| exception(mrb, exc))
+    }
+
+    /// Creates a `Fiber` running `block`. Blocks defined in Rust cannot run in a fiber and
+    /// return a `FiberError`.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `block` a live value of it.
+    #[inline]
+    pub unsafe fn fiber(mrb: *const MrState, block: MrValue) -> Result<MrValue, MrubyError> {
+        let mut raised = false;
+
+        let value = mrb_ext_fiber_new_nothrow(mrb, block, &mut raised as *mut bool);
+
+        if raised {
+            Err(exception(mrb, value))
+        } else {
+            Ok(value)
+        }
+    }
+
+    /// Resumes `fiber`, returning the value it yields or finishes with. Resuming a fiber that has
+    /// already finished returns a `StopIteration` exception.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state, and `fiber` and `args` live values of it.
+    #[inline]
+    pub unsafe fn fiber_resume(mrb: *const MrState, fiber: MrValue,
+                               args: &[MrValue]) -> Result<MrValue, MrubyError> {
+        if fiber.typ() != MrType::MRB_TT_FIBER {
+            return Err(MrubyError::Cast("Fiber".to_owned()));
+        }
+
+        let mut raised = false;
+
+        let value = mrb_ext_fiber_resume_nothrow(mrb, fiber, args.len() as MrInt, args.as_ptr(),
+                                                 &mut raised as *mut bool);
+
+        if raised {
+            Err(exception(mrb, value))
+        } else {
+            Ok(value)
+        }
+    }
+
+    /// Yields `args` to the caller of the current fiber. Must be returned directly from the
+    /// function called by mruby.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state, `args` live values of it, and the result must be returned
+    /// directly from the function called by mruby.
+    #[inline]
+    pub unsafe fn fiber_yield(mrb: *const MrState, args: &[MrValue]) -> MrValue {
+        mrb_fiber_yield(mrb, args.len() as MrInt, args.as_ptr())
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `value` must stay valid for as long as the returned
+    /// value is used.
+    #[inline]
+    pub unsafe fn ptr(mrb: *const MrState, value: *const u8) -> MrValue {
+        mrb_ext_set_ptr(mrb, value)
+    }
+
+    /// Calls `method` with `args`, returning raised exceptions as `MrubyError::Exception`.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state, and `self` and `args` live values of it.
+    pub unsafe fn funcall(&self, mrb: *const MrState, method: &str,
+                          args: &[MrValue]) -> Result<MrValue, MrubyError> {
+        let sym = mrb_intern(mrb, method.as_ptr() as *const c_char, method.len());
+
+        let value = *self;
+
+        protect(mrb, |mrb| {
+            mrb_funcall_argv(mrb, value, sym, args.len() as MrInt, args.as_ptr())
+        }).map_err(|exc| exception(mrb, exc))
+    }
+
+    /// Not meant to be called directly.
+    ///
+    /// Reads the arguments of the current method call, failing unless there are between
+    /// `required` and `total` of them. Missing optional arguments are padded with `nil`.
+    #[doc(hidden)]
+    pub unsafe fn method_args(mrb: *const MrState, required: usize,
+                              total: usize) -> Result<Vec<MrValue>, MrubyError> {
+        let mut args: *const MrValue = ptr::null();
+        let mut count: MrInt = 0;
+
+        mrb_get_args(mrb, "*\0".as_ptr() as *const c_char, &mut args as *mut *const MrValue,
+                     &mut count as *mut MrInt);
+
+        let count = count as usize;
+
+        if count < required || count > total {
+            let expected = if required == total {
+                format!("{}", total)
+            } else {
+                format!("{}..{}", required, total)
+            };
+
+            return Err(MrubyError::Cast(format!("{} arguments, found {}", expected, count)));
+        }
+
+        let mut values = if count == 0 {
+            Vec::with_capacity(total)
+        } else {
+            slice::from_raw_parts(args, count).to_vec()
+        };
+
+        values.resize(total, MrValue::nil());
+
+        Ok(values)
+    }
+
+    /// Not meant to be called directly.
+    ///
+    /// Returns the value of a method call or raises the `Err` in mruby.
+    #[doc(hidden)]
+    pub unsafe fn method_return(mrb: *const MrState, result: Result<MrValue, MrubyError>) -> MrValue {
+        match result {
+            Ok(value) => value,
+            Err(err)  => raise(mrb, err.exception_class(), &err.to_string())
+        }
+    }
+
+    /// Not meant to be called directly.
+    ///
+    /// Runs `f`, turning a Rust panic into a `RustPanic` mruby exception (or a `RuntimeError` if
+    /// `RustPanic` is not defined) since unwinding into C is undefined behavior.
+    #[doc(hidden)]
+    pub unsafe fn catch_panic<F>(mrb: *const MrState, f: F) -> MrValue
+        where F: FnOnce() -> MrValue {
+
+        match panic::catch_unwind(AssertUnwindSafe(f)) {
+            Ok(value)  => value,
+            Err(error) => {
+                let message = match error.downcast_ref::<&'static str>() {
+                    Some(s) => *s,
+                    None    => match error.downcast_ref::<String>() {
+                        Some(s) => &s[..],
+                        None    => "Rust panic"
+                    }
+                };
+
+                if mrb_class_defined(mrb, "RustPanic\0".as_ptr() as *const c_char) {
+                    raise(mrb, "RustPanic", message)
+                } else {
+                    raise(mrb, "RuntimeError", message)
+                }
+            }
+        }
+    }
+
+    /// Encodes the value with `JSON.generate`. Returns an `Err` if the `mruby-json` gem is not
+    /// compiled in or if encoding raises.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `self` a live value of it.
+    #[inline]
+    pub unsafe fn to_json(&self, mrb: *const MrState) -> Result<String, MrubyError> {
+        json_module(mrb)?.funcall(mrb, "generate", &[*self])?.to_string(mrb)
+    }
+
+    /// Decodes `json` with `JSON.parse`. Returns an `Err` if the `mruby-json` gem is not compiled
+    /// in or if parsing raises.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state.
+    #[inline]
+    pub unsafe fn from_json(mrb: *const MrState, json: &str) -> Result<MrValue, MrubyError> {
+        json_module(mrb)?.funcall(mrb, "parse", &[MrValue::string(mrb, json)])
+    }
+
+    /// Calls `respond_to?` with `method` as a `Symbol`. Returns `false` when `respond_to?` raises
+    /// or does not return a boolean.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `self` a live value of it.
+    #[inline]
+    pub unsafe fn respond_to(&self, mrb: *const MrState, method: &str) -> bool {
+        let method = MrValue::symbol(mrb, method);
+
+        self.funcall(mrb, "respond_to?", &[method])
+            .and_then(|result| result.to_bool())
+            .unwrap_or(false)
+    }
+
+    #[inline]
+    pub fn is_nil(&self) -> bool {
+        unsafe {
+            mrb_ext_nil_p(*self)
+        }
+    }
+
+    #[inline]
+    pub fn is_bool(&self) -> bool {
+        match self.typ() {
+            MrType::MRB_TT_TRUE  => true,
+            MrType::MRB_TT_FALSE => !self.is_nil(),
+            _ => false
+        }
+    }
+
+    #[inline]
+    pub fn is_true(&self) -> bool {
+        self.typ() == MrType::MRB_TT_TRUE
+    }
+
+    #[inline]
+    pub fn is_false(&self) -> bool {
+        self.typ() == MrType::MRB_TT_FALSE && !self.is_nil()
+    }
+
+    #[inline]
+    pub fn is_integer(&self) -> bool {
+        self.typ() == MrType::MRB_TT_FIXNUM
+    }
+
+    #[inline]
+    pub fn is_float(&self) -> bool {
+        self.typ() == MrType::MRB_TT_FLOAT
+    }
+
+    #[inline]
+    pub fn is_string(&self) -> bool {
+        self.typ() == MrType::MRB_TT_STRING
+    }
+
+    #[inline]
+    pub fn is_symbol(&self) -> bool {
+        self.typ() == MrType::MRB_TT_SYMBOL
+    }
+
+    #[inline]
+    pub fn is_array(&self) -> bool {
+        self.typ() == MrType::MRB_TT_ARRAY
+    }
+
+    #[inline]
+    pub fn is_hash(&self) -> bool {
+        self.typ() == MrType::MRB_TT_HASH
+    }
+
+    #[inline]
+    pub fn is_data(&self) -> bool {
+        self.typ() == MrType::MRB_TT_DATA
+    }
+
+    #[inline]
+    pub fn is_class(&self) -> bool {
+        self.typ() == MrType::MRB_TT_CLASS
+    }
+
+    #[inline]
+    pub fn is_module(&self) -> bool {
+        self.typ() == MrType::MRB_TT_MODULE
+    }
+
+    #[inline]
+    pub fn is_proc(&self) -> bool {
+        self.typ() == MrType::MRB_TT_PROC
+    }
+
+    #[inline]
+    pub fn is_fiber(&self) -> bool {
+        self.typ() == MrType::MRB_TT_FIBER
+    }
+
+    /// # Safety
+    ///
+    /// `self` must be a live value of an open mruby state.
+    #[inline]
+    pub unsafe fn to_bool(&self) -> Result<bool, MrubyError> {
+        match self.typ() {
+            MrType::MRB_TT_FALSE => Ok(false),
+            MrType::MRB_TT_TRUE  => Ok(true),
+            _ => Err(MrubyError::Cast("TrueClass or FalseClass".to_owned()))
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `self` must be a live value of an open mruby state.
+    #[inline]
+    pub unsafe fn to_i32(&self) -> Result<i32, MrubyError> {
+        let value = self.to_i64()?;
+
+        i32::try_from(value).map_err(|_| overflow(value, "i32"))
+    }
+
+    /// # Safety
+    ///
+    /// `self` must be a live value of an open mruby state.
+    #[inline]
+    pub unsafe fn to_i64(&self) -> Result<i64, MrubyError> {
+        match self.typ() {
+            MrType::MRB_TT_FIXNUM => {
+                Ok(mrb_ext_fixnum_to_cint(*self) as i64)
+            },
+            _ => Err(MrubyError::Cast("Fixnum".to_owned()))
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `self` must be a live value of an open mruby state.
+    #[inline]
+    pub unsafe fn to_i64_exact(&self) -> Result<i64, MrubyError> {
+        match self.typ() {
+            MrType::MRB_TT_FLOAT => {
+                let value = mrb_ext_float_to_cdouble(*self) as f64;
+
+                if !value.is_finite() {
+                    Err(MrubyError::Cast(format!("finite Float, found {}", value)))
+                } else if value.fract() != 0.0 {
+                    Err(MrubyError::Cast(format!("integral Float, found {}", value)))
+                } else if value < i64::MIN as f64 || value >= i64::MAX as f64 {
+                    Err(MrubyError::Overflow(format!("{} does not fit in i64", value)))
+                } else {
+                    Ok(value as i64)
+                }
+            },
+            _ => self.to_i64().map_err(|_| MrubyError::Cast("Fixnum or Float".to_owned()))
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `self` must be a live value of an open mruby state.
+    #[inline]
+    pub unsafe fn to_u32(&self) -> Result<u32, MrubyError> {
+        let value = self.to_i64()?;
+
+        u32::try_from(value).map_err(|_| overflow(value, "u32"))
+    }
+
+    /// # Safety
+    ///
+    /// `self` must be a live value of an open mruby state.
+    #[inline]
+    pub unsafe fn to_u64(&self) -> Result<u64, MrubyError> {
+        let value = self.to_i64()?;
+
+        u64::try_from(value).map_err(|_| overflow(value, "u64"))
+    }
+
+    /// # Safety
+    ///
+    /// `self` must be a live value of an open mruby state.
+    #[inline]
+    pub unsafe fn to_usize(&self) -> Result<usize, MrubyError> {
+        let value = self.to_i64()?;
+
+        usize::try_from(value).map_err(|_| overflow(value, "usize"))
+    }
+
+    /// # Safety
+    ///
+    /// `self` must be a live value of an open mruby state.
+    #[inline]
+    pub unsafe fn to_isize(&self) -> Result<isize, MrubyError> {
+        let value = self.to_i64()?;
+
+        isize::try_from(value).map_err(|_| overflow(value, "isize"))
+    }
+
+    /// # Safety
+    ///
+    /// `self` must be a live value of an open mruby state.
+    #[inline]
+    pub unsafe fn to_f64(&self) -> Result<f64, MrubyError> {
+        match self.typ() {
+            MrType::MRB_TT_FIXNUM => {
+                Ok(mrb_ext_fixnum_to_cint(*self) as f64)
+            },
+            _ => self.to_f64_strict().map_err(|_| {
+                MrubyError::Cast("Float or Fixnum".to_owned())
+            })
+        }
+    }
+
+    /// Rounds the `f64` to the nearest `f32`, losing precision beyond `f32`'s 24-bit mantissa.
+    /// Values outside of `f32`'s range become infinite.
+    ///
+    /// # Safety
+    ///
+    /// `self` must be a live value of an open mruby state.
+    #[inline]
+    pub unsafe fn to_f32(&self) -> Result<f32, MrubyError> {
+        self.to_f64().map(|value| value as f32)
+    }
+
+    /// # Safety
+    ///
+    /// `self` must be a live value of an open mruby state.
+    #[inline]
+    pub unsafe fn to_f64_strict(&self) -> Result<f64, MrubyError> {
+        match self.typ() {
+            MrType::MRB_TT_FLOAT => {
+                Ok(mrb_ext_float_to_cdouble(*self) as f64)
+            },
+            _ => Err(MrubyError::Cast("Float".to_owned()))
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `self` a live value of it. The returned `&str` borrows
+    /// the mruby `String` and must not outlive it.
+    #[inline]
+    pub unsafe fn to_str(&self, mrb: *const MrState) -> Result<&str, MrubyError> {
+        match self.typ() {
+            MrType::MRB_TT_STRING => {
+                str::from_utf8(self.to_bytes(mrb)?).map_err(|_| invalid_utf8())
+            },
+            MrType::MRB_TT_SYMBOL => {
+                let s = mrb_ext_sym2name(mrb, *self);
+
+                CStr::from_ptr(s).to_str().map_err(|_| invalid_utf8())
+            },
+            _ => Err(MrubyError::Cast("String".to_owned()))
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `self` a live value of it. The returned `&str` borrows
+    /// the mruby `String` and must not outlive it.
+    #[inline]
+    pub unsafe fn to_str_strict(&self, mrb: *const MrState) -> Result<&str, MrubyError> {
+        match self.typ() {
+            MrType::MRB_TT_STRING => self.to_str(mrb),
+            _ => Err(MrubyError::Cast("String".to_owned()))
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `self` a live value of it. The returned `&str` borrows
+    /// the symbol table of `mrb` and must not outlive it.
+    #[inline]
+    pub unsafe fn to_sym(&self, mrb: *const MrState) -> Result<&str, MrubyError> {
+        match self.typ() {
+            MrType::MRB_TT_SYMBOL => self.to_str(mrb),
+            _ => Err(MrubyError::Cast("Symbol".to_owned()))
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `self` a live value of it.
+    #[inline]
+    pub unsafe fn to_string(&self, mrb: *const MrState) -> Result<String, MrubyError> {
+        match self.typ() {
+            MrType::MRB_TT_STRING => {
+                self.to_str(mrb).map(|s| s.to_owned())
+            },
+            MrType::MRB_TT_SYMBOL => self.to_str(mrb).map(|s| s.to_owned()),
+            _ => Err(MrubyError::Cast("String".to_owned()))
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `self` a live value of it. A borrowed result must not
+    /// outlive the mruby `String`.
+    #[inline]
+    pub unsafe fn to_string_lossy(&self, mrb: *const MrState) -> Result<Cow<'_, str>, MrubyError> {
+        match self.typ() {
+            MrType::MRB_TT_STRING => Ok(String::from_utf8_lossy(self.to_bytes(mrb)?)),
+            MrType::MRB_TT_SYMBOL => {
+                let s = mrb_ext_sym2name(mrb, *self);
+
+                Ok(CStr::from_ptr(s).to_string_lossy())
+            },
+            _ => Err(MrubyError::Cast("String".to_owned()))
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `self` must be a live value of an open mruby state. The returned slice borrows the mruby
+    /// `String` and must not outlive it.
+    #[inline]
+    pub unsafe fn to_bytes(&self, _mrb: *const MrState) -> Result<&[u8], MrubyError> {
+        match self.typ() {
+            MrType::MRB_TT_STRING => {
+                let mut len: MrInt = 0;
+                let ptr = mrb_ext_str_ptr_len(*self, &mut len) as *const u8;
+                let len = len as usize;
+
+                if len == 0 {
+                    Ok(&[])
+                } else {
+                    Ok(slice::from_raw_parts(ptr, len))
+                }
+            },
+            _ => Err(MrubyError::Cast("String".to_owned()))
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state, `self` a live value of it and `typ` the data type `T` was
+    /// wrapped with.
+    #[inline]
+    pub unsafe fn to_obj<T: Any>(&self, mrb: *const MrState,
+                                 typ: &MrDataType) -> Result<Rc<RefCell<T>>, MrubyError> {
+        match self.typ() {
+            MrType::MRB_TT_DATA => {
+                let ptr = mrb_data_get_ptr(mrb, *self, typ as *const MrDataType);
+                let rc: Rc<RefCell<T>> = mem::transmute(ptr);
+
+                let result = Ok(rc.clone());
+
+                mem::forget(rc);
+
+                result
+            },
+            _ => Err(MrubyError::Cast("Data(Rust Rc<RefCell<T>>)".to_owned()))
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state, `self` a live value of it and `typ` the data type `T` was
+    /// wrapped with.
+    #[inline]
+    pub unsafe fn to_obj_arc<T: Any + Send>(&self, mrb: *const MrState,
+                                            typ: &MrDataType) -> Result<Arc<Mutex<T>>, MrubyError> {
+        let ptr = match self.typ() {
+            MrType::MRB_TT_DATA => {
+                mrb_data_get_ptr(mrb, *self, typ as *const MrDataType) as *const Mutex<T>
+            },
+            _ => ptr::null()
+        };
+
+        if ptr.is_null() {
+            Err(MrubyError::Cast("Data(Rust Arc<Mutex<T>>)".to_owned()))
+        } else {
+            Arc::increment_strong_count(ptr);
+
+            Ok(Arc::from_raw(ptr))
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `self` a live value of it.
+    #[inline]
+    pub unsafe fn to_vec(&self, mrb: *const MrState) -> Result<Vec<MrValue>, MrubyError> {
+        match self.typ() {
+            MrType::MRB_TT_ARRAY => {
+                let len = mrb_ext_ary_len(mrb, *self);
+                let mut vec = Vec::with_capacity(len as usize);
+
+                for i in 0..len {
+                    vec.push(mrb_ary_ref(mrb, *self, i as MrInt));
+                }
+
+                Ok(vec)
+            },
+            _ => Err(MrubyError::Cast("Array".to_owned()))
+        }
+    }
+
+    /// Returns an iterator over the elements of an `Array` that reads them one by one instead of
+    /// collecting them like `to_vec`. Its length is fixed when it is created; if the `Array`
+    /// shrinks while iterating, the missing elements come back as `nil`.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `self` a live value of it.
+    #[inline]
+    pub unsafe fn array_iter(&self, mrb: *const MrState) -> Result<MrArrayIter, MrubyError> {
+        match self.typ() {
+            MrType::MRB_TT_ARRAY => {
+                Ok(MrArrayIter {
+                    mrb,
+                    array: *self,
+                    index: 0,
+                    len: mrb_ext_ary_len(mrb, *self)
+                })
+            },
+            _ => Err(MrubyError::Cast("Array".to_owned()))
+        }
+    }
+
+    /// Returns an iterator over the key-value pairs of a `Hash` in insertion order that looks the
+    /// values up one by one instead of collecting them like `to_hash`. The keys are snapshotted
+    /// in an `Array` when it is created; a key removed while iterating yields the `Hash`'s default
+    /// value, and a lookup that raises yields the exception in an `Err`. The `Array` is only kept
+    /// alive by the GC arena, so the arena must not be restored while iterating.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `self` a live value of it.
+    #[inline]
+    pub unsafe fn hash_iter(&self, mrb: *const MrState) -> Result<MrHashIter, MrubyError> {
+        let keys = MrValue::hash_keys(mrb, *self)?;
+
+        Ok(MrHashIter {
+            hash: *self,
+            keys: keys.array_iter(mrb)?
+        })
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `self` a live value of it.
+    #[inline]
+    pub unsafe fn to_hash(&self, mrb: *const MrState) -> Result<Vec<(MrValue, MrValue)>, MrubyError> {
+        match self.typ() {
+            MrType::MRB_TT_HASH => {
+                let keys = mrb_hash_keys(mrb, *self);
+                let len = mrb_ext_ary_len(mrb, keys);
+                let mut vec = Vec::with_capacity(len as usize);
+
+                for i in 0..len {
+                    let key = mrb_ary_ref(mrb, keys, i as MrInt);
+
+                    vec.push((key, mrb_hash_get(mrb, *self, key)));
+                }
+
+                Ok(vec)
+            },
+            _ => Err(MrubyError::Cast("Hash".to_owned()))
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `self` a live value of it.
+    #[inline]
+    pub unsafe fn to_range(&self, mrb: *const MrState) -> Result<(MrValue, MrValue, bool), MrubyError> {
+        match self.typ() {
+            MrType::MRB_TT_RANGE => {
+                Ok((mrb_ext_range_beg(mrb, *self), mrb_ext_range_end(mrb, *self),
+                    mrb_ext_range_excl(mrb, *self)))
+            },
+            _ => Err(MrubyError::Cast("Range".to_owned()))
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `self` must be a live value of an open mruby state.
+    #[inline]
+    pub unsafe fn to_class(&self) -> Result<*const MrClass, MrubyError> {
+        match self.typ() {
+            MrType::MRB_TT_CLASS => {
+                Ok(mrb_ext_class_ptr(*self))
+            },
+            _ => Err(MrubyError::Cast("Class".to_owned()))
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `self` must be a live value of an open mruby state.
+    #[inline]
+    pub unsafe fn to_module(&self) -> Result<*const MrClass, MrubyError> {
+        match self.typ() {
+            MrType::MRB_TT_MODULE => {
+                Ok(mrb_ext_class_ptr(*self))
+            },
+            _ => Err(MrubyError::Cast("Module".to_owned()))
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `self` must be a live value of an open mruby state.
+    #[inline]
+    pub unsafe fn to_ptr(&self) -> Result<*const u8, MrubyError> {
+        match self.typ() {
+            MrType::MRB_TT_CPTR => {
+                Ok(mrb_ext_get_ptr(*self))
+            },
+            _ => Err(MrubyError::Cast("Pointer".to_owned()))
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `self` a live value of it.
+    #[inline]
+    pub unsafe fn ivar_defined(&self, mrb: *const MrState, name: &str) -> bool {
+        let sym = mrb_intern(mrb, name.as_ptr() as *const c_char, name.len());
+
+        mrb_iv_defined(mrb, *self, sym)
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `self` a live value of it.
+    #[inline]
+    pub unsafe fn ivar_get(&self, mrb: *const MrState, name: &str) -> MrValue {
+        let sym = mrb_intern(mrb, name.as_ptr() as *const c_char, name.len());
+
+        mrb_iv_get(mrb, *self, sym)
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state, and `self` and `value` live values of it.
+    #[inline]
+    pub unsafe fn ivar_set(&self, mrb: *const MrState, name: &str, value: MrValue) {
+        let sym = mrb_intern(mrb, name.as_ptr() as *const c_char, name.len());
+
+        mrb_iv_set(mrb, *self, sym, value)
+    }
+
+    #[inline]
+    pub fn typ(&self) -> MrType {
+        unsafe { mrb_ext_type(*self) }
+    }
+}
+
+impl MrClass {
+    /// Defines instance method `name` on `class`, implemented by `fun`, e.g. a function generated
+    /// with `#[mruby_method]`.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `class` a class or module of it.
+    #[inline]
+    pub unsafe fn define_method(mrb: *const MrState, class: *const MrClass, name: &str,
+                                fun: MrFunc) {
+        let name = CString::new(name).unwrap();
+
+        mrb_define_method(mrb, class, name.as_ptr(), fun, 1 << 12);
+    }
+
+    /// Defines class method `name` on `class`, implemented by `fun`.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `class` a class or module of it.
+    #[inline]
+    pub unsafe fn define_class_method(mrb: *const MrState, class: *const MrClass, name: &str,
+                                      fun: MrFunc) {
+        let name = CString::new(name).unwrap();
+
+        mrb_define_class_method(mrb, class, name.as_ptr(), fun, 1 << 12);
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `class` a class or module of it.
+    #[inline]
+    pub unsafe fn cvar_defined(mrb: *const MrState, class: *const MrClass, name: &str) -> bool {
+        let sym = mrb_intern(mrb, name.as_ptr() as *const c_char, name.len());
+
+        mrb_cv_defined(mrb, mrb_ext_class_value(class), sym)
+    }
+
+    /// Returns `nil` if the class variable is undefined, like `ivar_get`, where `mrb_cv_get` would
+    /// raise a `NameError`.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `class` a class or module of it.
+    #[inline]
+    pub unsafe fn cvar_get(mrb: *const MrState, class: *const MrClass, name: &str) -> MrValue {
+        let sym = mrb_intern(mrb, name.as_ptr() as *const c_char, name.len());
+        let module = mrb_ext_class_value(class);
+
+        if mrb_cv_defined(mrb, module, sym) {
+            mrb_cv_get(mrb, module, sym)
+        } else {
+            MrValue::nil()
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state, `value` a live value of it and `class` a class or module
+    /// of it.
+    #[inline]
+    pub unsafe fn cvar_set(mrb: *const MrState, class: *const MrClass, name: &str,
+                           value: MrValue) {
+        let sym = mrb_intern(mrb, name.as_ptr() as *const c_char, name.len());
+
+        mrb_cv_set(mrb, mrb_ext_class_value(class), sym, value)
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `class` a class or module of it.
+    #[inline]
+    pub unsafe fn const_defined(mrb: *const MrState, class: *const MrClass, name: &str) -> bool {
+        let sym = mrb_intern(mrb, name.as_ptr() as *const c_char, name.len());
+
+        mrb_const_defined(mrb, mrb_ext_class_value(class), sym)
+    }
+
+    /// Returns `nil` if the constant is undefined, where `mrb_const_get` would raise a `NameError`.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `class` a class or module of it.
+    #[inline]
+    pub unsafe fn const_get(mrb: *const MrState, class: *const MrClass, name: &str) -> MrValue {
+        let sym = mrb_intern(mrb, name.as_ptr() as *const c_char, name.len());
+        let module = mrb_ext_class_value(class);
+
+        if mrb_const_defined(mrb, module, sym) {
+            mrb_const_get(mrb, module, sym)
+        } else {
+            MrValue::nil()
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state, `value` a live value of it and `class` a class or module
+    /// of it.
+    #[inline]
+    pub unsafe fn define_const(mrb: *const MrState, class: *const MrClass, name: &str,
+                               value: MrValue) {
+        let name = CString::new(name).unwrap();
+
+        mrb_define_const(mrb, class, name.as_ptr(), value);
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `class` a class or module of it.
+    #[inline]
+    pub unsafe fn define_const_i32(mrb: *const MrState, class: *const MrClass, name: &str,
+                                   value: i32) {
+        MrClass::define_const(mrb, class, name, MrValue::fixnum(value));
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `class` a class or module of it.
+    #[inline]
+    pub unsafe fn define_const_str(mrb: *const MrState, class: *const MrClass, name: &str,
+                                   value: &str) {
+        MrClass::define_const(mrb, class, name, MrValue::string(mrb, value));
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `class` a class or module of it.
+    #[inline]
+    pub unsafe fn define_const_bool(mrb: *const MrState, class: *const MrClass, name: &str,
+                                    value: bool) {
+        MrClass::define_const(mrb, class, name, MrValue::bool(value));
+    }
+
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state and `class` a class or module of it.
+    #[inline]
+    pub unsafe fn define_const_f64(mrb: *const MrState, class: *const MrClass, name: &str,
+                                   value: f64) {
+        MrClass::define_const(mrb, class, name, MrValue::float(mrb, value));
+    }
+}
+
+/// A `struct` owning a raw mruby state which gets closed when dropped.
+///
+/// It wraps the `MrValue` helpers that take a `*const MrState` into `&self` methods. The ones
+/// taking `MrValue`s stay `unsafe`, since nothing ties those values to this state or keeps them
+/// alive. The state is still reachable through `as_ptr` for the rest of the low-level API.
+pub struct MrubyVm(*const MrState);
+
+impl MrubyVm {
+    /// Opens a new mruby state with the default settings. (see `MrubyVmBuilder`)
+    pub fn new() -> MrubyVm {
+        MrubyVmBuilder::new().build().unwrap()
+    }
+
+    #[inline]
+    pub fn as_ptr(&self) -> *const MrState {
+        self.0
+    }
+
+    #[inline]
+    pub fn float(&self, value: f64) -> MrValue {
+        unsafe { MrValue::float(self.0, value) }
+    }
+
+    #[inline]
+    pub fn float_f32(&self, value: f32) -> MrValue {
+        unsafe { MrValue::float_f32(self.0, value) }
+    }
+
+    #[inline]
+    pub fn string(&self, value: &str) -> MrValue {
+        unsafe { MrValue::string(self.0, value) }
+    }
+
+    #[inline]
+    pub fn bytes(&self, value: &[u8]) -> MrValue {
+        unsafe { MrValue::bytes(self.0, value) }
+    }
+
+    #[inline]
+    pub fn symbol(&self, value: &str) -> MrValue {
+        unsafe { MrValue::symbol(self.0, value) }
+    }
+
+    /// # Safety
+    ///
+    /// Every element of `value` must be a live value of this state.
+    #[inline]
+    pub unsafe fn array(&self, value: Vec<MrValue>) -> MrValue {
+        MrValue::array(self.0, value)
+    }
+
+    /// # Safety
+    ///
+    /// `array` and `value` must be live values of this state.
+    #[inline]
+    pub unsafe fn array_push(&self, array: MrValue, value: MrValue) -> Result<(), MrubyError> {
+        MrValue::array_push(self.0, array, value)
+    }
+
+    /// # Safety
+    ///
+    /// `array` must be a live value of this state.
+    #[inline]
+    pub unsafe fn array_pop(&self, array: MrValue) -> Result<MrValue, MrubyError> {
+        MrValue::array_pop(self.0, array)
+    }
+
+    /// # Safety
+    ///
+    /// `array` must be a live value of this state.
+    #[inline]
+    pub unsafe fn array_shift(&self, array: MrValue) -> Result<MrValue, MrubyError> {
+        MrValue::array_shift(self.0, array)
+    }
+
+    /// # Safety
+    ///
+    /// `array` and `value` must be live values of this state.
+    #[inline]
+    pub unsafe fn array_unshift(&self, array: MrValue, value: MrValue) -> Result<(), MrubyError> {
+        MrValue::array_unshift(self.0, array, value)
+    }
+
+    /// # Safety
+    ///
+    /// `array` and `other` must be live values of this state.
+    #[inline]
+    pub unsafe fn array_concat(&self, array: MrValue,
+                               other: MrValue) -> Result<MrValue, MrubyError> {
+        MrValue::array_concat(self.0, array, other)
+    }
+
+    /// # Safety
+    ///
+    /// Every key and value of `value` must be a live value of this state.
+    #[inline]
+    pub unsafe fn hash(&self, value: Vec<(MrValue, MrValue)>) -> MrValue {
+        MrValue::hash(self.0, value)
+    }
+
+    /// # Safety
+    ///
+    /// `hash` and `key` must be live values of this state.
+    #[inline]
+    pub unsafe fn hash_get(&self, hash: MrValue, key: MrValue) -> Result<MrValue, MrubyError> {
+        MrValue::hash_get(self.0, hash, key)
+    }
+
+    /// # Safety
+    ///
+    /// `hash`, `key` and `value` must be live values of this state.
+    #[inline]
+    pub unsafe fn hash_set(&self, hash: MrValue, key: MrValue,
+                           value: MrValue) -> Result<(), MrubyError> {
+        MrValue::hash_set(self.0, hash, key, value)
+    }
+
+    /// # Safety
+    ///
+    /// `hash` and `key` must be live values of this state.
+    #[inline]
+    pub unsafe fn hash_delete(&self, hash: MrValue, key: MrValue) -> Result<MrValue, MrubyError> {
+        MrValue::hash_delete(self.0, hash, key)
+    }
+
+    /// # Safety
+    ///
+    /// `hash` must be a live value of this state.
+    #[inline]
+    pub unsafe fn hash_keys(&self, hash: MrValue) -> Result<MrValue, MrubyError> {
+        MrValue::hash_keys(self.0, hash)
+    }
+
+    /// # Safety
+    ///
+    /// `hash` must be a live value of this state.
+    #[inline]
+    pub unsafe fn hash_values(&self, hash: MrValue) -> Result<MrValue, MrubyError> {
+        MrValue::hash_values(self.0, hash)
+    }
+
+    /// # Safety
+    ///
+    /// `start` and `end` must be live values of this state.
+    #[inline]
+    pub unsafe fn range(&self, start: MrValue, end: MrValue, exclusive: bool) -> MrValue {
+        MrValue::range(self.0, start, end, exclusive)
+    }
+
+    /// # Safety
+    ///
+    /// Every element of `env` must be a live value of this state.
+    #[inline]
+    pub unsafe fn proc(&self, fun: MrFunc, env: Vec<MrValue>) -> MrValue {
+        MrValue::proc(self.0, fun, env)
+    }
+
+    #[inline]
+    pub fn proc_from_fn(&self, fun: MrFunc) -> MrValue {
+        unsafe { MrValue::proc_from_fn(self.0, fun) }
+    }
+
+    /// # Safety
+    ///
+    /// `proc` and every element of `args` must be live values of this state.
+    #[inline]
+    pub unsafe fn call_proc(&self, proc: MrValue, args: &[MrValue]) -> Result<MrValue, MrubyError> {
+        MrValue::call_proc(self.0, proc, args)
+    }
+
+    /// # Safety
+    ///
+    /// `block` must be a live value of this state.
+    #[inline]
+    pub unsafe fn fiber(&self, block: MrValue) -> Result<MrValue, MrubyError> {
+        MrValue::fiber(self.0, block)
+    }
+
+    /// # Safety
+    ///
+    /// `fiber` and every element of `args` must be live values of this state.
+    #[inline]
+    pub unsafe fn fiber_resume(&self, fiber: MrValue,
+                               args: &[MrValue]) -> Result<MrValue, MrubyError> {
+        MrValue::fiber_resume(self.0, fiber, args)
+    }
+
+    /// # Safety
+    ///
+    /// `value` and every element of `args` must be live values of this state.
+    #[inline]
+    pub unsafe fn funcall(&self, value: MrValue, method: &str,
+                          args: &[MrValue]) -> Result<MrValue, MrubyError> {
+        value.funcall(self.0, method, args)
+    }
+
+    /// # Safety
+    ///
+    /// `value` must be a live value of this state.
+    #[inline]
+    pub unsafe fn to_json(&self, value: MrValue) -> Result<String, MrubyError> {
+        value.to_json(self.0)
+    }
+
+    #[inline]
+    pub fn from_json(&self, json: &str) -> Result<MrValue, MrubyError> {
+        unsafe { MrValue::from_json(self.0, json) }
+    }
+
+    #[inline]
+    pub fn intern(&self, name: &str) -> u32 {
+        unsafe { mrb_intern(self.0, name.as_ptr() as *const c_char, name.len()) }
+    }
+
+    #[inline]
+    pub fn full_gc(&self) {
+        unsafe { mrb_full_gc(self.0) }
+    }
+
+    #[inline]
+    pub fn incremental_gc(&self) {
+        unsafe { mrb_incremental_gc(self.0) }
+    }
+
+    /// Reads and runs a `.rb` script or `.mrb` bytecode file in a new context named after `path`.
+    pub fn eval_file(&self, path: &Path) -> Result<MrValue, MrubyError> {
+        let mut file = File::open(path)?;
+        let context = unsafe { MrubyContext::new(self.0, Some(&path.to_string_lossy())) };
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("rb") => {
+                let mut script = String::new();
+                file.read_to_string(&mut script)?;
+
+                context.eval(&script)
+            },
+            Some("mrb") => {
+                let mut script = Vec::new();
+                file.read_to_end(&mut script)?;
+
+                context.load_irep(&script)
+            },
+            _ => Err(MrubyError::Filetype)
+        }
+    }
+}
+
+impl Default for MrubyVm {
+    fn default() -> MrubyVm {
+        MrubyVm::new()
+    }
+}
+
+impl Drop for MrubyVm {
+    fn drop(&mut self) {
+        unsafe {
+            mrb_close(self.0);
+        }
+    }
+}
+
+/// A `struct` wrapping a raw mruby `Class` together with its state, so that methods and constants
+/// can be defined without passing raw pointers around. Modules are wrapped by `MrubyModule`.
+#[derive(Clone, Copy)]
+pub struct MrubyClass {
+    mrb:   *const MrState,
+    class: *const MrClass
+}
+
+impl MrubyClass {
+    /// Wraps `class` of `mrb`.
+    ///
+    /// # Safety
+    ///
+    /// The `MrubyClass` must not outlive `mrb`.
+    #[inline]
+    pub unsafe fn new(mrb: *const MrState, class: *const MrClass) -> MrubyClass {
+        MrubyClass {
+            mrb,
+            class
+        }
+    }
+
+    #[inline]
+    pub fn as_ptr(&self) -> *const MrClass {
+        self.class
+    }
+
+    #[inline]
+    pub fn define_method(&self, name: &str, fun: MrFunc, aspec: u32) {
+        let name = CString::new(name).unwrap();
+
+        unsafe { mrb_define_method(self.mrb, self.class, name.as_ptr(), fun, aspec) }
+    }
+
+    #[inline]
+    pub fn define_class_method(&self, name: &str, fun: MrFunc, aspec: u32) {
+        let name = CString::new(name).unwrap();
+
+        unsafe { mrb_define_class_method(self.mrb, self.class, name.as_ptr(), fun, aspec) }
+    }
+
+    /// Defines the constant `name` holding `value`.
+    ///
+    /// # Safety
+    ///
+    /// `value` must be a live value of this `MrubyClass`'s state.
+    #[inline]
+    pub unsafe fn define_const(&self, name: &str, value: MrValue) {
+        MrClass::define_const(self.mrb, self.class, name, value)
+    }
+
+    #[inline]
+    pub fn include_module(&self, module: &MrubyModule) {
+        unsafe { mrb_include_module(self.mrb, self.class, module.module) }
+    }
+
+    /// Returns the name of the `Class` in an `Ok` or an `Err` if it is not valid UTF-8. The name is
+    /// copied since mruby may collect the `String` it is read from.
+    pub fn name(&self) -> Result<String, MrubyError> {
+        unsafe {
+            let name = mrb_class_name(self.mrb, self.class);
+
+            CStr::from_ptr(name).to_str().map(|name| name.to_owned()).map_err(|_| invalid_utf8())
+        }
+    }
+
+    /// Returns the superclass in a `Some` or `None` for `BasicObject` and modules.
+    pub fn superclass(&self) -> Option<MrubyClass> {
+        unsafe {
+            let class = mrb_ext_class_value(self.class);
+
+            match class.funcall(self.mrb, "superclass", &[]) {
+                Ok(superclass) => superclass.to_class().ok().map(|superclass| {
+                    MrubyClass::new(self.mrb, superclass)
+                }),
+                Err(_)         => None
+            }
+        }
+    }
+
+    /// Defines a `Class` named `name` inheriting from this one or returns it if it is already
+    /// defined with the same superclass. Returns an `Err` with the raised `TypeError` if `name` is
+    /// already defined as something else or with another superclass.
+    pub fn define_subclass(&self, name: &str) -> Result<MrubyClass, MrubyError> {
+        let name = CString::new(name).unwrap();
+
+        unsafe {
+            let class = protect(self.mrb, |mrb| {
+                mrb_ext_class_value(mrb_define_class(mrb, name.as_ptr(), self.class))
+            }).map_err(|exc| exception(self.mrb, exc))?;
+
+            Ok(MrubyClass::new(self.mrb, mrb_ext_class_ptr(class)))
+        }
+    }
+
+    /// Defines an exception `Class` named `name` inheriting from `StandardError`.
+    ///
+    /// # Safety
+    ///
+    /// The `MrubyClass` must not outlive `mrb`.
+    #[inline]
+    pub unsafe fn exception_subclass(mrb: *const MrState,
+                                     name: &str) -> Result<MrubyClass, MrubyError> {
+        MrubyClass::builtin_subclass(mrb, "StandardError", name)
+    }
+
+    /// Defines an exception `Class` named `name` inheriting from `ArgumentError`.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state that outlives the returned `MrubyClass`.
+    #[inline]
+    pub unsafe fn argument_error_subclass(mrb: *const MrState,
+                                          name: &str) -> Result<MrubyClass, MrubyError> {
+        MrubyClass::builtin_subclass(mrb, "ArgumentError", name)
+    }
+
+    /// Defines an exception `Class` named `name` inheriting from `TypeError`.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state that outlives the returned `MrubyClass`.
+    #[inline]
+    pub unsafe fn type_error_subclass(mrb: *const MrState,
+                                      name: &str) -> Result<MrubyClass, MrubyError> {
+        MrubyClass::builtin_subclass(mrb, "TypeError", name)
+    }
+
+    /// Defines an exception `Class` named `name` inheriting from `RuntimeError`.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be an open mruby state that outlives the returned `MrubyClass`.
+    #[inline]
+    pub unsafe fn runtime_error_subclass(mrb: *const MrState,
+                                         name: &str) -> Result<MrubyClass, MrubyError> {
+        MrubyClass::builtin_subclass(mrb, "RuntimeError", name)
+    }
+
+    // mrb_class_get raises on undefined names; only used for classes every mruby defines
+    unsafe fn builtin_subclass(mrb: *const MrState, superclass: &str,
+                               name: &str) -> Result<MrubyClass, MrubyError> {
+        let superclass = CString::new(superclass).unwrap();
+
+        MrubyClass::new(mrb, mrb_class_get(mrb, superclass.as_ptr())).define_subclass(name)
+    }
+}
+
+/// A `struct` wrapping a raw mruby `Module` together with its state, like `MrubyClass`.
+#[derive(Clone, Copy)]
+pub struct MrubyModule {
+    mrb:    *const MrState,
+    module: *const MrClass
+}
+
+impl MrubyModule {
+    /// Wraps `module` of `mrb` in an `Ok` or returns an `Err` if it is a `Class` rather than a
+    /// `Module`.
+    ///
+    /// # Safety
+    ///
+    /// The `MrubyModule` must not outlive `mrb`.
+    #[inline]
+    pub unsafe fn new(mrb: *const MrState,
+                      module: *const MrClass) -> Result<MrubyModule, MrubyError> {
+        if mrb_ext_class_type(module) != MrType::MRB_TT_MODULE {
+            return Err(MrubyError::Cast("Module".to_owned()));
+        }
+
+        Ok(MrubyModule {
+            mrb,
+            module
+        })
+    }
+
+    #[inline]
+    pub fn as_ptr(&self) -> *const MrClass {
+        self.module
+    }
+
+    #[inline]
+    pub fn define_module_function(&self, name: &str, fun: MrFunc, aspec: u32) {
+        let name = CString::new(name).unwrap();
+
+        unsafe { mrb_define_module_function(self.mrb, self.module, name.as_ptr(), fun, aspec) }
+    }
+
+    /// Defines the constant `name` holding `value`.
+    ///
+    /// # Safety
+    ///
+    /// `value` must be a live value of this `MrubyModule`'s state.
+    #[inline]
+    pub unsafe fn define_const(&self, name: &str, value: MrValue) {
+        MrClass::define_const(self.mrb, self.module, name, value)
+    }
+
+    #[inline]
+    pub fn include_into(&self, class: &MrubyClass) {
+        class.include_module(self);
+    }
+
+    /// Returns the name of the `Module` in an `Ok` or an `Err` if it is not valid UTF-8, like
+    /// `MrubyClass::name`.
+    pub fn name(&self) -> Result<String, MrubyError> {
+        unsafe {
+            let name = mrb_class_name(self.mrb, self.module);
+
+            CStr::from_ptr(name).to_str().map(|name| name.to_owned()).map_err(|_| invalid_utf8())
+        }
+    }
+}
+
+/// A `struct` owning an mruby compiler context which gets freed when dropped.
+pub struct MrubyContext {
+    mrb: *const MrState,
+    ctx: *const MrContext
+}
+
+impl MrubyContext {
+    /// Creates a context on `mrb`, naming the scripts it evaluates `filename` in backtraces.
+    ///
+    /// # Safety
+    ///
+    /// The context must not outlive `mrb`.
+    pub unsafe fn new(mrb: *const MrState, filename: Option<&str>) -> MrubyContext {
+        let ctx = mrbc_context_new(mrb);
+
+        if let Some(filename) = filename {
+            let filename_str = CString::new(filename).unwrap();
+
+            mrbc_filename(mrb, ctx, filename_str.as_ptr());
+        }
+
+        MrubyContext {
+            mrb,
+            ctx
+        }
+    }
+
+    #[inline]
+    pub fn as_ptr(&self) -> *const MrContext {
+        self.ctx
+    }
+
+    /// Evaluates `code` and returns its result or the raised exception as an `Err`.
+    pub fn eval(&self, code: &str) -> Result<MrValue, MrubyError> {
+        unsafe {
+            let value = mrb_ext_load_nstring_cxt_nothrow(self.mrb, code.as_ptr(), code.len(),
+                                                         self.ctx);
+            let exc = mrb_ext_take_exc(self.mrb);
+
+            if exc.is_nil() {
+                Ok(value)
+            } else {
+                Err(exception(self.mrb, exc))
+            }
+        }
+    }
+
+    /// Loads precompiled `.mrb` bytecode and returns its result or the raised exception as an
+    /// `Err`. `code` is copied before being run, so it does not need to be aligned.
+    pub fn load_irep(&self, code: &[u8]) -> Result<MrValue, MrubyError> {
+        // RITE binary header: ident, version, crc, size, compiler name and version
+        const HEADER_SIZE: usize = 22;
+
+        if code.len() < HEADER_SIZE {
+            return Err(MrubyError::runtime("irep binary is truncated".to_owned()));
+        }
+
+        let size = code[10..14].iter().fold(0, |size, &byte| size << 8 | byte as usize);
+
+        if size > code.len() {
+            return Err(MrubyError::runtime("irep binary is truncated".to_owned()));
+        }
+
+        unsafe {
+            let value = mrb_ext_load_irep_cxt_suppress_alignment(self.mrb, code.as_ptr(),
+                                                                 self.ctx);
+            let exc = mrb_ext_take_exc(self.mrb);
+
+            if exc.is_nil() {
+                Ok(value)
+            } else {
+                Err(exception(self.mrb, exc))
+            }
+        }
+    }
+}
+
+impl Drop for MrubyContext {
+    fn drop(&mut self) {
+        unsafe {
+            mrbc_context_free(self.mrb, self.ctx);
+        }
+    }
+}
+
+/// A builder for `MrubyVm` setting up the allocator, GC thresholds and `ud` pointer of the mruby
+/// state before it is opened.
+///
+/// # Examples
+///
+/// ```
+/// # use mrusty::MrubyVmBuilder;
+/// let vm = MrubyVmBuilder::new()
+///     .gc_interval_ratio(150)
+///     .gc_step_ratio(400)
+///     .build()
+///     .unwrap();
+///
+/// assert!(!vm.as_ptr().is_null());
+/// ```
+pub struct MrubyVmBuilder {
+    allocf:            Option<(MrAllocf, *const u8)>,
+    gc_interval_ratio: Option<i32>,
+    gc_step_ratio:     Option<i32>,
+    ud:                Option<*const u8>
+}
+
+impl MrubyVmBuilder {
+    pub fn new() -> MrubyVmBuilder {
+        MrubyVmBuilder {
+            allocf:            None,
+            gc_interval_ratio: None,
+            gc_step_ratio:     None,
+            ud:                None
+        }
+    }
+
+    /// Uses `allocf` for every allocation of the state, passing it `ud`.
+    pub fn allocf(mut self, allocf: MrAllocf, ud: *const u8) -> MrubyVmBuilder {
+        self.allocf = Some((allocf, ud));
+        self
+    }
+
+    /// Sets the GC interval ratio in percent. (mruby defaults to 200)
+    pub fn gc_interval_ratio(mut self, ratio: i32) -> MrubyVmBuilder {
+        self.gc_interval_ratio = Some(ratio);
+        self
+    }
+
+    /// Sets the GC step ratio in percent. (mruby defaults to 200)
+    pub fn gc_step_ratio(mut self, ratio: i32) -> MrubyVmBuilder {
+        self.gc_step_ratio = Some(ratio);
+        self
+    }
+
+    /// Sets the initial `ud` pointer of the state.
+    ///
+    /// *Note:* `Mruby` keeps its own data in `ud`; only use it for states that are not wrapped.
+    pub fn ud(mut self, ud: *const u8) -> MrubyVmBuilder {
+        self.ud = Some(ud);
+        self
+    }
+
+    /// Opens the mruby state or returns an `Err` if it could not be allocated.
+    pub fn build(self) -> Result<MrubyVm, MrubyError> {
+        unsafe {
+            let mrb = match self.allocf {
+                Some((allocf, ud)) => mrb_open_allocf(allocf, ud),
+                None               => mrb_open()
+            };
+
+            if mrb.is_null() {
+                return Err(MrubyError::runtime("could not open mruby state".to_owned()));
+            }
+
+            if let Some(ratio) = self.gc_interval_ratio {
+                mrb_ext_gc_set_interval_ratio(mrb, ratio);
+            }
+
+            if let Some(ratio) = self.gc_step_ratio {
+                mrb_ext_gc_set_step_ratio(mrb, ratio);
+            }
+
+            if let Some(ud) = self.ud {
+                mrb_ext_set_ud(mrb, ud);
+            }
+
+            Ok(MrubyVm(mrb))
+        }
+    }
+}
+
+impl Default for MrubyVmBuilder {
+    fn default() -> MrubyVmBuilder {
+        MrubyVmBuilder::new()
+    }
+}
+
+/// Not meant to be called directly.
+#[doc(hidden)]
+pub extern "C" fn free_rc<T: Any>(_mrb: *const MrState, ptr: *const u8) {
+    if !ptr.is_null() {
+        unsafe {
+            drop(mem::transmute::<*const u8, Rc<RefCell<T>>>(ptr));
+        }
+    }
 }
 
 /// Not meant to be called directly.
 #[doc(hidden)]
-#[repr(C)]
-#[derive(Clone, Copy, Eq, PartialEq)]
-pub struct MrValue {
-    _buf: [u8; 16]
+pub extern "C" fn free_arc<T: Any + Send>(_mrb: *const MrState, ptr: *const u8) {
+    if !ptr.is_null() {
+        unsafe {
+            Arc::decrement_strong_count(ptr as *const Mutex<T>);
+        }
+    }
 }
 
-impl MrValue {
+/// A `trait` used to cast Rust types to raw `MrValue`s generically. `mrb` must be an open mruby
+/// state, e.g. the one of an `MrubyVm`.
+///
+/// # Examples
+///
+/// ```
+/// use mrusty::{FromMrValue, IntoMrValue, MrubyVm};
+///
+/// let vm = MrubyVm::new();
+/// let value = vec![Some(1), None].into_mrvalue(vm.as_ptr());
+///
+/// assert_eq!(Vec::<Option<i32>>::from_mrvalue(vm.as_ptr(), value).unwrap(), vec![Some(1), None]);
+/// ```
+pub trait IntoMrValue {
+    fn into_mrvalue(self, mrb: *const MrState) -> MrValue;
+}
+
+/// A `trait` used to cast raw `MrValue`s to Rust types generically. `mrb` must be the open mruby
+/// state `value` belongs to.
+///
+/// # Examples
+///
+/// ```
+/// use mrusty::{FromMrValue, IntoMrValue, MrubyVm};
+///
+/// let vm = MrubyVm::new();
+/// let value = "hi".into_mrvalue(vm.as_ptr());
+///
+/// assert_eq!(String::from_mrvalue(vm.as_ptr(), value).unwrap(), "hi");
+/// assert!(Option::<i32>::from_mrvalue(vm.as_ptr(), value).is_err());
+/// ```
+pub trait FromMrValue: Sized {
+    fn from_mrvalue(mrb: *const MrState, value: MrValue) -> Result<Self, MrubyError>;
+}
+
+impl IntoMrValue for MrValue {
     #[inline]
-    pub unsafe fn nil() -> MrValue {
-        mrb_ext_nil()
+    fn into_mrvalue(self, _mrb: *const MrState) -> MrValue {
+        self
     }
+}
 
+impl IntoMrValue for bool {
     #[inline]
-    pub unsafe fn bool(value: bool) -> MrValue {
-        if value {
-            mrb_ext_true()
-        } else {
-            mrb_ext_false()
+    fn into_mrvalue(self, _mrb: *const MrState) -> MrValue {
+        unsafe {
+            MrValue::bool(self)
         }
     }
+}
 
+impl IntoMrValue for i32 {
     #[inline]
-    pub unsafe fn fixnum(value: i32) -> MrValue {
-        mrb_ext_cint_to_fixnum(value as MrInt)
+    fn into_mrvalue(self, _mrb: *const MrState) -> MrValue {
+        unsafe {
+            MrValue::fixnum(self)
+        }
     }
+}
 
+impl IntoMrValue for i64 {
     #[inline]
-    pub unsafe fn float(mrb: *const MrState, value: f64) -> MrValue {
-        mrb_ext_cdouble_to_float(mrb, value as MrFloat)
+    fn into_mrvalue(self, _mrb: *const MrState) -> MrValue {
+        unsafe {
+            MrValue::fixnum_i64(self)
+        }
     }
+}
 
+impl IntoMrValue for u32 {
     #[inline]
-    pub unsafe fn string(mrb: *const MrState, value: &str) -> MrValue {
-        mrb_str_new(mrb, value.as_ptr(), value.len())
+    fn into_mrvalue(self, _mrb: *const MrState) -> MrValue {
+        unsafe {
+            MrValue::fixnum_i64(self as i64)
+        }
     }
+}
 
+impl IntoMrValue for f64 {
     #[inline]
-    pub unsafe fn symbol(mrb: *const MrState, value: &str) -> MrValue {
-        mrb_ext_sym_new(mrb, value.as_ptr(), value.len())
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn into_mrvalue(self, mrb: *const MrState) -> MrValue {
+        unsafe {
+            MrValue::float(mrb, self)
+        }
     }
+}
 
+impl IntoMrValue for String {
     #[inline]
-    pub unsafe fn obj<T: Any>(mrb: *const MrState, class: *const MrClass,
-                              obj: T, typ: &MrDataType) -> MrValue {
-        let rc = Rc::new(RefCell::new(obj));
-        let ptr: *const u8 = mem::transmute(rc);
-        let data = mrb_data_object_alloc(mrb, class, ptr, typ as *const MrDataType);
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn into_mrvalue(self, mrb: *const MrState) -> MrValue {
+        unsafe {
+            MrValue::string(mrb, &self)
+        }
+    }
+}
 
-        mrb_ext_data_value(data)
+impl IntoMrValue for &str {
+    #[inline]
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn into_mrvalue(self, mrb: *const MrState) -> MrValue {
+        unsafe {
+            MrValue::string(mrb, self)
+        }
     }
+}
 
+impl<T: IntoMrValue> IntoMrValue for Vec<T> {
     #[inline]
-    pub unsafe fn array(mrb: *const MrState, value: Vec<MrValue>) -> MrValue {
-        let array = mrb_ary_new_capa(mrb, value.len() as MrInt);
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn into_mrvalue(self, mrb: *const MrState) -> MrValue {
+        unsafe {
+            let array = self.into_iter().map(|value| value.into_mrvalue(mrb)).collect();
 
-        for (i, value) in value.iter().enumerate() {
-            mrb_ary_set(mrb, array, i as MrInt, *value);
+            MrValue::array(mrb, array)
         }
+    }
+}
 
-        array
+impl<T: IntoMrValue> IntoMrValue for Option<T> {
+    #[inline]
+    fn into_mrvalue(self, mrb: *const MrState) -> MrValue {
+        unsafe {
+            match self {
+                Some(value) => value.into_mrvalue(mrb),
+                None        => MrValue::nil()
+            }
+        }
     }
+}
 
+impl FromMrValue for MrValue {
     #[inline]
-    pub unsafe fn ptr(mrb: *const MrState, value: *const u8) -> MrValue {
-        mrb_ext_set_ptr(mrb, value)
+    fn from_mrvalue(_mrb: *const MrState, value: MrValue) -> Result<MrValue, MrubyError> {
+        Ok(value)
     }
+}
 
+impl FromMrValue for bool {
     #[inline]
-    pub unsafe fn to_bool<'a>(&self) -> Result<bool, MrubyError> {
-        match self.typ() {
-            MrType::MRB_TT_FALSE => Ok(false),
-            MrType::MRB_TT_TRUE  => Ok(true),
-            _ => Err(MrubyError::Cast("TrueClass or FalseClass".to_owned()))
+    fn from_mrvalue(_mrb: *const MrState, value: MrValue) -> Result<bool, MrubyError> {
+        unsafe {
+            value.to_bool()
         }
     }
+}
 
+impl FromMrValue for i32 {
     #[inline]
-    pub unsafe fn to_i32(&self) -> Result<i32, MrubyError> {
-        match self.typ() {
-            MrType::MRB_TT_FIXNUM => {
-                Ok(mrb_ext_fixnum_to_cint(*self) as i32)
-            },
-            _ => Err(MrubyError::Cast("Fixnum".to_owned()))
+    fn from_mrvalue(_mrb: *const MrState, value: MrValue) -> Result<i32, MrubyError> {
+        unsafe {
+            value.to_i32()
         }
     }
+}
 
+impl FromMrValue for i64 {
     #[inline]
-    pub unsafe fn to_f64(&self) -> Result<f64, MrubyError> {
-        match self.typ() {
-            MrType::MRB_TT_FLOAT => {
-                Ok(mrb_ext_float_to_cdouble(*self) as f64)
-            },
-            _ => Err(MrubyError::Cast("Float".to_owned()))
+    fn from_mrvalue(_mrb: *const MrState, value: MrValue) -> Result<i64, MrubyError> {
+        unsafe {
+            value.to_i64()
         }
     }
+}
 
+impl FromMrValue for u32 {
     #[inline]
-    pub unsafe fn to_str<'a>(&self, mrb: *const MrState) -> Result<&'a str, MrubyError> {
-        match self.typ() {
-            MrType::MRB_TT_STRING => {
-                let s = mrb_str_to_cstr(mrb, *self);
+    fn from_mrvalue(_mrb: *const MrState, value: MrValue) -> Result<u32, MrubyError> {
+        unsafe {
+            value.to_u32()
+        }
+    }
+}
 
-                Ok(CStr::from_ptr(s).to_str().unwrap().clone())
-            },
-            MrType::MRB_TT_SYMBOL => {
-                let s = mrb_ext_sym2name(mrb, *self);
+impl FromMrValue for f64 {
+    #[inline]
+    fn from_mrvalue(_mrb: *const MrState, value: MrValue) -> Result<f64, MrubyError> {
+        unsafe {
+            value.to_f64()
+        }
+    }
+}
 
-                Ok(CStr::from_ptr(s).to_str().unwrap().clone())
-            },
-            _ => Err(MrubyError::Cast("String".to_owned()))
+impl FromMrValue for String {
+    #[inline]
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn from_mrvalue(mrb: *const MrState, value: MrValue) -> Result<String, MrubyError> {
+        unsafe {
+            value.to_string(mrb)
         }
     }
+}
 
+impl<T: FromMrValue> FromMrValue for Vec<T> {
     #[inline]
-    pub unsafe fn to_obj<T: Any>(&self, mrb: *const MrState,
-                                 typ: &MrDataType) -> Result<Rc<RefCell<T>>, MrubyError> {
-        match self.typ() {
-            MrType::MRB_TT_DATA => {
-                let ptr = mrb_data_get_ptr(mrb, *self, typ as *const MrDataType);
-                let rc: Rc<RefCell<T>> = mem::transmute(ptr);
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn from_mrvalue(mrb: *const MrState, value: MrValue) -> Result<Vec<T>, MrubyError> {
+        unsafe {
+            value.to_vec(mrb)?.into_iter().map(|value| T::from_mrvalue(mrb, value)).collect()
+        }
+    }
+}
 
-                let result = Ok(rc.clone());
+impl<T: FromMrValue> FromMrValue for Option<T> {
+    #[inline]
+    fn from_mrvalue(mrb: *const MrState, value: MrValue) -> Result<Option<T>, MrubyError> {
+        if value.is_nil() {
+            Ok(None)
+        } else {
+            T::from_mrvalue(mrb, value).map(Some)
+        }
+    }
+}
 
-                mem::forget(rc);
+#[inline]
+unsafe fn json_module(mrb: *const MrState) -> Result<MrValue, MrubyError> {
+    let object = mrb_class_get(mrb, "Object\0".as_ptr() as *const c_char);
 
-                result
-            },
-            _ => Err(MrubyError::Cast("Data(Rust Rc<RefCell<T>>)".to_owned()))
+    if MrClass::const_defined(mrb, object, "JSON") {
+        Ok(MrClass::const_get(mrb, object, "JSON"))
+    } else {
+        Err(MrubyError::runtime("JSON is not defined; mruby needs the mruby-json gem".to_owned()))
+    }
+}
+
+#[inline]
+fn invalid_utf8() -> MrubyError {
+    MrubyError::Cast("String with valid UTF-8".to_owned())
+}
+
+#[inline]
+fn overflow<T: fmt::Display>(value: T, typ: &str) -> MrubyError {
+    MrubyError::Overflow(format!("{} does not fit in {}", value, typ))
+}
+
+/// Runs `f` under `mrb_protect`, returning the exception it raises in an `Err`.
+///
+/// `f` must not own values that need dropping, since a raise skips its remaining Rust frames.
+pub(crate) unsafe fn protect<F>(mrb: *const MrState, f: F) -> Result<MrValue, MrValue>
+    where F: FnOnce(*const MrState) -> MrValue {
+
+    extern "C" fn protected<F>(mrb: *const MrState, data: MrValue) -> MrValue
+        where F: FnOnce(*const MrState) -> MrValue {
+
+        unsafe {
+            let f = &mut *(data.to_ptr().unwrap() as *mut Option<F>);
+            let result = f.take().unwrap()(mrb);
+
+            mrb_ext_raise_current(mrb);
+
+            result
         }
     }
 
-    #[inline]
-    pub unsafe fn to_vec(&self, mrb: *const MrState) -> Result<Vec<MrValue>, MrubyError> {
-        match self.typ() {
-            MrType::MRB_TT_ARRAY => {
-                let len = mrb_ext_ary_len(mrb, *self);
-                let mut vec = Vec::with_capacity(len as usize);
+    let mut f = Some(f);
+    let data = MrValue::ptr(mrb, &mut f as *mut Option<F> as *const u8);
+    let mut state = false;
 
-                for i in 0..len {
-                    vec.push(mrb_ary_ref(mrb, *self, i as MrInt));
-                }
+    let value = mrb_protect(mrb, protected::<F>, data, &mut state as *const bool);
 
-                Ok(vec)
-            },
-            _ => Err(MrubyError::Cast("Array".to_owned()))
+    if state {
+        Err(value)
+    } else {
+        Ok(value)
+    }
+}
+
+/// Converts the raised mruby exception `exc` into an `MrubyError::Exception`.
+pub(crate) unsafe fn exception(mrb: *const MrState, exc: MrValue) -> MrubyError {
+    let (class, message, backtrace) = exception_parts(mrb, exc);
+
+    mrb_ext_keep_exc(mrb, exc);
+
+    MrubyError::Exception {
+        class,
+        message,
+        backtrace: if backtrace.is_empty() { None } else { Some(backtrace) }
+    }
+}
+
+/// Converts the exception `exc` raised by a script into an `MrubyError::Runtime`.
+pub(crate) unsafe fn runtime(mrb: *const MrState, exc: MrValue) -> MrubyError {
+    let (class, message, backtrace) = exception_parts(mrb, exc);
+
+    mrb_ext_keep_exc(mrb, exc);
+
+    MrubyError::Runtime {
+        class,
+        message,
+        backtrace
+    }
+}
+
+unsafe fn exception_parts(mrb: *const MrState, exc: MrValue) -> (String, String, Vec<String>) {
+    let class = CStr::from_ptr(mrb_class_name(mrb, mrb_ext_class(mrb, exc)));
+
+    // an exception raised by #message leaves the message empty
+    let message = mrb_ext_exc_str_nothrow(mrb, exc);
+
+    // scripts run without a filename carry no debug info and thus an empty backtrace
+    let backtrace = mrb_ext_exc_backtrace(mrb, exc).to_vec(mrb)
+        .map(|lines| lines.iter().filter_map(|line| line.to_string(mrb).ok()).collect())
+        .unwrap_or_default();
+
+    (class.to_string_lossy().into_owned(), message.to_string(mrb).unwrap_or_default(), backtrace)
+}
+
+/// Raises an mruby exception of class `eclass` with `message` without unwinding through Rust.
+///
+/// If the last exception turned into an `MrubyError` has the same class and message, that very
+/// exception is re-raised, keeping its backtrace.
+pub(crate) fn raise(mrb: *const MrState, eclass: &str, message: &str) -> MrValue {
+    unsafe {
+        let kept = mrb_ext_take_kept_exc(mrb);
+
+        if !kept.is_nil() {
+            let (class, kept_message, _) = exception_parts(mrb, kept);
+
+            if class == eclass && kept_message == message {
+                mrb_ext_exc_raise_nothrow(mrb, kept);
+
+                return MrValue::nil();
+            }
         }
+
+        let eclass_str = CString::new(eclass).unwrap();
+        let message_str = CString::new(message).unwrap();
+
+        mrb_ext_raise_nothrow(mrb, eclass_str.as_ptr(), message_str.as_ptr());
+
+        MrValue::nil()
     }
+}
 
+impl MrValue {
+    /// Returns a wrapper whose `Debug` output resolves symbol names, string contents and class
+    /// names through `mrb`.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must be the open mruby state `self` belongs to.
     #[inline]
-    pub unsafe fn to_class(&self) -> Result<*const MrClass, MrubyError> {
-        match self.typ() {
-            MrType::MRB_TT_CLASS => {
-                Ok(mrb_ext_class_ptr(*self))
-            },
-            _ => Err(MrubyError::Cast("Class".to_owned()))
+    pub unsafe fn debug_with(&self, mrb: *const MrState) -> MrValueDebug {
+        MrValueDebug {
+            value: *self,
+            mrb
         }
     }
 
-    #[inline]
-    pub unsafe fn to_module(&self) -> Result<*const MrClass, MrubyError> {
-        match self.typ() {
-            MrType::MRB_TT_MODULE => {
-                Ok(mrb_ext_class_ptr(*self))
+    unsafe fn fmt_debug(&self, mrb: Option<*const MrState>,
+                        f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self.typ() {
+            MrType::MRB_TT_FALSE if self.is_nil() => return write!(f, "nil"),
+            MrType::MRB_TT_FALSE  => return write!(f, "false"),
+            MrType::MRB_TT_TRUE   => return write!(f, "true"),
+            MrType::MRB_TT_FLOAT  => return write!(f, "Float({:?})", mrb_ext_float_to_cdouble(*self)),
+            MrType::MRB_TT_FIXNUM => return write!(f, "Fixnum({})", mrb_ext_fixnum_to_cint(*self)),
+            MrType::MRB_TT_SYMBOL => {
+                return match mrb.map(|mrb| self.to_sym(mrb)) {
+                    Some(Ok(name)) => write!(f, "Symbol(:{})", name),
+                    _ => write!(f, "Symbol({})", mrb_ext_symbol_to_cuint(*self))
+                };
             },
-            _ => Err(MrubyError::Cast("Module".to_owned()))
+            MrType::MRB_TT_UNDEF  => return write!(f, "Undef"),
+            MrType::MRB_TT_CPTR   => return write!(f, "Pointer({:?})", mrb_ext_get_ptr(*self)),
+            MrType::MRB_TT_STRING => {
+                if let Some(Ok(string)) = mrb.map(|mrb| self.to_string_lossy(mrb)) {
+                    return write!(f, "String({:?})", string);
+                }
+
+                "String"
+            },
+            MrType::MRB_TT_FREE      => "Free",
+            MrType::MRB_TT_OBJECT    => "Object",
+            MrType::MRB_TT_CLASS     => "Class",
+            MrType::MRB_TT_MODULE    => "Module",
+            MrType::MRB_TT_ICLASS    => "IClass",
+            MrType::MRB_TT_SCLASS    => "SClass",
+            MrType::MRB_TT_PROC      => "Proc",
+            MrType::MRB_TT_ARRAY     => "Array",
+            MrType::MRB_TT_HASH      => "Hash",
+            MrType::MRB_TT_RANGE     => "Range",
+            MrType::MRB_TT_EXCEPTION => "Exception",
+            MrType::MRB_TT_ENV       => "Env",
+            MrType::MRB_TT_DATA      => "Data",
+            MrType::MRB_TT_FIBER     => "Fiber",
+            MrType::MRB_TT_ISTRUCT   => "IStruct",
+            MrType::MRB_TT_BREAK     => "Break",
+            MrType::MRB_TT_MAXDEFINE => "MaxDefine"
+        };
+
+        let class_name = match (mrb, self.typ()) {
+            (_, MrType::MRB_TT_FREE) | (_, MrType::MRB_TT_ENV) |
+            (_, MrType::MRB_TT_ICLASS) | (_, MrType::MRB_TT_BREAK) => ptr::null(),
+            (Some(mrb), MrType::MRB_TT_CLASS) | (Some(mrb), MrType::MRB_TT_MODULE) |
+            (Some(mrb), MrType::MRB_TT_SCLASS) => mrb_class_name(mrb, mrb_ext_class_ptr(*self)),
+            (Some(mrb), _) => mrb_class_name(mrb, mrb_ext_class(mrb, *self)),
+            (None, _) => ptr::null()
+        };
+
+        if class_name.is_null() {
+            write!(f, "{}({:?})", name, mrb_ext_ptr_to_ptr(*self))
+        } else {
+            write!(f, "{}({})", name, CStr::from_ptr(class_name).to_string_lossy())
         }
     }
+}
+
+impl fmt::Debug for MrValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        unsafe { self.fmt_debug(None, f) }
+    }
+}
+
+/// A `struct` returned by `MrValue::debug_with` that formats an `MrValue` with names resolved
+/// through its mruby state.
+pub struct MrValueDebug {
+    value: MrValue,
+    mrb: *const MrState
+}
+
+impl fmt::Debug for MrValueDebug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        unsafe { self.value.fmt_debug(Some(self.mrb), f) }
+    }
+}
+
+/// A `struct` returned by `MrValue::array_iter` that yields the elements of an `Array` without
+/// allocating.
+pub struct MrArrayIter {
+    mrb: *const MrState,
+    array: MrValue,
+    index: MrInt,
+    len: MrInt
+}
+
+impl Iterator for MrArrayIter {
+    type Item = MrValue;
 
     #[inline]
-    pub unsafe fn to_ptr(&self) -> Result<*const u8, MrubyError> {
-        match self.typ() {
-            MrType::MRB_TT_CPTR => {
-                Ok(mrb_ext_get_ptr(*self))
-            },
-            _ => Err(MrubyError::Cast("Pointer".to_owned()))
+    fn next(&mut self) -> Option<MrValue> {
+        if self.index >= self.len {
+            return None;
         }
+
+        let value = unsafe { mrb_ary_ref(self.mrb, self.array, self.index) };
+
+        self.index += 1;
+
+        Some(value)
     }
 
     #[inline]
-    pub fn typ(&self) -> MrType {
-        unsafe { mrb_ext_type(*self) }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.len - self.index) as usize;
+
+        (len, Some(len))
     }
 }
 
-use std::fmt;
+impl ExactSizeIterator for MrArrayIter {}
 
-impl fmt::Debug for MrValue {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        unsafe {
-            write!(f, "p:{:?}, f:{}, i:{}, sym:{}, typ:{:?}",
-                mrb_ext_ptr_to_ptr(*self),
-                mrb_ext_float_to_cdouble(*self),
-                mrb_ext_fixnum_to_cint(*self),
-                mrb_ext_symbol_to_cuint(*self),
-                self.typ())
-        }
+/// A `struct` returned by `MrValue::hash_iter` that yields the key-value pairs of a `Hash`
+/// without collecting them.
+pub struct MrHashIter {
+    hash: MrValue,
+    keys: MrArrayIter
+}
+
+impl Iterator for MrHashIter {
+    type Item = Result<(MrValue, MrValue), MrubyError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Result<(MrValue, MrValue), MrubyError>> {
+        let mrb = self.keys.mrb;
+
+        self.keys.next().map(|key| {
+            unsafe {
+                MrValue::hash_get(mrb, self.hash, key).map(|value| (key, value))
+            }
+        })
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.keys.size_hint()
     }
 }
 
+impl ExactSizeIterator for MrHashIter {}
+
 #[allow(dead_code)]
 #[allow(non_camel_case_types)]
 #[repr(C)]
@@ -266,8 +2501,20 @@ pub enum MrType {
 
 extern "C" {
     pub fn mrb_open() -> *const MrState;
+    pub fn mrb_open_allocf(allocf: MrAllocf, ud: *const u8) -> *const MrState;
     pub fn mrb_close(mrb: *const MrState);
 
+    pub fn mrb_full_gc(mrb: *const MrState);
+    pub fn mrb_incremental_gc(mrb: *const MrState);
+    pub fn mrb_gc_register(mrb: *const MrState, obj: MrValue);
+    pub fn mrb_gc_unregister(mrb: *const MrState, obj: MrValue);
+    pub fn mrb_ext_gc_set_interval_ratio(mrb: *const MrState, ratio: i32);
+    pub fn mrb_ext_gc_set_step_ratio(mrb: *const MrState, ratio: i32);
+    pub fn mrb_ext_gc_enable(mrb: *const MrState) -> bool;
+    pub fn mrb_ext_gc_disable(mrb: *const MrState) -> bool;
+    pub fn mrb_ext_gc_arena_save(mrb: *const MrState) -> i32;
+    pub fn mrb_ext_gc_arena_restore(mrb: *const MrState, idx: i32);
+
     pub fn mrb_ext_get_ud(mrb: *const MrState) -> *const u8;
     pub fn mrb_ext_set_ud(mrb: *const MrState, ud: *const u8);
 
@@ -285,6 +2532,8 @@ extern "C" {
     pub fn mrb_class_defined(mrb: *const MrState, name: *const c_char) -> bool;
     pub fn mrb_ext_class_defined_under(mrb: *const MrState, outer: *const MrClass,
                                        name: *const c_char) -> bool;
+    pub fn mrb_ext_const_class_get(mrb: *const MrState, sym: u32, typ: MrType) -> *const MrClass;
+    pub fn mrb_ext_const_is(mrb: *const MrState, sym: u32, value: MrValue) -> bool;
 
     pub fn mrb_class_get(mrb: *const MrState, name: *const c_char) -> *const MrClass;
     pub fn mrb_module_get(mrb: *const MrState, name: *const c_char) -> *const MrClass;
@@ -311,6 +2560,7 @@ extern "C" {
     pub fn mrb_class_name(mrb: *const MrState, class: *const MrClass) -> *const c_char;
     pub fn mrb_ext_class_value(class: *const MrClass) -> MrValue;
     pub fn mrb_ext_module_value(module: *const MrClass) -> MrValue;
+    pub fn mrb_ext_class_type(class: *const MrClass) -> MrType;
 
     pub fn mrb_define_method(mrb: *const MrState, class: *const MrClass, name: *const c_char,
                              fun: MrFunc, aspec: u32);
@@ -320,8 +2570,13 @@ extern "C" {
     pub fn mrb_protect(mrb: *const MrState, fun: MrFunc, data: MrValue, state: *const bool) -> MrValue;
 
     pub fn mrb_ext_class(mrb: *const MrState, value: MrValue) -> *const MrClass;
+    pub fn mrb_obj_class(mrb: *const MrState, value: MrValue) -> *const MrClass;
+    pub fn mrb_obj_is_kind_of(mrb: *const MrState, object: MrValue, class: *const MrClass) -> bool;
+    pub fn mrb_ext_method_defined(mrb: *const MrState, class: *const MrClass, sym: u32) -> bool;
 
     pub fn mrb_get_args(mrb: *const MrState, format: *const c_char, ...) -> MrInt;
+    pub fn mrb_get_argc(mrb: *const MrState) -> MrInt;
+    pub fn mrb_get_argv(mrb: *const MrState) -> *const MrValue;
     pub fn mrb_ext_get_mid(mrb: *const MrState) -> u32;
 
     pub fn mrb_intern(mrb: *const MrState, string: *const c_char, len: usize) -> u32;
@@ -329,11 +2584,36 @@ extern "C" {
     // *const MrValue is MrValue array
     pub fn mrb_funcall_argv(mrb: *const MrState, object: MrValue, sym: u32, argc: MrInt,
                             argv: *const MrValue) -> MrValue;
+    pub fn mrb_yield_argv(mrb: *const MrState, block: MrValue, argc: MrInt,
+                          argv: *const MrValue) -> MrValue;
+    pub fn mrb_funcall_with_block(mrb: *const MrState, object: MrValue, sym: u32, argc: MrInt,
+                                  argv: *const MrValue, block: MrValue) -> MrValue;
+
+    pub fn mrb_ext_proc_new_cfunc_with_env(mrb: *const MrState, fun: MrFunc, argc: MrInt,
+                                           argv: *const MrValue) -> MrValue;
+    pub fn mrb_proc_cfunc_env_get(mrb: *const MrState, i: MrInt) -> MrValue;
+
+    pub fn mrb_ext_fiber_new_nothrow(mrb: *const MrState, block: MrValue,
+                                     raised: *mut bool) -> MrValue;
+    pub fn mrb_ext_fiber_resume_nothrow(mrb: *const MrState, fiber: MrValue, argc: MrInt,
+                                        argv: *const MrValue, raised: *mut bool) -> MrValue;
+    pub fn mrb_fiber_yield(mrb: *const MrState, argc: MrInt, argv: *const MrValue) -> MrValue;
 
     pub fn mrb_iv_defined(mrb: *const MrState, object: MrValue, sym: u32) -> bool;
     pub fn mrb_iv_get(mrb: *const MrState, object: MrValue, sym: u32) -> MrValue;
     pub fn mrb_iv_set(mrb: *const MrState, object: MrValue, sym: u32, value: MrValue);
 
+    pub fn mrb_gv_get(mrb: *const MrState, sym: u32) -> MrValue;
+    pub fn mrb_gv_set(mrb: *const MrState, sym: u32, value: MrValue);
+
+    pub fn mrb_cv_defined(mrb: *const MrState, module: MrValue, sym: u32) -> bool;
+    pub fn mrb_cv_get(mrb: *const MrState, module: MrValue, sym: u32) -> MrValue;
+    pub fn mrb_cv_set(mrb: *const MrState, module: MrValue, sym: u32, value: MrValue);
+
+    pub fn mrb_const_defined(mrb: *const MrState, module: MrValue, sym: u32) -> bool;
+    pub fn mrb_const_get(mrb: *const MrState, module: MrValue, sym: u32) -> MrValue;
+    pub fn mrb_const_set(mrb: *const MrState, module: MrValue, sym: u32, value: MrValue);
+
     pub fn mrb_ext_fixnum_to_cint(value: MrValue) -> MrInt;
     pub fn mrb_ext_float_to_cdouble(value: MrValue) -> MrFloat;
     pub fn mrb_ext_ptr_to_ptr(value: MrValue) -> *const u8;
@@ -341,11 +2621,13 @@ extern "C" {
     pub fn mrb_ext_type(value: MrValue) -> MrType;
 
     pub fn mrb_ext_nil() -> MrValue;
+    pub fn mrb_ext_nil_p(value: MrValue) -> bool;
     pub fn mrb_ext_false() -> MrValue;
     pub fn mrb_ext_true() -> MrValue;
     pub fn mrb_ext_cint_to_fixnum(value: MrInt) -> MrValue;
     pub fn mrb_ext_cdouble_to_float(mrb: *const MrState, value: MrFloat) -> MrValue;
     pub fn mrb_str_new(mrb: *const MrState, value: *const c_uchar, len: usize) -> MrValue;
+    pub fn mrb_ext_str_ptr_len(value: MrValue, len: *mut MrInt) -> *const c_char;
     pub fn mrb_ext_sym2name(mrb: *const MrState, value: MrValue) -> *const c_char;
     pub fn mrb_ext_sym_new(mrb: *const MrState, value: *const c_uchar, len: usize) -> MrValue;
     pub fn mrb_ext_get_ptr(value: MrValue) -> *const u8;
@@ -353,8 +2635,6 @@ extern "C" {
 
     pub fn mrb_ext_data_type(name: *const c_char, dfree: MrDfree) -> MrDataType;
 
-    pub fn mrb_str_to_cstr(mrb: *const MrState, value: MrValue) -> *const c_char;
-
     pub fn mrb_data_object_alloc(mrb: *const MrState, class: *const MrClass, ptr: *const u8,
                                  typ: *const MrDataType) -> *const MrData;
     pub fn mrb_data_get_ptr(mrb: *const MrState, value: MrValue,
@@ -369,13 +2649,48 @@ extern "C" {
     pub fn mrb_ary_ref(mrb: *const MrState, array: MrValue, i: MrInt) -> MrValue;
     pub fn mrb_ary_set(mrb: *const MrState, array: MrValue, i: MrInt, value: MrValue);
     pub fn mrb_ext_ary_len(mrb: *const MrState, array: MrValue) -> MrInt;
+    pub fn mrb_ext_ary_push_nothrow(mrb: *const MrState, array: MrValue, value: MrValue,
+                                    raised: *mut bool) -> MrValue;
+    pub fn mrb_ext_ary_pop_nothrow(mrb: *const MrState, array: MrValue, raised: *mut bool) -> MrValue;
+    pub fn mrb_ext_ary_shift_nothrow(mrb: *const MrState, array: MrValue, raised: *mut bool) -> MrValue;
+    pub fn mrb_ext_ary_unshift_nothrow(mrb: *const MrState, array: MrValue, value: MrValue,
+                                       raised: *mut bool) -> MrValue;
+    pub fn mrb_ext_ary_concat_nothrow(mrb: *const MrState, array: MrValue, other: MrValue,
+                                      raised: *mut bool) -> MrValue;
+
+    pub fn mrb_range_new(mrb: *const MrState, start: MrValue, end: MrValue, exclusive: bool) -> MrValue;
+    pub fn mrb_ext_range_beg(mrb: *const MrState, range: MrValue) -> MrValue;
+    pub fn mrb_ext_range_end(mrb: *const MrState, range: MrValue) -> MrValue;
+    pub fn mrb_ext_range_excl(mrb: *const MrState, range: MrValue) -> bool;
+
+    pub fn mrb_hash_new_capa(mrb: *const MrState, capa: MrInt) -> MrValue;
+    pub fn mrb_hash_set(mrb: *const MrState, hash: MrValue, key: MrValue, value: MrValue);
+    pub fn mrb_hash_get(mrb: *const MrState, hash: MrValue, key: MrValue) -> MrValue;
+    pub fn mrb_hash_keys(mrb: *const MrState, hash: MrValue) -> MrValue;
+    pub fn mrb_ext_hash_get_nothrow(mrb: *const MrState, hash: MrValue, key: MrValue,
+                                    raised: *mut bool) -> MrValue;
+    pub fn mrb_ext_hash_set_nothrow(mrb: *const MrState, hash: MrValue, key: MrValue,
+                                    value: MrValue, raised: *mut bool) -> MrValue;
+    pub fn mrb_ext_hash_delete_nothrow(mrb: *const MrState, hash: MrValue, key: MrValue,
+                                       raised: *mut bool) -> MrValue;
+    pub fn mrb_ext_hash_keys_nothrow(mrb: *const MrState, hash: MrValue, raised: *mut bool) -> MrValue;
+    pub fn mrb_ext_hash_values_nothrow(mrb: *const MrState, hash: MrValue, raised: *mut bool) -> MrValue;
 
     pub fn mrb_ext_raise_nothrow(mrb: *const MrState, eclass: *const c_char, msg: *const c_char);
     pub fn mrb_ext_raise_current(mrb: *const MrState);
-    pub fn mrb_ext_exc_str(mrb: *const MrState, exc: MrValue) -> MrValue;
+    pub fn mrb_ext_take_exc(mrb: *const MrState) -> MrValue;
+    pub fn mrb_ext_keep_exc(mrb: *const MrState, exc: MrValue);
+    pub fn mrb_ext_take_kept_exc(mrb: *const MrState) -> MrValue;
+    pub fn mrb_exc_new(mrb: *const MrState, class: *const MrClass, message: *const c_char,
+                       len: usize) -> MrValue;
+    pub fn mrb_ext_exc_raise_nothrow(mrb: *const MrState, exc: MrValue);
+    pub fn mrb_ext_exc_str_nothrow(mrb: *const MrState, exc: MrValue) -> MrValue;
+    pub fn mrb_ext_exc_backtrace(mrb: *const MrState, exc: MrValue) -> MrValue;
 
     pub fn mrb_ext_class_ptr(class: MrValue) -> *const MrClass;
 
+    pub fn mrb_ext_equal_nothrow(mrb: *const MrState, a: MrValue, b: MrValue) -> bool;
+
     pub fn mrb_ext_value_sizeof() -> usize;
     pub fn mrb_ext_data_type_sizeof() -> usize;
     pub fn mrb_ext_int_sizeof() -> usize;
@@ -385,4 +2700,7 @@ extern "C" {
 
 #[path="tests/mruby_ffi.rs"]
 #[cfg(test)]
+#[allow(clippy::assertions_on_constants, clippy::bool_assert_comparison,
+        clippy::transmute_ptr_to_ref, clippy::useless_transmute, clippy::uninit_assumed_init,
+        invalid_value, static_mut_refs)]
 mod tests;
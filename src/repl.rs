@@ -40,7 +40,7 @@ impl Repl {
     /// ```
     pub fn new(mruby: MrubyType) -> Repl {
         Repl {
-            mruby: mruby,
+            mruby,
             name: "mrusty".to_owned()
         }
     }
@@ -104,14 +104,14 @@ impl Repl {
                 let trimmed = input.trim_end_matches("\\");
 
                 command = command + trimmed + "\n";
-                read_line.add(&trimmed);
+                read_line.add(trimmed);
 
                 continue
             } else {
                 read_line.add(&input);
             }
 
-            if command == "" {
+            if command.is_empty() {
                 command = input;
             } else {
                 command = command + &input;
@@ -119,9 +119,9 @@ impl Repl {
 
             match self.mruby.run(&command) {
                 Ok(value) => {
-                    let result = value.call("to_s", vec![]).unwrap().to_str().unwrap();
+                    let result = value.call("to_s", vec![]).unwrap();
 
-                    println!("{}", result);
+                    println!("{}", result.to_str().unwrap());
                 },
                 Err(message) => {
                     println!("{}", message);
@@ -133,6 +133,6 @@ impl Repl {
             }
         }
 
-        println!("");
+        println!();
     }
 }
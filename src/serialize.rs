@@ -0,0 +1,125 @@
+// mrusty. mruby safe bindings for Rust
+// Copyright (C) 2016  Dragoș Tiselice
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use serde::ser::{Error, Serialize, SerializeMap, SerializeSeq, Serializer};
+
+use super::mruby::Value;
+use super::mruby_ffi::{MrState, MrType, MrValue};
+
+const MAX_DEPTH: usize = 128;
+
+/// A `struct` that serializes an `MrValue` tree with serde. `nil`, booleans, `Fixnum`s, `Float`s,
+/// `String`s and `Symbol`s map to their plain equivalents, `Array`s to sequences and `Hash`es to
+/// maps with `String` or `Symbol` keys. Any other type, invalid UTF-8 or nesting deeper than 128
+/// levels fails.
+///
+/// Only available with the `serde` feature.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate mrusty;
+/// # extern crate serde_json;
+/// # use mrusty::Mruby;
+/// # use mrusty::MrubyImpl;
+/// # fn main() {
+/// let mruby = Mruby::new();
+///
+/// let value = mruby.run("{ name: 'mruby', tags: [1, 2.5, nil, true] }").unwrap();
+///
+/// assert_eq!(serde_json::to_string(&value).unwrap(),
+///            r#"{"name":"mruby","tags":[1,2.5,null,true]}"#);
+/// # }
+/// ```
+pub struct MrValueSerializer {
+    value: MrValue,
+    mrb: *const MrState,
+    depth: usize
+}
+
+impl MrValueSerializer {
+    /// Wraps `value` so that it can be serialized.
+    ///
+    /// # Safety
+    ///
+    /// `mrb` must stay open while serializing, and `value` must be a live value of it.
+    #[inline]
+    pub unsafe fn new(mrb: *const MrState, value: MrValue) -> MrValueSerializer {
+        MrValueSerializer {
+            value,
+            mrb,
+            depth: 0
+        }
+    }
+
+    #[inline]
+    fn child(&self, value: MrValue) -> MrValueSerializer {
+        MrValueSerializer {
+            value,
+            mrb: self.mrb,
+            depth: self.depth + 1
+        }
+    }
+}
+
+impl Serialize for MrValueSerializer {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.depth > MAX_DEPTH {
+            return Err(S::Error::custom("nesting too deep"));
+        }
+
+        unsafe {
+            match self.value.typ() {
+                MrType::MRB_TT_FALSE if self.value.is_nil() => serializer.serialize_unit(),
+                MrType::MRB_TT_FALSE  => serializer.serialize_bool(false),
+                MrType::MRB_TT_TRUE   => serializer.serialize_bool(true),
+                MrType::MRB_TT_FIXNUM => {
+                    serializer.serialize_i64(self.value.to_i64().map_err(S::Error::custom)?)
+                },
+                MrType::MRB_TT_FLOAT  => {
+                    serializer.serialize_f64(self.value.to_f64().map_err(S::Error::custom)?)
+                },
+                MrType::MRB_TT_STRING | MrType::MRB_TT_SYMBOL => {
+                    serializer.serialize_str(self.value.to_str(self.mrb).map_err(S::Error::custom)?)
+                },
+                MrType::MRB_TT_ARRAY => {
+                    let vec = self.value.to_vec(self.mrb).map_err(S::Error::custom)?;
+                    let mut seq = serializer.serialize_seq(Some(vec.len()))?;
+
+                    for value in vec {
+                        seq.serialize_element(&self.child(value))?;
+                    }
+
+                    seq.end()
+                },
+                MrType::MRB_TT_HASH => {
+                    let pairs = self.value.to_hash(self.mrb).map_err(S::Error::custom)?;
+                    let mut map = serializer.serialize_map(Some(pairs.len()))?;
+
+                    for (key, value) in pairs {
+                        let key = key.to_str(self.mrb).map_err(|_| {
+                            S::Error::custom("Hash keys must be String or Symbol")
+                        })?;
+
+                        map.serialize_entry(key, &self.child(value))?;
+                    }
+
+                    map.end()
+                },
+                typ => Err(S::Error::custom(format!("cannot serialize {:?}", typ)))
+            }
+        }
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        unsafe {
+            MrValueSerializer::new(self.mruby.borrow().mrb, self.value).serialize(serializer)
+        }
+    }
+}
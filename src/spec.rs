@@ -213,7 +213,7 @@ impl Spec {
         Spec {
             script: script.to_owned(),
             target: name.to_owned(),
-            mruby: mruby
+            mruby
         }
     }
 
@@ -221,6 +221,42 @@ fn mrusty_class_mruby_values() {
     assert_eq!(result.to_bool().unwrap(), true);
 }
 
+#[test]
+fn mrusty_class_untyped_slf() {
+    let mruby = Mruby::new();
+
+    struct Cont {
+        value: i32
+    }
+
+    mrusty_class!(Cont, "Container", {
+        def!("initialize", |v: i32| {
+            Cont { value: v }
+        });
+
+        def!("value", |mruby, slf: (&Cont)| {
+            mruby.fixnum(slf.value)
+        });
+
+        def_self!("create", |mruby, slf| {
+            slf.call("new", vec![mruby.fixnum(2)]).unwrap()
+        });
+
+        def_self!("create_with", |mruby, slf, v: i32; &block| {
+            let v = block.call("call", vec![mruby.fixnum(v)]).unwrap();
+
+            slf.call("new", vec![v]).unwrap()
+        });
+    });
+
+    Cont::require(mruby.clone());
+
+    let result = mruby.run("[Container.create.value, Container.create_with(3) { |v| v * 3 }.value]")
+        .unwrap();
+
+    assert_eq!(result.to_vec().unwrap(), vec![mruby.fixnum(2), mruby.fixnum(9)]);
+}
+
 #[test]
 fn mrusty_initialize_block_only_values() {
     let mruby = Mruby::new();
@@ -1303,3 +1339,318 @@ fn mruby_class_args_block_mruby_values() {
 
     assert_eq!(result.to_i32().unwrap(), 3);
 }
+
+#[test]
+fn mrfn_result_raises() {
+    let mruby = Mruby::new();
+
+    struct Cont;
+
+    mruby.def_class_for::<Cont>("Container");
+    mruby.def_class_method_for::<Cont, _>("parse", mrfn!(|mruby, _slf: Value, a: Value|
+                                                         -> Result<Value, MrubyError> {
+        let s = a.to_str()?;
+
+        match s.parse::<i32>() {
+            Ok(n)  => Ok(mruby.fixnum(n)),
+            Err(_) => Err(MrubyError::runtime(format!("cannot parse {}", s)))
+        }
+    }));
+
+    let result = mruby.run("
+      [Container.parse('12'), *['x', 1].map do |a|
+        begin
+          Container.parse(a)
+        rescue ArgumentError => e
+          [:arg, e.message]
+        rescue RuntimeError => e
+          [:runtime, e.message]
+        end
+      end]
+    ").unwrap();
+
+    assert_eq!(result.call("inspect", vec![]).unwrap().to_str().unwrap(),
+               "[12, [:runtime, \"cannot parse x\"], \
+                [:arg, \"Cast error: expected String\"]]");
+}
+
+#[test]
+fn mrfn_result_exception_class() {
+    let mruby = Mruby::new();
+
+    struct Cont;
+
+    mruby.def_class_for::<Cont>("Container");
+    mruby.def_class_method_for::<Cont, _>("big", mrfn!(|mruby, _slf: Value|
+                                                       -> Result<Value, MrubyError> {
+        Ok(mruby.fixnum(mruby.fixnum_i64(1 << 40).to_i32()?))
+    }));
+
+    let result = mruby.run("
+      begin
+        Container.big
+      rescue RangeError => e
+        e.class
+      end
+    ").unwrap();
+
+    assert_eq!(result.to_class().unwrap().to_str(), "RangeError");
+}
+
+#[test]
+fn mrfn_result_reraises_exception() {
+    let mruby = Mruby::new();
+
+    struct Cont;
+
+    mruby.def_class_for::<Cont>("Container");
+    mruby.def_class_method_for::<Cont, _>("run", mrfn!(|mruby, _slf: Value, code: (&str)|
+                                                       -> Result<Value, MrubyError> {
+        mruby.run(code)
+    }));
+
+    let result = mruby.run("
+      module Outer
+        class Error < StandardError; end
+      end
+
+      e1 = begin
+        Container.run 'raise KeyError, \"x\"'
+      rescue KeyError => e
+        e
+      end
+
+      e2 = begin
+        Container.run 'raise Outer::Error'
+      rescue Outer::Error => e
+        e
+      end
+
+      [e1.class, e1.message, e2.class, e2.message]
+    ").unwrap();
+
+    assert_eq!(result.call("inspect", vec![]).unwrap().to_str().unwrap(),
+               "[KeyError, \"x\", Outer::Error, \"Outer::Error\"]");
+}
+
+#[test]
+fn symbol_enum_arguments() {
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Mode {
+        Fast,
+        Safe
+    }
+
+    symbol_enum!(Mode {
+        Fast => "fast",
+        Safe => "safe",
+    });
+
+    let mruby = Mruby::new();
+
+    struct Cont;
+
+    mruby.def_class_for::<Cont>("Container");
+    mruby.def_class_method_for::<Cont, _>("toggle", mrfn!(|_mruby, _slf: Value, mode: Mode|
+                                                          -> Result<Mode, MrubyError> {
+        match mode {
+            Mode::Fast => Ok(Mode::Safe),
+            Mode::Safe => Ok(Mode::Fast)
+        }
+    }));
+
+    let result = mruby.run("[Container.toggle(:fast), Container.toggle('safe')]").unwrap();
+
+    assert_eq!(result.to_s(), "[:safe, :fast]");
+
+    assert_eq!(Mode::from_symbol_name("safe"), Some(Mode::Safe));
+    assert_eq!(Mode::from_symbol_name("Safe"), None);
+    assert_eq!(Mode::Safe.to_symbol_name(), "safe");
+
+    match Mode::from_value(mruby.fixnum(1)) {
+        Err(MrubyError::Cast(expected)) => assert_eq!(expected, "one of :fast, :safe, found 1"),
+        _ => assert!(false)
+    }
+
+    match mruby.run("Container.toggle(:slow)") {
+        Err(MrubyError::Runtime { class, .. }) => assert_eq!(class, "ArgumentError"),
+        _ => assert!(false)
+    }
+}
+
+#[test]
+fn mrfn_default_arguments() {
+    let mruby = Mruby::new();
+
+    struct Cont;
+
+    mruby.def_class_for::<Cont>("Container");
+    mruby.def_class_method_for::<Cont, _>("take", mrfn!(|mruby, _slf: Value, count: i32,
+                                                         step: i32 = 1, name: (&str) = "x"| {
+        mruby.string(&format!("{}:{}:{}", name, count, step))
+    }));
+    mruby.def_class_method_for::<Cont, _>("scale", mrfn!(|mruby, _slf: Value,
+                                                          factor: f64 = (-1.5)| {
+        mruby.float(factor * 2.0)
+    }));
+
+    let result = mruby.run("[Container.take(3), Container.take(3, 2), Container.take(3, 2, 'y')]")
+        .unwrap();
+
+    assert_eq!(result.to_s(), "[\"x:3:1\", \"x:3:2\", \"y:3:2\"]");
+
+    assert_eq!(mruby.run("Container.scale").unwrap().to_f64().unwrap(), -3.0);
+    assert_eq!(mruby.run("Container.scale(2.0)").unwrap().to_f64().unwrap(), 4.0);
+
+    assert!(mruby.run("Container.take").is_err());
+    assert!(mruby.run("Container.take(1, 2, 'y', 4)").is_err());
+}
+
+#[test]
+fn flag_set_arguments() {
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Perms {
+        bits: u32
+    }
+
+    impl Perms {
+        const READ: Perms = Perms { bits: 1 };
+        const WRITE: Perms = Perms { bits: 2 };
+        const EXEC: Perms = Perms { bits: 4 };
+
+        const fn bits(&self) -> u32 {
+            self.bits
+        }
+
+        fn from_bits_truncate(bits: u32) -> Perms {
+            Perms { bits: bits & 7 }
+        }
+    }
+
+    flag_set!(Perms {
+        READ  => "read",
+        WRITE => "write",
+        EXEC  => "exec",
+    });
+
+    let mruby = Mruby::new();
+
+    struct Cont;
+
+    mruby.def_class_for::<Cont>("Container");
+    mruby.def_class_method_for::<Cont, _>("flip", mrfn!(|_mruby, _slf: Value, perms: Perms|
+                                                        -> Result<Perms, MrubyError> {
+        Ok(Perms::from_bits_truncate(!perms.bits()))
+    }));
+
+    let result = mruby.run("[Container.flip([:read, 'exec']), Container.flip([]), \
+                            Container.flip([:read, :write, :exec])]").unwrap();
+
+    assert_eq!(result.to_s(), "[[:write], [:read, :write, :exec], []]");
+
+    assert_eq!(Perms::from_value(mruby.run("[:write, :write]").unwrap()).unwrap(), Perms::WRITE);
+
+    match Perms::from_value(mruby.run("[:read, 1]").unwrap()) {
+        Err(err) => {
+            assert_eq!(err.to_string(), "Cast error: expected one of :read, :write, :exec, found 1 at [1]")
+        },
+        _ => assert!(false)
+    }
+
+    match Perms::from_value(mruby.run(":read").unwrap()) {
+        Err(MrubyError::Cast(expected)) => assert_eq!(expected, "Array"),
+        _ => assert!(false)
+    }
+
+    match mruby.run("Container.flip([:sudo])") {
+        Err(MrubyError::Runtime { class, .. }) => assert_eq!(class, "ArgumentError"),
+        _ => assert!(false)
+    }
+}
+
+#[test]
+fn mrfn_splat_arguments() {
+    let mruby = Mruby::new();
+
+    struct Cont;
+
+    mruby.def_class_for::<Cont>("Container");
+    mruby.def_class_method_for::<Cont, _>("log", mrfn!(|mruby, _slf: Value, *args: Vec<Value>| {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_s()).collect();
+
+        mruby.string(&args.join(" "))
+    }));
+    mruby.def_class_method_for::<Cont, _>("format", mrfn!(|mruby, _slf: Value, level: (&str),
+                                                           width: i32 = 0,
+                                                           *args: (Vec<Value>)|
+                                                           -> Result<Value, MrubyError> {
+        Ok(mruby.string(&format!("{}/{}/{}", level, width, args.len())))
+    }));
+
+    assert_eq!(mruby.run("Container.log").unwrap().to_str().unwrap(), "");
+    assert_eq!(mruby.run("Container.log(1)").unwrap().to_str().unwrap(), "1");
+    assert_eq!(mruby.run("Container.log(1, 'a', :b, nil, 2.5)").unwrap().to_str().unwrap(),
+               "1 a b  2.5");
+
+    let result = mruby.run("[Container.format('info'), Container.format('info', 8), \
+                            Container.format('info', 8, 1, 2, 3)]").unwrap();
+
+    assert_eq!(result.to_s(), "[\"info/0/0\", \"info/8/0\", \"info/8/3\"]");
+
+    assert!(mruby.run("Container.format").is_err());
+}
+
+#[test]
+fn mrfn_block_argument() {
+    let mruby = Mruby::new();
+
+    struct Cont;
+
+    mruby.def_class_for::<Cont>("Container");
+    mruby.def_class_method_for::<Cont, _>("each", mrfn!(|mruby, _slf: Value, count: i32,
+                                                         &block: Value|
+                                                         -> Result<Value, MrubyError> {
+        for i in 0..count {
+            block.yield_block(vec![mruby.fixnum(i)])?;
+        }
+
+        Ok(mruby.fixnum(count))
+    }));
+    mruby.def_class_method_for::<Cont, _>("fetch", mrfn!(|_mruby, _slf: Value, array: Value|
+                                                          -> Result<Value, MrubyError> {
+        array.call("fetch", vec![array.call("size", vec![])?])
+    }));
+    mruby.def_class_method_for::<Cont, _>("given", mrfn!(|mruby, _slf: Value, step: i32 = 1,
+                                                          *args: Vec<Value>, &block: Value| {
+        mruby.string(&format!("{}/{}/{}", step, args.len(), block.is_nil()))
+    }));
+
+    let result = mruby.run("
+      sum = 0
+      Container.each(4) { |i| sum += i }
+      sum
+    ").unwrap();
+
+    assert_eq!(result.to_i32().unwrap(), 6);
+
+    let result = mruby.run("
+      begin
+        Container.each(1)
+      rescue LocalJumpError => e
+        e.class
+      end
+    ").unwrap();
+
+    assert_eq!(result.to_class().unwrap().to_str(), "LocalJumpError");
+
+    match mruby.run("Container.fetch([1])") {
+        Err(MrubyError::Runtime { class, .. }) => assert_eq!(class, "IndexError"),
+        _ => assert!(false)
+    }
+
+    assert_eq!(mruby.run("Container.each(0)").unwrap().to_i32().unwrap(), 0);
+
+    let result = mruby.run("[Container.given, Container.given(2, 3, 4) { }]").unwrap();
+
+    assert_eq!(result.to_s(), "[\"1/0/true\", \"2/2/false\"]");
+}
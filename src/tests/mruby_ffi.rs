@@ -720,6 +720,235 @@ fn fixnum() {
     }
 }
 
+#[test]
+fn fixnum_i64() {
+    unsafe {
+        let number = mrb_ext_cint_to_fixnum(1 << 40);
+        assert_eq!(number.to_i64().unwrap(), 1 << 40);
+    }
+}
+
+#[test]
+fn fixnum_i32_overflow() {
+    unsafe {
+        let big = mrb_ext_cint_to_fixnum(i32::MAX as MrInt + 1);
+        let small = mrb_ext_cint_to_fixnum(i32::MIN as MrInt - 1);
+
+        assert!(big.to_i32().is_err());
+        assert!(small.to_i32().is_err());
+        assert_eq!(mrb_ext_cint_to_fixnum(i32::MIN as MrInt).to_i32().unwrap(), i32::MIN);
+    }
+}
+
+#[test]
+fn fixnum_unsigned() {
+    unsafe {
+        let number = MrValue::fixnum_u32(u32::MAX).unwrap();
+        assert_eq!(number.to_u32().unwrap(), u32::MAX);
+        assert_eq!(number.to_u64().unwrap(), u32::MAX as u64);
+
+        let number = MrValue::fixnum_u64(1 << 40).unwrap();
+        assert_eq!(number.to_u64().unwrap(), 1 << 40);
+        assert!(number.to_u32().is_err());
+
+        assert!(MrValue::fixnum_u64(u64::MAX).is_err());
+    }
+}
+
+#[test]
+fn fixnum_unsigned_negative() {
+    unsafe {
+        let number = MrValue::fixnum(-1);
+
+        assert!(number.to_u32().is_err());
+        assert!(number.to_u64().is_err());
+
+        match number.to_usize() {
+            Err(MrubyError::Overflow(err)) => assert_eq!(err, "-1 does not fit in usize"),
+            _ => assert!(false)
+        }
+    }
+}
+
+#[test]
+fn fixnum_pointer_sized() {
+    use std::mem;
+
+    unsafe {
+        let int_bits = mrb_ext_int_sizeof() * 8;
+        let ptr_bits = mem::size_of::<usize>() * 8;
+
+        let number = MrValue::fixnum_usize(12).unwrap();
+        assert_eq!(number.to_usize().unwrap(), 12);
+        assert_eq!(number.to_isize().unwrap(), 12);
+        assert_eq!(MrValue::fixnum(-12).to_isize().unwrap(), -12);
+        assert!(MrValue::fixnum(-12).to_usize().is_err());
+
+        let max = MrValue::fixnum_i64(MrInt::MAX);
+
+        assert_eq!(max.to_isize().is_ok(), ptr_bits >= int_bits);
+        assert_eq!(max.to_usize().is_ok(), ptr_bits >= int_bits);
+        assert_eq!(MrValue::fixnum_usize(usize::MAX).is_ok(), ptr_bits < int_bits);
+
+        if ptr_bits >= int_bits {
+            match MrValue::fixnum_usize(usize::MAX) {
+                Err(MrubyError::Overflow(err)) => {
+                    assert_eq!(err, format!("{} does not fit in Fixnum", usize::MAX))
+                },
+                _ => assert!(false)
+            }
+        }
+    }
+}
+
+#[test]
+fn float_exact_i64() {
+    unsafe {
+        let mrb = mrb_open();
+
+        assert_eq!(MrValue::float(mrb, -4.0).to_i64_exact().unwrap(), -4);
+        assert_eq!(MrValue::fixnum(7).to_i64_exact().unwrap(), 7);
+
+        let expect_cast = |value: MrValue, expected: &str| {
+            match value.to_i64_exact() {
+                Err(MrubyError::Cast(err)) => assert_eq!(err, expected),
+                _ => assert!(false)
+            }
+        };
+
+        expect_cast(MrValue::float(mrb, 4.5), "integral Float, found 4.5");
+        expect_cast(MrValue::float(mrb, f64::NAN), "finite Float, found NaN");
+        expect_cast(MrValue::float(mrb, f64::INFINITY), "finite Float, found inf");
+        expect_cast(MrValue::nil(), "Fixnum or Float");
+
+        match MrValue::float(mrb, 1e20).to_i64_exact() {
+            Err(MrubyError::Overflow(_)) => (),
+            _ => assert!(false)
+        }
+
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn float_f32() {
+    unsafe {
+        let mrb = mrb_open();
+
+        let number = MrValue::float_f32(mrb, 0.1);
+        assert_eq!(number.to_f32().unwrap(), 0.1);
+        assert_eq!(number.to_f64().unwrap(), 0.1f32 as f64);
+
+        assert_eq!(MrValue::float(mrb, 0.1).to_f32().unwrap(), 0.1);
+        assert_eq!(MrValue::float(mrb, 1e300).to_f32().unwrap(), f32::INFINITY);
+        assert!(MrValue::nil().to_f32().is_err());
+
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn float_from_fixnum() {
+    unsafe {
+        let mrb = mrb_open();
+
+        assert_eq!(MrValue::fixnum(-3).to_f64().unwrap(), -3.0);
+        assert!(MrValue::fixnum(-3).to_f64_strict().is_err());
+        assert_eq!(MrValue::float(mrb, 0.5).to_f64_strict().unwrap(), 0.5);
+
+        assert!(MrValue::nil().to_f64().is_err());
+        assert!(MrValue::string(mrb, "1.0").to_f64().is_err());
+        assert!(MrValue::symbol(mrb, "one").to_f64().is_err());
+
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn string_owned() {
+    unsafe {
+        let mrb = mrb_open();
+        let context = mrbc_context_new(mrb);
+
+        let string = MrValue::string(mrb, "a\0b");
+        assert_eq!(string.to_string(mrb).unwrap(), "a\0b");
+        assert_eq!(MrValue::symbol(mrb, "sym").to_string(mrb).unwrap(), "sym");
+        assert_eq!(MrValue::string(mrb, "").to_string(mrb).unwrap(), "");
+        assert!(MrValue::fixnum(1).to_string(mrb).is_err());
+
+        let code = "\"\\xff\"";
+        let invalid = mrb_ext_load_nstring_cxt_nothrow(mrb, code.as_ptr(), code.len(), context);
+
+        assert!(invalid.to_string(mrb).is_err());
+        assert!(invalid.to_str(mrb).is_err());
+
+        mrbc_context_free(mrb, context);
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn string_bytes() {
+    unsafe {
+        let mrb = mrb_open();
+
+        let bytes = [0xff, 0x00, 0x89, b'P', b'N', b'G', 0x00];
+        let string = MrValue::bytes(mrb, &bytes);
+
+        assert_eq!(string.typ(), MrType::MRB_TT_STRING);
+        assert_eq!(string.to_bytes(mrb).unwrap(), bytes.to_vec());
+        assert!(string.to_string(mrb).is_err());
+
+        assert_eq!(MrValue::bytes(mrb, &[]).to_bytes(mrb).unwrap(), vec![]);
+
+        #[allow(deprecated)]
+        let alias = MrValue::string_bytes(mrb, &bytes);
+
+        assert_eq!(alias.to_bytes(mrb).unwrap(), bytes.to_vec());
+        assert!(MrValue::symbol(mrb, "sym").to_bytes(mrb).is_err());
+
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn bytes_all_values() {
+    unsafe {
+        let mrb = mrb_open();
+
+        let bytes: Vec<u8> = (0..=255).collect();
+        let string = MrValue::bytes(mrb, &bytes);
+
+        assert_eq!(string.to_bytes(mrb).unwrap(), &bytes[..]);
+
+        let mut len: MrInt = 0;
+
+        mrb_ext_str_ptr_len(string, &mut len);
+
+        assert_eq!(len, 256);
+
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn string_lossy() {
+    unsafe {
+        let mrb = mrb_open();
+
+        let string = MrValue::bytes(mrb, b"caf\xc3\xa9 \xff\x00!");
+
+        assert!(string.to_str(mrb).is_err());
+        assert_eq!(string.to_string_lossy(mrb).unwrap(), "caf\u{e9} \u{fffd}\u{0}!");
+
+        assert_eq!(MrValue::string(mrb, "a\0b").to_str(mrb).unwrap(), "a\0b");
+        assert_eq!(MrValue::symbol(mrb, "sym").to_string_lossy(mrb).unwrap(), "sym");
+        assert!(MrValue::fixnum(1).to_string_lossy(mrb).is_err());
+
+        mrb_close(mrb);
+    }
+}
+
 #[test]
 fn float() {
     unsafe {
@@ -783,6 +1012,44 @@ fn obj() {
     }
 }
 
+#[test]
+fn obj_arc() {
+    use std::thread;
+
+    unsafe {
+        struct Cont {
+            value: i32
+        }
+
+        let mrb = mrb_open();
+
+        let obj_str = CString::new("Object").unwrap();
+        let obj_class = mrb_class_get(mrb, obj_str.as_ptr());
+        let cont_str = CString::new("Cont").unwrap();
+        let cont_class = mrb_define_class(mrb, cont_str.as_ptr(), obj_class);
+
+        mrb_ext_set_instance_tt(cont_class, MrType::MRB_TT_DATA);
+
+        let data_type = mrb_ext_data_type(cont_str.as_ptr(), free_arc::<Cont>);
+
+        let obj = MrValue::obj_arc(mrb, cont_class, Cont { value: 3 }, &data_type);
+        let arc = obj.to_obj_arc::<Cont>(mrb, &data_type).unwrap();
+
+        thread::spawn(move || {
+            arc.lock().unwrap().value = 5;
+        }).join().unwrap();
+
+        let arc = obj.to_obj_arc::<Cont>(mrb, &data_type).unwrap();
+
+        assert_eq!(arc.lock().unwrap().value, 5);
+        assert!(MrValue::fixnum(1).to_obj_arc::<Cont>(mrb, &data_type).is_err());
+
+        mrb_close(mrb);
+
+        assert_eq!(Arc::strong_count(&arc), 1);
+    }
+}
+
 #[test]
 fn obj_init() {
     use std::cell::RefCell;
@@ -858,6 +1125,58 @@ fn obj_init() {
     }
 }
 
+#[test]
+fn obj_gc_drop() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    unsafe {
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+        static mut DATA_TYPE: Option<MrDataType> = None;
+
+        struct Cont;
+
+        impl Drop for Cont {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let mrb = mrb_open();
+        let context = mrbc_context_new(mrb);
+
+        let obj_str = CString::new("Object").unwrap();
+        let obj_class = mrb_class_get(mrb, obj_str.as_ptr());
+        let cont_str = CString::new("Cont").unwrap();
+        let cont_class = mrb_define_class(mrb, cont_str.as_ptr(), obj_class);
+
+        mrb_ext_set_instance_tt(cont_class, MrType::MRB_TT_DATA);
+
+        DATA_TYPE = Some(mrb_ext_data_type(cont_str.as_ptr(), free_rc::<Cont>));
+
+        extern "C" fn make(mrb: *const MrState, slf: MrValue) -> MrValue {
+            unsafe {
+                let class = mrb_ext_class_ptr(slf);
+
+                MrValue::obj(mrb, class, Cont, (*ptr::addr_of!(DATA_TYPE)).as_ref().unwrap())
+            }
+        }
+
+        let make_str = CString::new("make").unwrap();
+
+        mrb_define_class_method(mrb, cont_class, make_str.as_ptr(), make, 0);
+
+        let code = "3.times { Cont.make }; nil";
+        mrb_ext_load_nstring_cxt_nothrow(mrb, code.as_ptr(), code.len(), context);
+
+        mrb_full_gc(mrb);
+
+        assert_eq!(DROPS.load(Ordering::SeqCst), 3);
+
+        mrbc_context_free(mrb, context);
+        mrb_close(mrb);
+    }
+}
+
 #[test]
 fn obj_scoping() {
     use std::cell::RefCell;
@@ -950,3 +1269,1152 @@ fn ptr() {
     }
 }
 
+#[test]
+fn hash() {
+    unsafe {
+        let mrb = mrb_open();
+
+        let vec = vec![
+            (MrValue::fixnum(1), MrValue::fixnum(2)),
+            (MrValue::fixnum(3), MrValue::fixnum(4))
+        ];
+
+        let hash = MrValue::hash(mrb, vec.clone());
+
+        assert_eq!(hash.to_hash(mrb).unwrap(), vec);
+
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn hash_from_script() {
+    unsafe {
+        let mrb = mrb_open();
+        let context = mrbc_context_new(mrb);
+
+        let code = "{ one: 1, 'two' => 2.0 }";
+        let hash = mrb_ext_load_nstring_cxt_nothrow(mrb, code.as_ptr(), code.len(), context);
+        let pairs = hash.to_hash(mrb).unwrap();
+
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(pairs[0].0.typ(), MrType::MRB_TT_SYMBOL);
+        assert_eq!(pairs[0].0.to_str(mrb).unwrap(), "one");
+        assert_eq!(pairs[0].1.to_i32().unwrap(), 1);
+        assert_eq!(pairs[1].0.to_str(mrb).unwrap(), "two");
+        assert_eq!(pairs[1].1.to_f64().unwrap(), 2.0);
+
+        mrbc_context_free(mrb, context);
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn hash_empty() {
+    unsafe {
+        let mrb = mrb_open();
+
+        let hash = MrValue::hash(mrb, vec![]);
+
+        assert_eq!(hash.typ(), MrType::MRB_TT_HASH);
+        assert!(hash.to_hash(mrb).unwrap().is_empty());
+
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn hash_cast() {
+    unsafe {
+        let mrb = mrb_open();
+
+        let array = MrValue::array(mrb, vec![]);
+
+        match array.to_hash(mrb) {
+            Err(MrubyError::Cast(expected)) => assert_eq!(expected, "Hash"),
+            _ => assert!(false)
+        }
+
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn range_from_script() {
+    unsafe {
+        let mrb = mrb_open();
+        let context = mrbc_context_new(mrb);
+
+        let code = "1...10";
+        let range = mrb_ext_load_nstring_cxt_nothrow(mrb, code.as_ptr(), code.len(), context);
+        let (start, end, exclusive) = range.to_range(mrb).unwrap();
+
+        assert_eq!(start.to_i32().unwrap(), 1);
+        assert_eq!(end.to_i32().unwrap(), 10);
+        assert!(exclusive);
+
+        assert!(MrValue::fixnum(1).to_range(mrb).is_err());
+
+        mrbc_context_free(mrb, context);
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn range() {
+    unsafe {
+        let mrb = mrb_open();
+
+        let exclusive = MrValue::range(mrb, MrValue::fixnum(1), MrValue::fixnum(10), true);
+        let inclusive = MrValue::range(mrb, MrValue::fixnum(-5), MrValue::fixnum(5), false);
+
+        assert_eq!(exclusive.typ(), MrType::MRB_TT_RANGE);
+
+        let (start, end, exclusive) = exclusive.to_range(mrb).unwrap();
+
+        assert_eq!(start.to_i32().unwrap(), 1);
+        assert_eq!(end.to_i32().unwrap(), 10);
+        assert!(exclusive);
+
+        let (start, end, exclusive) = inclusive.to_range(mrb).unwrap();
+
+        assert_eq!(start.to_i32().unwrap(), -5);
+        assert_eq!(end.to_i32().unwrap(), 5);
+        assert!(!exclusive);
+
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn range_inclusive_from_script() {
+    unsafe {
+        let mrb = mrb_open();
+        let context = mrbc_context_new(mrb);
+
+        let code = "(1 + 1)..(2 * 5)";
+        let range = mrb_ext_load_nstring_cxt_nothrow(mrb, code.as_ptr(), code.len(), context);
+        let (start, end, exclusive) = range.to_range(mrb).unwrap();
+
+        assert_eq!(start.to_i32().unwrap(), 2);
+        assert_eq!(end.to_i32().unwrap(), 10);
+        assert!(!exclusive);
+
+        mrbc_context_free(mrb, context);
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn into_mrvalue() {
+    unsafe {
+        let mrb = mrb_open();
+
+        assert_eq!(true.into_mrvalue(mrb).to_bool().unwrap(), true);
+        assert_eq!((-3).into_mrvalue(mrb).to_i32().unwrap(), -3);
+        assert_eq!((1i64 << 40).into_mrvalue(mrb).to_i64().unwrap(), 1 << 40);
+        assert_eq!(u32::MAX.into_mrvalue(mrb).to_u32().unwrap(), u32::MAX);
+        assert_eq!(1.5.into_mrvalue(mrb).to_f64().unwrap(), 1.5);
+        assert_eq!("hi".into_mrvalue(mrb).to_str(mrb).unwrap(), "hi");
+        assert_eq!("hi".to_owned().into_mrvalue(mrb).to_str(mrb).unwrap(), "hi");
+        assert!(None::<i32>.into_mrvalue(mrb).is_nil());
+
+        let array = vec![Some(1), None].into_mrvalue(mrb).to_vec(mrb).unwrap();
+
+        assert_eq!(array[0].to_i32().unwrap(), 1);
+        assert!(array[1].is_nil());
+
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn from_mrvalue() {
+    unsafe {
+        let mrb = mrb_open();
+        let context = mrbc_context_new(mrb);
+
+        let code = "[[1, nil, 3], 'hi', false]";
+        let value = mrb_ext_load_nstring_cxt_nothrow(mrb, code.as_ptr(), code.len(), context);
+        let values = Vec::<MrValue>::from_mrvalue(mrb, value).unwrap();
+
+        assert_eq!(Vec::<Option<i32>>::from_mrvalue(mrb, values[0]).unwrap(),
+                   vec![Some(1), None, Some(3)]);
+        assert_eq!(String::from_mrvalue(mrb, values[1]).unwrap(), "hi");
+        assert_eq!(Option::<bool>::from_mrvalue(mrb, values[2]).unwrap(), Some(false));
+
+        assert!(Vec::<i32>::from_mrvalue(mrb, values[0]).is_err());
+        assert!(i64::from_mrvalue(mrb, values[1]).is_err());
+
+        mrbc_context_free(mrb, context);
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn fiber_resume() {
+    unsafe {
+        let mrb = mrb_open();
+        let context = mrbc_context_new(mrb);
+
+        let code = "proc { |x| x = Fiber.yield(x + 1); x * 2 }";
+        let block = mrb_ext_load_nstring_cxt_nothrow(mrb, code.as_ptr(), code.len(), context);
+        let fiber = MrValue::fiber(mrb, block).unwrap();
+
+        assert_eq!(MrValue::fiber_resume(mrb, fiber, &[MrValue::fixnum(1)]).unwrap()
+                       .to_i32().unwrap(), 2);
+        assert_eq!(MrValue::fiber_resume(mrb, fiber, &[MrValue::fixnum(5)]).unwrap()
+                       .to_i32().unwrap(), 10);
+
+        match MrValue::fiber_resume(mrb, fiber, &[]) {
+            Err(MrubyError::Exception { class, .. }) => assert_eq!(class, "StopIteration"),
+            _ => panic!("expected StopIteration")
+        }
+
+        assert!(MrValue::fiber_resume(mrb, MrValue::fixnum(1), &[]).is_err());
+
+        extern "C" fn nothing(_mrb: *const MrState, _slf: MrValue) -> MrValue {
+            unsafe { MrValue::nil() }
+        }
+
+        match MrValue::fiber(mrb, MrValue::proc_from_fn(mrb, nothing)) {
+            Err(MrubyError::Exception { class, .. }) => assert_eq!(class, "FiberError"),
+            _ => panic!("expected FiberError")
+        }
+
+        let code = "proc { raise 'inside' }";
+        let block = mrb_ext_load_nstring_cxt_nothrow(mrb, code.as_ptr(), code.len(), context);
+        let fiber = MrValue::fiber(mrb, block).unwrap();
+
+        match MrValue::fiber_resume(mrb, fiber, &[]) {
+            Err(MrubyError::Exception { class, message, .. }) => {
+                assert_eq!(class, "RuntimeError");
+                assert_eq!(message, "inside");
+            },
+            _ => panic!("expected RuntimeError")
+        }
+
+        mrbc_context_free(mrb, context);
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn fiber_yield() {
+    unsafe {
+        let mrb = mrb_open();
+        let context = mrbc_context_new(mrb);
+
+        extern "C" fn pass(mrb: *const MrState, _slf: MrValue) -> MrValue {
+            unsafe {
+                let argv = mrb_get_argv(mrb);
+
+                MrValue::fiber_yield(mrb, &[*argv, *argv])
+            }
+        }
+
+        let obj_str = CString::new("Object").unwrap();
+        let obj_class = mrb_class_get(mrb, obj_str.as_ptr());
+        let pass_str = CString::new("pass").unwrap();
+
+        mrb_define_method(mrb, obj_class, pass_str.as_ptr(), pass, 1 << 12);
+
+        let code = "proc { pass(3); :done }";
+        let block = mrb_ext_load_nstring_cxt_nothrow(mrb, code.as_ptr(), code.len(), context);
+        let fiber = MrValue::fiber(mrb, block).unwrap();
+
+        let pair = MrValue::fiber_resume(mrb, fiber, &[]).unwrap().to_vec(mrb).unwrap();
+
+        assert_eq!(pair.len(), 2);
+        assert_eq!(pair[0].to_i32().unwrap(), 3);
+        assert_eq!(MrValue::fiber_resume(mrb, fiber, &[]).unwrap().to_str(mrb).unwrap(), "done");
+
+        mrbc_context_free(mrb, context);
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn call_proc() {
+    unsafe {
+        let mrb = mrb_open();
+        let context = mrbc_context_new(mrb);
+
+        let code = "proc { |a, b| a + b }";
+        let add = mrb_ext_load_nstring_cxt_nothrow(mrb, code.as_ptr(), code.len(), context);
+
+        let result = MrValue::call_proc(mrb, add, &[MrValue::fixnum(1), MrValue::fixnum(2)]);
+
+        assert_eq!(result.unwrap().to_i32().unwrap(), 3);
+
+        match MrValue::call_proc(mrb, add, &[MrValue::fixnum(1), MrValue::nil()]) {
+            Err(MrubyError::Exception { class, .. }) => assert_eq!(class, "TypeError"),
+            _ => panic!("expected TypeError")
+        }
+
+        assert!(MrValue::call_proc(mrb, MrValue::fixnum(1), &[]).is_err());
+
+        mrbc_context_free(mrb, context);
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn proc_from_fn() {
+    unsafe {
+        let mrb = mrb_open();
+
+        extern "C" fn argc(mrb: *const MrState, _slf: MrValue) -> MrValue {
+            unsafe {
+                MrValue::fixnum(mrb_get_argc(mrb) as i32)
+            }
+        }
+
+        let block = MrValue::proc_from_fn(mrb, argc);
+
+        assert_eq!(block.typ(), MrType::MRB_TT_PROC);
+
+        let result = MrValue::call_proc(mrb, block, &[MrValue::nil(), MrValue::nil()]);
+
+        assert_eq!(result.unwrap().to_i32().unwrap(), 2);
+
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn gc_arena_save_restore() {
+    unsafe {
+        let mrb = mrb_open();
+
+        let idx = mrb_ext_gc_arena_save(mrb);
+
+        for _ in 0..100_000 {
+            let ai = mrb_ext_gc_arena_save(mrb);
+
+            MrValue::string(mrb, "temporary");
+
+            assert_eq!(mrb_ext_gc_arena_save(mrb), ai + 1);
+
+            mrb_ext_gc_arena_restore(mrb, ai);
+        }
+
+        assert_eq!(mrb_ext_gc_arena_save(mrb), idx);
+
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn ivar_get_set() {
+    unsafe {
+        let mrb = mrb_open();
+        let context = mrbc_context_new(mrb);
+
+        let code = "o = Object.new; o.instance_variable_set(:@a, 1); o";
+
+        let obj = mrb_ext_load_nstring_cxt_nothrow(mrb, code.as_ptr(), code.len(), context);
+
+        assert!(obj.ivar_defined(mrb, "@a"));
+        assert!(!obj.ivar_defined(mrb, "@b"));
+        assert_eq!(obj.ivar_get(mrb, "@a").to_i32().unwrap(), 1);
+        assert!(obj.ivar_get(mrb, "@b").is_nil());
+
+        obj.ivar_set(mrb, "@b", MrValue::fixnum(2));
+
+        assert!(obj.ivar_defined(mrb, "@b"));
+        assert_eq!(obj.ivar_get(mrb, "@b").to_i32().unwrap(), 2);
+
+        mrbc_context_free(mrb, context);
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn cvar_get_set() {
+    unsafe {
+        let mrb = mrb_open();
+        let context = mrbc_context_new(mrb);
+
+        let code = "class Counter; @@count = 1; def self.count; @@count; end; end; Counter";
+
+        let class = mrb_ext_load_nstring_cxt_nothrow(mrb, code.as_ptr(), code.len(), context)
+            .to_class().unwrap();
+
+        assert!(MrClass::cvar_defined(mrb, class, "@@count"));
+        assert_eq!(MrClass::cvar_get(mrb, class, "@@count").to_i32().unwrap(), 1);
+        assert!(MrClass::cvar_get(mrb, class, "@@total").is_nil());
+
+        MrClass::cvar_set(mrb, class, "@@count", MrValue::fixnum(5));
+
+        let code = "Counter.count";
+
+        assert_eq!(mrb_ext_load_nstring_cxt_nothrow(mrb, code.as_ptr(), code.len(), context).to_i32().unwrap(), 5);
+
+        mrbc_context_free(mrb, context);
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn const_get_defined() {
+    unsafe {
+        let mrb = mrb_open();
+        let context = mrbc_context_new(mrb);
+
+        let code = "class MyClass; VERSION = '1.2.0'; end; MyClass";
+
+        let class = mrb_ext_load_nstring_cxt_nothrow(mrb, code.as_ptr(), code.len(), context)
+            .to_class().unwrap();
+
+        assert!(MrClass::const_defined(mrb, class, "VERSION"));
+        assert!(!MrClass::const_defined(mrb, class, "RELEASE"));
+        assert_eq!(MrClass::const_get(mrb, class, "VERSION").to_str(mrb).unwrap(), "1.2.0");
+        assert!(MrClass::const_get(mrb, class, "RELEASE").is_nil());
+
+        mrbc_context_free(mrb, context);
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn define_const_typed() {
+    unsafe {
+        let mrb = mrb_open();
+        let context = mrbc_context_new(mrb);
+
+        let code = "class MyClass; end; MyClass";
+
+        let class = mrb_ext_load_nstring_cxt_nothrow(mrb, code.as_ptr(), code.len(), context)
+            .to_class().unwrap();
+
+        MrClass::define_const_i32(mrb, class, "COUNT", 3);
+        MrClass::define_const_str(mrb, class, "NAME", "mrusty");
+        MrClass::define_const_bool(mrb, class, "DEBUG", true);
+        MrClass::define_const_f64(mrb, class, "RATIO", 0.5);
+
+        let code = "[MyClass::COUNT, MyClass::NAME, MyClass::DEBUG, MyClass::RATIO].inspect";
+
+        let result = mrb_ext_load_nstring_cxt_nothrow(mrb, code.as_ptr(), code.len(), context);
+
+        assert_eq!(result.to_str(mrb).unwrap(), "[3, \"mrusty\", true, 0.5]");
+
+        mrbc_context_free(mrb, context);
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn mruby_vm_builder() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    extern "C" {
+        fn realloc(ptr: *mut u8, size: usize) -> *mut u8;
+        fn free(ptr: *mut u8);
+    }
+
+    extern "C" fn counting_allocf(_mrb: *const MrState, ptr: *mut u8, size: usize,
+                                  ud: *const u8) -> *mut u8 {
+        unsafe {
+            let count = &*(ud as *const AtomicUsize);
+
+            if size == 0 {
+                free(ptr);
+
+                ptr::null_mut()
+            } else {
+                count.fetch_add(1, Ordering::SeqCst);
+
+                realloc(ptr, size)
+            }
+        }
+    }
+
+    let count = AtomicUsize::new(0);
+    let marker = 42u8;
+
+    {
+        let vm = MrubyVmBuilder::new()
+            .allocf(counting_allocf, &count as *const AtomicUsize as *const u8)
+            .gc_interval_ratio(100)
+            .gc_step_ratio(400)
+            .ud(&marker as *const u8)
+            .build()
+            .unwrap();
+
+        assert!(count.load(Ordering::SeqCst) > 0);
+
+        unsafe {
+            assert_eq!(*mrb_ext_get_ud(vm.as_ptr()), 42);
+            assert_eq!(vm.string("hi").to_str(vm.as_ptr()).unwrap(), "hi");
+        }
+
+        unsafe {
+            let array = vm.array(vec![MrValue::fixnum(1), vm.float(2.5)]);
+
+            assert_eq!(array.to_vec(vm.as_ptr()).unwrap().len(), 2);
+        }
+
+        vm.full_gc();
+    }
+}
+
+#[test]
+fn mruby_vm_wrappers() {
+    let vm = MrubyVm::new();
+
+    unsafe {
+        let array = vm.array(vec![]);
+
+        vm.array_push(array, MrValue::fixnum(1)).unwrap();
+        vm.array_unshift(array, vm.string("a")).unwrap();
+
+        assert_eq!(vm.array_pop(array).unwrap().to_i32().unwrap(), 1);
+        assert_eq!(vm.funcall(array, "size", &[]).unwrap().to_i32().unwrap(), 1);
+
+        let hash = vm.hash(vec![]);
+
+        vm.hash_set(hash, vm.symbol("a"), vm.float_f32(0.5)).unwrap();
+
+        assert_eq!(vm.hash_get(hash, vm.symbol("a")).unwrap().to_f64().unwrap(), 0.5);
+        assert_eq!(vm.hash_keys(hash).unwrap().to_vec(vm.as_ptr()).unwrap().len(), 1);
+        assert!(vm.hash_get(array, vm.symbol("a")).is_err());
+    }
+}
+
+#[test]
+fn mruby_context_eval() {
+    let vm = MrubyVm::new();
+
+    unsafe {
+        let context = MrubyContext::new(vm.as_ptr(), Some("config.rb"));
+
+        assert_eq!(context.eval("a = 1 + 2").unwrap().to_i32().unwrap(), 3);
+
+        match context.eval("def load\n  raise ArgumentError, 'bad'\nend\nload") {
+            Err(MrubyError::Exception { class, message, backtrace }) => {
+                assert_eq!(class, "ArgumentError");
+                assert_eq!(message, "bad");
+                assert_eq!(backtrace.unwrap()[0], "config.rb:2:in load");
+            },
+            _ => assert!(false)
+        }
+
+        assert!(context.eval("1 +").is_err());
+        assert_eq!(context.eval("2 * 2").unwrap().to_i32().unwrap(), 4);
+    }
+}
+
+#[test]
+fn mruby_context_load_irep() {
+    use std::fs::File;
+    use std::io::Read;
+
+    let mut code = Vec::new();
+
+    File::open("tests/compiled.mrb").unwrap().read_to_end(&mut code).unwrap();
+
+    let vm = MrubyVm::new();
+
+    unsafe {
+        let context = MrubyContext::new(vm.as_ptr(), None);
+
+        match context.load_irep(&code) {
+            Err(MrubyError::Exception { class, .. }) => assert_eq!(class, "NameError"),
+            _ => assert!(false)
+        }
+
+        context.eval("class Scalar; def initialize(value); @value = value; end; end").unwrap();
+
+        let scalar = context.load_irep(&code).unwrap();
+
+        assert_eq!(scalar.ivar_get(vm.as_ptr(), "@value").to_f64().unwrap(), 2.0);
+
+        assert!(context.load_irep(&code[..10]).is_err());
+        assert!(context.load_irep(&code[..code.len() - 1]).is_err());
+    }
+}
+
+#[test]
+fn mruby_vm_eval_file() {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::path::Path;
+    use std::process;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().subsec_nanos();
+    let path = env::temp_dir().join(format!("mrusty_eval_file_{}_{}.rb", process::id(), nanos));
+
+    File::create(&path).unwrap().write_all(b"def fail_here\n  raise 'oops'\nend\n40 + 2\n").unwrap();
+
+    let vm = MrubyVm::new();
+    let result = vm.eval_file(&path);
+
+    fs::remove_file(&path).unwrap();
+
+    unsafe {
+        assert_eq!(result.unwrap().to_i32().unwrap(), 42);
+    }
+
+    let context = unsafe { MrubyContext::new(vm.as_ptr(), None) };
+
+    match context.eval("fail_here") {
+        Err(MrubyError::Exception { backtrace, .. }) => {
+            let line = format!("{}:2:in fail_here", path.to_string_lossy());
+
+            assert_eq!(backtrace.unwrap()[0], line);
+        },
+        _ => assert!(false)
+    }
+
+    assert!(vm.eval_file(Path::new("tests/compiled.mrb")).is_err());
+
+    match vm.eval_file(Path::new("tests/api.rs")) {
+        Err(MrubyError::Filetype) => (),
+        _ => assert!(false)
+    }
+}
+
+#[test]
+fn funcall() {
+    unsafe {
+        let mrb = mrb_open();
+
+        let array = MrValue::array(mrb, vec![MrValue::fixnum(3), MrValue::fixnum(1)]);
+
+        let sorted = array.funcall(mrb, "sort", &[]).unwrap();
+
+        assert_eq!(sorted.to_vec(mrb).unwrap()[0].to_i32().unwrap(), 1);
+
+        let sum = MrValue::fixnum(2).funcall(mrb, "+", &[MrValue::fixnum(3)]).unwrap();
+
+        assert_eq!(sum.to_i32().unwrap(), 5);
+
+        match MrValue::fixnum(2).funcall(mrb, "+", &[MrValue::string(mrb, "a")]) {
+            Err(MrubyError::Exception { class, .. }) => assert_eq!(class, "TypeError"),
+            _ => assert!(false)
+        }
+
+        match MrValue::nil().funcall(mrb, "undefined_method", &[]) {
+            Err(MrubyError::Exception { class, .. }) => assert_eq!(class, "NoMethodError"),
+            _ => assert!(false)
+        }
+
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn respond_to() {
+    unsafe {
+        let mrb = mrb_open();
+
+        let array = MrValue::array(mrb, vec![]);
+
+        assert!(array.respond_to(mrb, "push"));
+        assert!(array.respond_to(mrb, "each"));
+        assert!(!array.respond_to(mrb, "undefined_method"));
+        assert!(MrValue::fixnum(1).respond_to(mrb, "+"));
+        assert!(!MrValue::nil().respond_to(mrb, "push"));
+
+        let context = mrbc_context_new(mrb);
+        let code = "
+          class Quacker
+            def respond_to?(method)
+              raise 'nope' if method == :broken
+              method == :quack
+            end
+          end
+
+          Quacker.new
+        ";
+
+        let quacker = mrb_ext_load_nstring_cxt_nothrow(mrb, code.as_ptr(), code.len(), context);
+
+        assert!(quacker.respond_to(mrb, "quack"));
+        assert!(!quacker.respond_to(mrb, "walk"));
+        assert!(!quacker.respond_to(mrb, "broken"));
+
+        mrbc_context_free(mrb, context);
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn debug_format() {
+    unsafe {
+        let mrb = mrb_open();
+
+        assert_eq!(format!("{:?}", MrValue::nil()), "nil");
+        assert_eq!(format!("{:?}", MrValue::bool(true)), "true");
+        assert_eq!(format!("{:?}", MrValue::bool(false)), "false");
+        assert_eq!(format!("{:?}", MrValue::fixnum(42)), "Fixnum(42)");
+        assert_eq!(format!("{:?}", MrValue::float(mrb, 3.5)), "Float(3.5)");
+        assert!(format!("{:?}", MrValue::string(mrb, "hi")).starts_with("String(0x"));
+
+        let symbol = MrValue::symbol(mrb, "foo");
+        let string = MrValue::string(mrb, "a \"quoted\" string");
+        let array = MrValue::array(mrb, vec![]);
+
+        assert_eq!(format!("{:?}", symbol.debug_with(mrb)), "Symbol(:foo)");
+        assert_eq!(format!("{:?}", string.debug_with(mrb)), "String(\"a \\\"quoted\\\" string\")");
+        assert_eq!(format!("{:?}", array.debug_with(mrb)), "Array(Array)");
+        assert_eq!(format!("{:?}", MrValue::fixnum(1).debug_with(mrb)), "Fixnum(1)");
+
+        let context = mrbc_context_new(mrb);
+        let code = "class Point; end; Point.new";
+
+        let point = mrb_ext_load_nstring_cxt_nothrow(mrb, code.as_ptr(), code.len(), context);
+        let point_class = mrb_ext_class_value(mrb_ext_class(mrb, point));
+
+        assert_eq!(format!("{:?}", point.debug_with(mrb)), "Object(Point)");
+        assert_eq!(format!("{:?}", point_class.debug_with(mrb)), "Class(Point)");
+
+        mrbc_context_free(mrb, context);
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn equality_is_representation() {
+    unsafe {
+        let mrb = mrb_open();
+
+        let a = MrValue::string(mrb, "same");
+        let b = MrValue::string(mrb, "same");
+
+        assert!(a == a);
+        assert!(a != b);
+        assert!(MrValue::fixnum(1) == MrValue::fixnum(1));
+        assert!(MrValue::symbol(mrb, "sym") == MrValue::symbol(mrb, "sym"));
+
+        assert!(a.funcall(mrb, "==", &[b]).unwrap().to_bool().unwrap());
+        assert_eq!(a.funcall(mrb, "hash", &[]).unwrap().to_i64().unwrap(),
+                   b.funcall(mrb, "hash", &[]).unwrap().to_i64().unwrap());
+
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn type_predicates() {
+    unsafe {
+        let mrb = mrb_open();
+
+        assert!(MrValue::nil().is_nil());
+        assert!(!MrValue::nil().is_bool());
+        assert!(!MrValue::nil().is_false());
+        assert!(MrValue::bool(true).is_bool());
+        assert!(MrValue::bool(true).is_true());
+        assert!(MrValue::bool(false).is_bool());
+        assert!(MrValue::bool(false).is_false());
+        assert!(!MrValue::bool(false).is_true());
+
+        assert!(MrValue::fixnum(1).is_integer());
+        assert!(!MrValue::fixnum(1).is_float());
+        assert!(MrValue::float(mrb, 1.0).is_float());
+        assert!(MrValue::string(mrb, "a").is_string());
+        assert!(!MrValue::string(mrb, "a").is_symbol());
+        assert!(MrValue::symbol(mrb, "a").is_symbol());
+        assert!(MrValue::array(mrb, vec![]).is_array());
+        assert!(MrValue::hash(mrb, vec![]).is_hash());
+
+        let context = mrbc_context_new(mrb);
+        let code = "[Object, Kernel, Proc.new {}, Fiber.new {}]";
+
+        let values = mrb_ext_load_nstring_cxt_nothrow(mrb, code.as_ptr(), code.len(), context)
+            .to_vec(mrb).unwrap();
+
+        assert!(values[0].is_class());
+        assert!(!values[0].is_module());
+        assert!(values[1].is_module());
+        assert!(values[2].is_proc());
+        assert!(values[3].is_fiber());
+        assert!(!values[3].is_data());
+
+        mrbc_context_free(mrb, context);
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn json() {
+    unsafe {
+        let mrb = mrb_open();
+
+        match MrValue::fixnum(1).to_json(mrb) {
+            Err(MrubyError::Runtime { message, .. }) => assert!(message.contains("mruby-json")),
+            _ => assert!(false)
+        }
+
+        assert!(MrValue::from_json(mrb, "1").is_err());
+
+        let context = mrbc_context_new(mrb);
+        let code = "
+          module JSON
+            def self.generate(value)
+              raise TypeError, 'no floats' if value.is_a?(Float)
+              value.inspect
+            end
+
+            def self.parse(json)
+              raise ArgumentError, 'empty' if json.empty?
+              json.to_i
+            end
+          end
+        ";
+
+        mrb_ext_load_nstring_cxt_nothrow(mrb, code.as_ptr(), code.len(), context);
+
+        let array = MrValue::array(mrb, vec![MrValue::fixnum(1), MrValue::string(mrb, "a")]);
+
+        assert_eq!(array.to_json(mrb).unwrap(), "[1, \"a\"]");
+        assert_eq!(MrValue::from_json(mrb, "42").unwrap().to_i32().unwrap(), 42);
+
+        match MrValue::float(mrb, 1.5).to_json(mrb) {
+            Err(MrubyError::Exception { class, .. }) => assert_eq!(class, "TypeError"),
+            _ => assert!(false)
+        }
+
+        match MrValue::from_json(mrb, "") {
+            Err(MrubyError::Exception { class, .. }) => assert_eq!(class, "ArgumentError"),
+            _ => assert!(false)
+        }
+
+        mrbc_context_free(mrb, context);
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn array_mutation() {
+    unsafe {
+        let mrb = mrb_open();
+
+        let array = MrValue::array(mrb, vec![MrValue::fixnum(2)]);
+
+        MrValue::array_push(mrb, array, MrValue::fixnum(3)).unwrap();
+        MrValue::array_unshift(mrb, array, MrValue::fixnum(1)).unwrap();
+
+        let other = MrValue::array(mrb, vec![MrValue::fixnum(4), MrValue::fixnum(5)]);
+        let result = MrValue::array_concat(mrb, array, other).unwrap();
+
+        assert_eq!(result, array);
+        assert_eq!(MrValue::array_pop(mrb, array).unwrap().to_i32().unwrap(), 5);
+        assert_eq!(MrValue::array_shift(mrb, array).unwrap().to_i32().unwrap(), 1);
+
+        let vec: Vec<i32> = array.to_vec(mrb).unwrap().iter().map(|value| {
+            value.to_i32().unwrap()
+        }).collect();
+
+        assert_eq!(vec, vec![2, 3, 4]);
+
+        let empty = MrValue::array(mrb, vec![]);
+
+        assert!(MrValue::array_pop(mrb, empty).unwrap().is_nil());
+        assert!(MrValue::array_shift(mrb, empty).unwrap().is_nil());
+
+        match MrValue::array_push(mrb, MrValue::fixnum(1), MrValue::nil()) {
+            Err(MrubyError::Cast(expected)) => assert_eq!(expected, "Array"),
+            _ => assert!(false)
+        }
+
+        match MrValue::array_concat(mrb, array, MrValue::fixnum(1)) {
+            Err(MrubyError::Cast(expected)) => assert_eq!(expected, "Array"),
+            _ => assert!(false)
+        }
+
+        array.funcall(mrb, "freeze", &[]).unwrap();
+
+        match MrValue::array_push(mrb, array, MrValue::nil()) {
+            Err(MrubyError::Exception { class, .. }) => assert_eq!(class, "FrozenError"),
+            _ => assert!(false)
+        }
+
+        assert!(MrValue::array_pop(mrb, array).is_err());
+
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn array_iter() {
+    unsafe {
+        let mrb = mrb_open();
+
+        let array = MrValue::array(mrb, vec![MrValue::fixnum(1), MrValue::fixnum(2),
+                                             MrValue::fixnum(3)]);
+
+        let mut iter = array.array_iter(mrb).unwrap();
+
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.next().unwrap().to_i32().unwrap(), 1);
+        assert_eq!(iter.len(), 2);
+
+        let rest: Vec<i32> = iter.map(|value| value.to_i32().unwrap()).collect();
+
+        assert_eq!(rest, vec![2, 3]);
+
+        let iter = array.array_iter(mrb).unwrap();
+
+        MrValue::array_pop(mrb, array).unwrap();
+
+        let values: Vec<MrValue> = iter.collect();
+
+        assert_eq!(values.len(), 3);
+        assert!(values[2].is_nil());
+
+        assert_eq!(MrValue::array(mrb, vec![]).array_iter(mrb).unwrap().count(), 0);
+
+        match MrValue::fixnum(1).array_iter(mrb) {
+            Err(MrubyError::Cast(expected)) => assert_eq!(expected, "Array"),
+            _ => assert!(false)
+        }
+
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn hash_access() {
+    unsafe {
+        let mrb = mrb_open();
+
+        let hash = MrValue::hash(mrb, vec![(MrValue::symbol(mrb, "a"), MrValue::fixnum(1))]);
+
+        MrValue::hash_set(mrb, hash, MrValue::symbol(mrb, "b"), MrValue::fixnum(2)).unwrap();
+
+        assert_eq!(MrValue::hash_get(mrb, hash, MrValue::symbol(mrb, "b")).unwrap()
+                       .to_i32().unwrap(), 2);
+        assert!(MrValue::hash_get(mrb, hash, MrValue::symbol(mrb, "c")).unwrap().is_nil());
+
+        let keys = MrValue::hash_keys(mrb, hash).unwrap().to_vec(mrb).unwrap();
+        let keys: Vec<String> = keys.iter().map(|key| key.to_str(mrb).unwrap().to_owned()).collect();
+
+        assert_eq!(keys, vec!["a", "b"]);
+
+        let values = MrValue::hash_values(mrb, hash).unwrap().to_vec(mrb).unwrap();
+        let values: Vec<i32> = values.iter().map(|value| value.to_i32().unwrap()).collect();
+
+        assert_eq!(values, vec![1, 2]);
+
+        assert_eq!(MrValue::hash_delete(mrb, hash, MrValue::symbol(mrb, "a")).unwrap()
+                       .to_i32().unwrap(), 1);
+        assert!(MrValue::hash_delete(mrb, hash, MrValue::symbol(mrb, "a")).unwrap().is_nil());
+        assert_eq!(hash.to_hash(mrb).unwrap().len(), 1);
+
+        match MrValue::hash_keys(mrb, MrValue::fixnum(1)) {
+            Err(MrubyError::Cast(expected)) => assert_eq!(expected, "Hash"),
+            _ => assert!(false)
+        }
+
+        hash.funcall(mrb, "freeze", &[]).unwrap();
+
+        match MrValue::hash_set(mrb, hash, MrValue::nil(), MrValue::nil()) {
+            Err(MrubyError::Exception { class, .. }) => assert_eq!(class, "FrozenError"),
+            _ => assert!(false)
+        }
+
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn hash_iter() {
+    unsafe {
+        let mrb = mrb_open();
+
+        let hash = MrValue::hash(mrb, vec![(MrValue::fixnum(1), MrValue::string(mrb, "one")),
+                                           (MrValue::fixnum(2), MrValue::string(mrb, "two"))]);
+
+        let mut iter = hash.hash_iter(mrb).unwrap();
+
+        assert_eq!(iter.len(), 2);
+
+        let (key, value) = iter.next().unwrap().unwrap();
+
+        assert_eq!(key.to_i32().unwrap(), 1);
+        assert_eq!(value.to_str(mrb).unwrap(), "one");
+        assert_eq!(iter.len(), 1);
+
+        let iter = hash.hash_iter(mrb).unwrap();
+
+        MrValue::hash_delete(mrb, hash, MrValue::fixnum(2)).unwrap();
+
+        let pairs: Vec<(MrValue, MrValue)> = iter.collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(pairs.len(), 2);
+        assert!(pairs[1].1.is_nil());
+
+        assert_eq!(MrValue::hash(mrb, vec![]).hash_iter(mrb).unwrap().count(), 0);
+
+        let context = mrbc_context_new(mrb);
+        let code = "h = Hash.new { raise 'boom' }; h[1] = 2; h[3] = 4; h";
+        let hash = mrb_ext_load_nstring_cxt_nothrow(mrb, code.as_ptr(), code.len(), context);
+
+        let mut iter = hash.hash_iter(mrb).unwrap();
+
+        MrValue::hash_delete(mrb, hash, MrValue::fixnum(1)).unwrap();
+
+        match iter.next() {
+            Some(Err(MrubyError::Exception { message, .. })) => assert_eq!(message, "boom"),
+            _ => assert!(false)
+        }
+
+        assert_eq!(iter.next().unwrap().unwrap().1.to_i32().unwrap(), 4);
+
+        match MrValue::fixnum(1).hash_iter(mrb) {
+            Err(MrubyError::Cast(expected)) => assert_eq!(expected, "Hash"),
+            _ => assert!(false)
+        }
+
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn mruby_class() {
+    extern "C" fn answer(_mrb: *const MrState, _slf: MrValue) -> MrValue {
+        unsafe { MrValue::fixnum(42) }
+    }
+
+    unsafe {
+        let mrb = mrb_open();
+        let context = mrbc_context_new(mrb);
+
+        let eval = |code: &str| {
+            mrb_ext_load_nstring_cxt_nothrow(mrb, code.as_ptr(), code.len(), context)
+        };
+
+        eval("class Base; end; class Derived < Base; end; module Greet; def greet; 'hi'; end; end");
+
+        let derived = MrubyClass::new(mrb, eval("Derived").to_class().unwrap());
+        let greet = MrubyModule::new(mrb, eval("Greet").to_module().unwrap()).unwrap();
+
+        derived.define_method("answer", answer, 0);
+        derived.define_class_method("answer", answer, 0);
+        derived.define_const("LIMIT", MrValue::fixnum(3));
+        derived.include_module(&greet);
+
+        let result = eval("[Derived.new.answer, Derived.answer, Derived::LIMIT, Derived.new.greet]");
+
+        assert_eq!(result.funcall(mrb, "inspect", &[]).unwrap().to_str(mrb).unwrap(),
+                   "[42, 42, 3, \"hi\"]");
+
+        assert_eq!(derived.name().unwrap(), "Derived");
+        assert_eq!(greet.name().unwrap(), "Greet");
+        assert_eq!(derived.superclass().unwrap().name().unwrap(), "Base");
+
+        let basic = MrubyClass::new(mrb, eval("BasicObject").to_class().unwrap());
+
+        assert!(basic.superclass().is_none());
+
+        let anonymous = MrubyClass::new(mrb, eval("Class.new").to_class().unwrap());
+
+        assert!(anonymous.name().unwrap().starts_with("#<Class:"));
+
+        mrbc_context_free(mrb, context);
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn mruby_module() {
+    extern "C" fn twice(mrb: *const MrState, _slf: MrValue) -> MrValue {
+        unsafe {
+            let args = MrValue::method_args(mrb, 1, 1).unwrap();
+
+            MrValue::fixnum(args[0].to_i32().unwrap() * 2)
+        }
+    }
+
+    unsafe {
+        let mrb = mrb_open();
+        let context = mrbc_context_new(mrb);
+
+        let eval = |code: &str| {
+            mrb_ext_load_nstring_cxt_nothrow(mrb, code.as_ptr(), code.len(), context)
+        };
+
+        eval("module Helpers; end; class Host; def answer; twice(LIMIT); end; end");
+
+        let helpers = MrubyModule::new(mrb, eval("Helpers").to_module().unwrap()).unwrap();
+        let host = MrubyClass::new(mrb, eval("Host").to_class().unwrap());
+
+        helpers.define_module_function("twice", twice, 1 << 12);
+        helpers.define_const("LIMIT", MrValue::fixnum(21));
+        helpers.include_into(&host);
+
+        assert_eq!(eval("Helpers.twice(2)").to_i32().unwrap(), 4);
+        assert_eq!(eval("Host.new.answer").to_i32().unwrap(), 42);
+        assert!(eval("Host.include?(Helpers)").to_bool().unwrap());
+        assert_eq!(helpers.name().unwrap(), "Helpers");
+
+        match MrubyModule::new(mrb, host.as_ptr()) {
+            Err(MrubyError::Cast(expected)) => assert_eq!(expected, "Module"),
+            _ => assert!(false)
+        }
+
+        mrbc_context_free(mrb, context);
+        mrb_close(mrb);
+    }
+}
+
+#[test]
+fn mruby_class_exception_subclass() {
+    unsafe {
+        let mrb = mrb_open();
+        let context = mrbc_context_new(mrb);
+
+        let eval = |code: &str| {
+            mrb_ext_load_nstring_cxt_nothrow(mrb, code.as_ptr(), code.len(), context)
+        };
+
+        let parse_error = MrubyClass::exception_subclass(mrb, "ParseError").unwrap();
+        let bad_option = MrubyClass::argument_error_subclass(mrb, "BadOption").unwrap();
+        let bad_kind = MrubyClass::type_error_subclass(mrb, "BadKind").unwrap();
+        let failed = MrubyClass::runtime_error_subclass(mrb, "Failed").unwrap();
+
+        assert_eq!(parse_error.name().unwrap(), "ParseError");
+        assert_eq!(parse_error.superclass().unwrap().name().unwrap(), "StandardError");
+        assert_eq!(bad_option.superclass().unwrap().name().unwrap(), "ArgumentError");
+        assert_eq!(bad_kind.superclass().unwrap().name().unwrap(), "TypeError");
+        assert_eq!(failed.superclass().unwrap().name().unwrap(), "RuntimeError");
+
+        let detail = failed.define_subclass("DetailedFailure").unwrap();
+
+        assert_eq!(detail.superclass().unwrap().name().unwrap(), "Failed");
+        assert_eq!(failed.define_subclass("DetailedFailure").unwrap().as_ptr(), detail.as_ptr());
+
+        match failed.define_subclass("BadOption") {
+            Err(MrubyError::Exception { class, .. }) => assert_eq!(class, "TypeError"),
+            _ => assert!(false)
+        }
+
+        eval("LIMIT = 1");
+
+        match failed.define_subclass("LIMIT") {
+            Err(MrubyError::Exception { class, .. }) => assert_eq!(class, "TypeError"),
+            _ => assert!(false)
+        }
+
+        let result = eval("
+          [ParseError, BadOption, BadKind, DetailedFailure].map do |error|
+            begin
+              raise error, 'oops'
+            rescue ArgumentError, TypeError, RuntimeError => e
+              e.class.superclass
+            rescue StandardError => e
+              :standard
+            end
+          end.inspect
+        ");
+
+        assert_eq!(result.to_str(mrb).unwrap(), "[:standard, ArgumentError, TypeError, Failed]");
+
+        mrbc_context_free(mrb, context);
+        mrb_close(mrb);
+    }
+}
@@ -0,0 +1,114 @@
+// mrusty. mruby safe bindings for Rust
+// Copyright (C) 2016  Dragoș Tiselice
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "datetime")]
+use chrono::{DateTime, Utc};
+
+use super::convert::FromValue;
+use super::mruby::{Class, MrubyError, MrubyImpl, MrubyType, Value};
+
+impl Value {
+    /// Casts mruby `Value` of `Class` `Time` to Rust type `SystemTime`, with microsecond
+    /// precision. Returns `MrubyError::Cast` if the `Value` is not a `Time` and
+    /// `MrubyError::Runtime` if mruby was built without the mruby-time gem.
+    ///
+    /// Only available with the `time` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("Time.at(1_000_000_000, 250_000)").unwrap();
+    ///
+    /// assert_eq!(result.to_system_time().unwrap(),
+    ///            UNIX_EPOCH + Duration::new(1_000_000_000, 250_000_000));
+    /// ```
+    pub fn to_system_time(&self) -> Result<SystemTime, MrubyError> {
+        let class = time_class(&self.mruby)?;
+
+        if !self.is_a(class) {
+            return Err(MrubyError::Cast("Time".to_owned()));
+        }
+
+        let secs = self.call("to_i", vec![])?.to_i64()?;
+        let usecs = self.call("usec", vec![])?.to_i64()?;
+
+        // usec is always positive; times before the epoch have a negative to_i rounded down.
+        let micros = Duration::from_micros(usecs as u64);
+
+        if secs >= 0 {
+            Ok(UNIX_EPOCH + Duration::from_secs(secs as u64) + micros)
+        } else {
+            Ok(UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs()) + micros)
+        }
+    }
+
+    /// Casts mruby `Value` of `Class` `Time` to Rust type `DateTime<Utc>` like
+    /// `to_system_time`.
+    ///
+    /// Only available with the `datetime` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("Time.at(1_000_000_000)").unwrap();
+    ///
+    /// assert_eq!(result.to_datetime().unwrap().to_rfc3339(), "2001-09-09T01:46:40+00:00");
+    /// ```
+    #[cfg(feature = "datetime")]
+    #[inline]
+    pub fn to_datetime(&self) -> Result<DateTime<Utc>, MrubyError> {
+        self.to_system_time().map(DateTime::from)
+    }
+}
+
+impl FromValue for SystemTime {
+    #[inline]
+    fn from_value(value: Value) -> Result<SystemTime, MrubyError> {
+        value.to_system_time()
+    }
+}
+
+#[cfg(feature = "datetime")]
+impl FromValue for DateTime<Utc> {
+    #[inline]
+    fn from_value(value: Value) -> Result<DateTime<Utc>, MrubyError> {
+        value.to_datetime()
+    }
+}
+
+// Creates a `Time` with `Time.at(secs, usecs)`, truncating `time` to microseconds.
+pub fn time(mruby: &MrubyType, time: SystemTime) -> Result<Value, MrubyError> {
+    let class = time_class(mruby)?;
+
+    let (secs, usecs) = match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => (duration.as_secs() as i64, duration.subsec_micros() as i64),
+        Err(err)     => {
+            let duration = err.duration();
+
+            (-(duration.as_secs() as i64), -(duration.subsec_micros() as i64))
+        }
+    };
+
+    class.to_value().call("at", vec![mruby.fixnum_i64(secs), mruby.fixnum_i64(usecs)])
+}
+
+#[inline]
+fn time_class(mruby: &MrubyType) -> Result<Class, MrubyError> {
+    mruby.get_class("Time").map_err(|_| {
+        MrubyError::runtime("Time is not defined; mruby needs the mruby-time gem".to_owned())
+    })
+}
@@ -5,8 +5,19 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+#![allow(clippy::assertions_on_constants, clippy::bool_assert_comparison,
+         clippy::legacy_numeric_constants, clippy::mutable_key_type,
+         clippy::needless_borrowed_reference, clippy::redundant_field_names,
+         clippy::unnecessary_get_then_check, static_mut_refs)]
+
 #[macro_use]
 extern crate mrusty;
+#[cfg(feature = "datetime")]
+extern crate chrono;
+#[cfg(feature = "deserializer")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_json;
 
 use std::path::Path;
 
@@ -231,6 +242,1594 @@ fn api_mruby_panic() {
     assert_eq!(result.to_str().unwrap(), "rescued");
 }
 
+#[test]
+fn api_hash() {
+    let mruby = Mruby::new();
+
+    let config = mruby.hash(vec![
+        (mruby.symbol("width"), mruby.fixnum(640)),
+        (mruby.string("title"), mruby.string("mrusty")),
+        (mruby.fixnum(1), mruby.bool(true))
+    ]);
+
+    mruby.run("
+      def options(config)
+        { size: config[:width] * 2, name: config['title'], first: config[1] }
+      end
+    ").unwrap();
+
+    let options = mruby.run("self").unwrap().call("options", vec![config]).unwrap();
+
+    assert_eq!(options.to_hash().unwrap(), vec![
+        (mruby.symbol("size"), mruby.fixnum(1280)),
+        (mruby.symbol("name"), mruby.string("mrusty")),
+        (mruby.symbol("first"), mruby.bool(true))
+    ]);
+
+    let empty = mruby.run("{}").unwrap();
+
+    assert!(empty.to_hash().unwrap().is_empty());
+    assert!(mruby.hash(vec![]).to_hash().unwrap().is_empty());
+}
+
+#[test]
+fn api_hashmap_errors() {
+    use mrusty::MrubyError;
+
+    let mruby = Mruby::new();
+
+    let result = mruby.run("{ 'a' => 1, 'b' => 'two' }").unwrap();
+
+    match result.to_hashmap::<String, i32>() {
+        Err(MrubyError::Nested { path, error }) => {
+            assert_eq!(path, vec!["\"b\""]);
+            assert_eq!(error.to_string(), "Cast error: expected Fixnum");
+        },
+        _ => assert!(false)
+    }
+
+    let result = mruby.run("{ a: 1, 'a' => 2 }").unwrap();
+
+    match result.to_hashmap::<String, i32>() {
+        Err(MrubyError::Cast(expected)) => {
+            assert_eq!(expected, "unique Hash keys, found duplicate \"a\"");
+        },
+        _ => assert!(false)
+    }
+}
+
+#[test]
+fn api_fixnum_i64() {
+    use mrusty::MrubyError;
+
+    let mruby = Mruby::new();
+
+    let timestamp = mruby.fixnum_i64(1_700_000_000_000);
+    let result = timestamp.call("+", vec![mruby.fixnum(1)]).unwrap();
+
+    assert_eq!(result.to_i64().unwrap(), 1_700_000_000_001);
+
+    match result.to_i32() {
+        Err(MrubyError::Overflow(err)) => assert_eq!(err, "1700000000001 does not fit in i32"),
+        _ => assert!(false)
+    }
+}
+
+#[test]
+fn api_call_with_block() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let mruby = Mruby::new();
+
+    let seen = Rc::new(RefCell::new(vec![]));
+    let seen_block = seen.clone();
+
+    let block = mruby.proc(move |mruby, args| {
+        seen_block.borrow_mut().push(args[0].to_i32().unwrap());
+
+        mruby.nil()
+    });
+
+    let array = mruby.run("[1, 2, 3]").unwrap();
+
+    array.call_with_block("each", vec![], block).unwrap();
+
+    assert_eq!(*seen.borrow(), vec![1, 2, 3]);
+
+    let block = mruby.proc(|mruby, args| {
+        mruby.fixnum(args[0].to_i32().unwrap() + args[1].to_i32().unwrap())
+    });
+
+    let result = array.call_with_block("inject", vec![mruby.fixnum(10)], block).unwrap();
+
+    assert_eq!(result.to_i32().unwrap(), 16);
+}
+
+#[test]
+fn api_call_with_block_panic() {
+    use mrusty::MrubyError;
+
+    let mruby = Mruby::new();
+
+    let block = mruby.proc(|_mruby, _args| {
+        panic!("block failed");
+    });
+
+    let array = mruby.run("[1]").unwrap();
+
+    match array.call_with_block("each", vec![], block) {
+        Err(MrubyError::Exception { class, message, .. }) => {
+            assert_eq!(class, "RustPanic");
+            assert_eq!(message, "block failed");
+        },
+        _ => assert!(false)
+    }
+}
+
+#[test]
+fn api_proc_stored() {
+    let mruby = Mruby::new();
+
+    let add = mruby.proc(|mruby, args| {
+        mruby.fixnum(args.iter().map(|arg| arg.to_i32().unwrap()).sum())
+    });
+
+    mruby.run("
+      class Callbacks
+        def self.store(block)
+          $callback = block
+        end
+      end
+    ").unwrap();
+
+    mruby.run("Callbacks").unwrap().call("store", vec![add.clone()]).unwrap();
+
+    assert_eq!(mruby.run("$callback.call(1, 2, 3)").unwrap().to_i32().unwrap(), 6);
+    assert_eq!(mruby.run("[1, 2].map(&$callback)").unwrap().to_vec().unwrap(), vec![
+        mruby.fixnum(1),
+        mruby.fixnum(2)
+    ]);
+
+    let callback = mruby.run("$callback").unwrap();
+
+    assert_eq!(callback.call_proc(vec![mruby.fixnum(4)]).unwrap().to_i32().unwrap(), 4);
+
+    let sum = add.call("call", vec![mruby.fixnum(1), mruby.fixnum(2)]).unwrap();
+
+    assert_eq!(sum.to_i32().unwrap(), 3);
+    assert_eq!(add.call("[]", vec![mruby.fixnum(4)]).unwrap().to_i32().unwrap(), 4);
+    assert!(mruby.fixnum(1).call_proc(vec![]).is_err());
+
+    let overridden = mruby.run("p = proc { 1 }; def p.call(*a); 42; end; p").unwrap();
+
+    assert_eq!(overridden.call("call", vec![]).unwrap().to_i32().unwrap(), 42);
+    assert_eq!(mruby.run("p.call").unwrap().to_i32().unwrap(), 42);
+}
+
+#[test]
+fn api_proc_reentrant() {
+    let mruby = Mruby::new();
+
+    let reenter = mruby.proc(|mruby, _args| {
+        mruby.run("begin; $reenter.call; rescue => e; e.message; end").unwrap()
+    });
+
+    mruby.set_gv("$reenter", reenter.clone());
+
+    assert_eq!(reenter.call_proc(vec![]).unwrap().to_str().unwrap(),
+               "Proc called while already running");
+}
+
+#[test]
+fn api_panic_rescue() {
+    use mrusty::MrubyType;
+
+    struct Broken;
+
+    impl MrubyFile for Broken {
+        fn require(_mruby: MrubyType) {
+            panic!("cannot require");
+        }
+    }
+
+    let mruby = Mruby::new();
+
+    mruby.def_file::<Broken>("broken");
+
+    let result = mruby.run("
+      begin
+        require 'broken'
+      rescue RuntimeError => e
+        e.message
+      end
+    ").unwrap();
+
+    assert_eq!(result.to_str().unwrap(), "cannot require");
+}
+
+#[test]
+fn api_exception() {
+    use mrusty::MrubyError;
+
+    let mruby = Mruby::new();
+
+    mruby.run("class ParseError < StandardError; end").unwrap();
+
+    match mruby.run("raise ParseError, 'unexpected token'") {
+        Err(MrubyError::Runtime { class, message, backtrace }) => {
+            assert_eq!(class, "ParseError");
+            assert_eq!(message, "unexpected token");
+            assert!(backtrace.is_empty());
+        },
+        _ => assert!(false)
+    }
+
+    let err = mruby.fixnum(1).call("+", vec![mruby.string("a")]).unwrap_err();
+
+    match err {
+        MrubyError::Exception { ref class, .. } => assert_eq!(class, "TypeError"),
+        _ => assert!(false)
+    }
+
+    assert_eq!(format!("{}", mruby.run("raise 'oops'").unwrap_err()),
+               "Runtime error: oops (RuntimeError)");
+
+    mruby.run("class SilentError < StandardError; def message; raise 'nope'; end; end").unwrap();
+
+    match mruby.run("raise SilentError") {
+        Err(MrubyError::Runtime { class, message, .. }) => {
+            assert_eq!(class, "SilentError");
+            assert_eq!(message, "");
+        },
+        _ => assert!(false)
+    }
+}
+
+#[test]
+fn api_full_gc_drop() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    struct Cont {
+        drops: Rc<Cell<u32>>
+    }
+
+    impl Drop for Cont {
+        fn drop(&mut self) {
+            self.drops.set(self.drops.get() + 1);
+        }
+    }
+
+    let mruby = Mruby::new();
+    let drops = Rc::new(Cell::new(0));
+
+    mruby.def_class_for::<Cont>("Container");
+
+    let drops_ptr = drops.clone();
+
+    mruby.def_class_method_for::<Cont, _>("make", move |mruby, _slf| {
+        mruby.obj(Cont { drops: drops_ptr.clone() })
+    });
+
+    mruby.run("3.times { Container.make }; nil").unwrap();
+    mruby.full_gc();
+
+    assert_eq!(drops.get(), 3);
+}
+
+#[test]
+fn api_symbol_string() {
+    let mruby = Mruby::new();
+
+    let values = mruby.run("[:foo, 'foo']").unwrap().to_vec().unwrap();
+
+    assert_eq!(values[0].to_sym().unwrap(), mruby.symbol("foo").to_sym().unwrap());
+    assert!(values[0].to_str_strict().is_err());
+    assert!(values[1].to_sym().is_err());
+    assert_eq!(values[1].to_str_strict().unwrap(), "foo");
+    assert_eq!(values[0].to_str().unwrap(), values[1].to_str().unwrap());
+}
+
+#[test]
+fn api_arena_guard() {
+    let mruby = Mruby::new();
+
+    let count = "GC.start; ObjectSpace.count_objects[:T_STRING]";
+    let before = mruby.run(count).unwrap().to_i32().unwrap();
+
+    for i in 0..100_000 {
+        let _guard = unsafe { mruby.arena_guard() };
+
+        mruby.string(&i.to_string());
+    }
+
+    let after = mruby.run(count).unwrap().to_i32().unwrap();
+
+    assert!(after - before < 1000);
+}
+
+#[test]
+fn api_intern_call_sym() {
+    let mruby = Mruby::new();
+
+    mruby.run("
+      class Counter
+        def initialize; @count = 0; end
+        def bump(n); @count += n; end
+      end
+    ").unwrap();
+
+    let counter = mruby.run("Counter.new").unwrap();
+    let bump = mruby.intern("bump");
+
+    for _ in 0..100 {
+        counter.call_sym(&bump, vec![mruby.fixnum(2)]).unwrap();
+    }
+
+    let count = mruby.intern("@count");
+
+    assert_eq!(counter.get_ivar_sym(&count).unwrap().to_i32().unwrap(), 200);
+    assert_eq!(mruby.intern("bump"), bump);
+    assert!(counter.call_sym(&mruby.intern("missing"), vec![]).is_err());
+}
+
+#[test]
+fn api_option_conversions() {
+    use std::collections::HashMap;
+
+    use mrusty::FromValue;
+
+    let mruby = Mruby::new();
+
+    assert_eq!(Option::<String>::from_value(mruby.nil()).unwrap(), None);
+    assert_eq!(Option::<String>::from_value(mruby.string("a")).unwrap(), Some("a".to_owned()));
+    assert!(Option::<String>::from_value(mruby.fixnum(1)).is_err());
+
+    let values = mruby.run("[1, nil, 3]").unwrap();
+
+    assert_eq!(Vec::<Option<i32>>::from_value(values.clone()).unwrap(), vec![Some(1), None, Some(3)]);
+    assert!(Vec::<i32>::from_value(values).is_err());
+
+    let map: HashMap<String, Option<i32>> = mruby.run("{ 'a' => nil, 'b' => 2 }").unwrap()
+                                                 .to_hashmap().unwrap();
+
+    assert_eq!(map["a"], None);
+    assert_eq!(map["b"], Some(2));
+
+    let value = mruby.obj_from(vec![Some(1), None]);
+
+    assert_eq!(value.call("compact", vec![]).unwrap().to_vec().unwrap(), vec![mruby.fixnum(1)]);
+}
+
+#[test]
+fn api_gc_disable() {
+    let mruby = Mruby::new();
+
+    let count = "ObjectSpace.count_objects[:T_STRING]";
+
+    mruby.full_gc();
+    mruby.gc_disable();
+
+    let before = mruby.run(count).unwrap().to_i32().unwrap();
+
+    mruby.run("1000.times { |i| i.to_s }").unwrap();
+    mruby.full_gc();
+
+    let disabled = mruby.run(count).unwrap().to_i32().unwrap();
+
+    assert!(disabled - before >= 1000);
+
+    mruby.gc_enable();
+    mruby.full_gc();
+
+    let enabled = mruby.run(count).unwrap().to_i32().unwrap();
+
+    assert!(enabled < disabled);
+}
+
+#[test]
+fn api_to_option_false_nil() {
+    let mruby = Mruby::new();
+
+    mruby.run("
+      def lookup(key)
+        { 'off' => false, 'on' => true }[key]
+      end
+    ").unwrap();
+
+    let lookup = |key: &str| {
+        mruby.run(&format!("lookup('{}')", key)).unwrap().to_option_from::<bool>().unwrap()
+    };
+
+    assert_eq!(lookup("off"), Some(false));
+    assert_eq!(lookup("on"), Some(true));
+    assert_eq!(lookup("missing"), None);
+
+    let args = mruby.obj_from(vec![Some(1), None]);
+
+    assert_eq!(args.call("count", vec![mruby.nil()]).unwrap().to_i32().unwrap(), 1);
+}
+
+#[test]
+fn api_bytes_round_trip() {
+    let mruby = Mruby::new();
+
+    let payload: Vec<u8> = vec![0x1f, 0x8b, 0, 0xff, 0xfe, b'a', 0, 0xc3];
+    let value = mruby.bytes(&payload);
+
+    assert_eq!(value.call("bytesize", vec![]).unwrap().to_i32().unwrap(), 8);
+    assert!(value.to_str().is_err());
+
+    let reversed = value.call("reverse", vec![]).unwrap().to_bytes().unwrap();
+
+    assert_eq!(reversed.into_iter().rev().collect::<Vec<_>>(), payload);
+    assert_eq!(mruby.bytes(&[]).to_bytes().unwrap(), Vec::<u8>::new());
+    assert!(mruby.fixnum(1).to_bytes().is_err());
+}
+
+#[test]
+fn api_exception_backtrace() {
+    use mrusty::MrubyError;
+
+    let mruby = Mruby::new();
+
+    mruby.filename("parser.rb");
+
+    let err = mruby.run("
+      def parse
+        raise IndexError, 'out of tokens'
+      end
+
+      parse
+    ").unwrap_err();
+
+    match err {
+        MrubyError::Runtime { class, message, backtrace } => {
+            assert_eq!(class, "IndexError");
+            assert_eq!(message, "out of tokens");
+            assert_eq!(backtrace, vec!["parser.rb:3:in parse", "parser.rb:6"]);
+        },
+        _ => assert!(false)
+    }
+}
+
+#[test]
+fn api_as_bytes_borrowed() {
+    let mruby = Mruby::new();
+
+    let value = mruby.run("'0123456789' * 1_048_576").unwrap();
+
+    let first = value.as_bytes().unwrap();
+    let second = value.as_bytes().unwrap();
+
+    assert_eq!(first.len(), 10 * 1024 * 1024);
+    assert_eq!(first.as_ptr(), second.as_ptr());
+    assert_eq!(&first[..12], b"012345678901");
+    assert!(mruby.fixnum(1).as_bytes().is_err());
+}
+
+#[test]
+fn api_def_exception_raise() {
+    let mruby = Mruby::new();
+
+    let config_error = mruby.def_exception("ConfigError", None).unwrap();
+    mruby.def_exception("MissingKeyError", Some(config_error)).unwrap();
+
+    struct Config;
+
+    mruby.def_class_for::<Config>("Config");
+    mruby.def_class_method_for::<Config, _>("fetch", mrfn!(|mruby, _slf: Value, key: (&str)| {
+        let class = mruby.get_class("MissingKeyError").unwrap();
+
+        mruby.raise(class, &format!("missing key: {}", key))
+    }));
+
+    let result = mruby.run("
+      begin
+        Config.fetch 'port'
+      rescue ConfigError => e
+        [e.class, e.message, e.is_a?(StandardError)]
+      end
+    ").unwrap();
+
+    assert_eq!(result.call("inspect", vec![]).unwrap().to_str().unwrap(),
+               "[MissingKeyError, \"missing key: port\", true]");
+}
+
+#[test]
+fn api_def_exception_conflict() {
+    use mrusty::MrubyError;
+
+    let mruby = Mruby::new();
+
+    mruby.run("Foo = 1; class Bar < StandardError; end").unwrap();
+
+    let argument_error = mruby.get_class("ArgumentError").unwrap();
+
+    match mruby.def_exception("Foo", None) {
+        Err(MrubyError::Exception { ref class, .. }) => assert_eq!(class, "TypeError"),
+        _ => assert!(false)
+    }
+
+    match mruby.def_exception("Bar", Some(argument_error)) {
+        Err(MrubyError::Exception { ref class, .. }) => assert_eq!(class, "TypeError"),
+        _ => assert!(false)
+    }
+
+    assert_eq!(mruby.def_exception("Bar", None).unwrap().to_str(), "Bar");
+}
+
+#[test]
+fn api_invalid_utf8() {
+    use mrusty::MrubyError;
+
+    let mruby = Mruby::new();
+
+    let value = mruby.run("[0x68, 0x69, 0xff, 0xfe].pack('C*')").unwrap();
+
+    match value.to_str() {
+        Err(MrubyError::Cast(_)) => (),
+        _ => assert!(false)
+    }
+
+    assert_eq!(value.to_str_lossy().unwrap(), "hi\u{fffd}\u{fffd}");
+    assert_eq!(mruby.run("'plain'").unwrap().to_str_lossy().unwrap(), "plain");
+    assert!(mruby.fixnum(1).to_str_lossy().is_err());
+}
+
+#[test]
+fn api_string_interior_nul() {
+    let mruby = Mruby::new();
+
+    let value = mruby.run("\"a\\0b\"").unwrap();
+
+    assert_eq!(value.to_str().unwrap(), "a\0b");
+    assert_eq!(value.to_str().unwrap().len(), 3);
+    assert_eq!(value.to_str().unwrap(), "a\0b");
+    assert_eq!(value.to_bytes().unwrap(), b"a\0b");
+    assert_eq!(value.as_bytes().unwrap().len(), 3);
+
+    let string = mruby.string("a\0b");
+
+    assert_eq!(string.call("bytesize", vec![]).unwrap().to_i32().unwrap(), 3);
+    assert_eq!(string.to_str().unwrap(), "a\0b");
+}
+
+#[test]
+fn api_global_variables() {
+    let mruby = Mruby::new();
+
+    mruby.set_gv("$config", mruby.hash(vec![(mruby.string("port"), mruby.fixnum(8080))]));
+
+    let result = mruby.run("
+      $port = $config['port'] + 1
+    ").unwrap();
+
+    assert_eq!(result.to_i32().unwrap(), 8081);
+    assert_eq!(mruby.get_gv("$port").to_i32().unwrap(), 8081);
+    assert!(mruby.get_gv("$undefined").is_nil());
+}
+
+#[test]
+fn api_constants() {
+    let mruby = Mruby::new();
+
+    mruby.run("
+      VERSION = '3.0.1'
+
+      class Config
+        LIMIT = 10
+
+        def self.limit
+          LIMIT
+        end
+      end
+    ").unwrap();
+
+    assert_eq!(mruby.get_const("VERSION").unwrap().to_str().unwrap(), "3.0.1");
+    assert!(mruby.get_const("Missing").is_none());
+
+    let config = mruby.get_class("Config").unwrap();
+
+    assert_eq!(config.get_const("LIMIT").unwrap().to_i32().unwrap(), 10);
+
+    config.set_const("LIMIT", mruby.fixnum(20));
+    config.set_const("NAME", mruby.string("app"));
+
+    assert_eq!(mruby.run("[Config.limit, Config::NAME]").unwrap()
+                    .call("inspect", vec![]).unwrap().to_str().unwrap(), "[20, \"app\"]");
+}
+
+#[test]
+fn api_class_variables() {
+    let mruby = Mruby::new();
+
+    mruby.run("
+      class Registry
+        @@entries = []
+
+        def self.register(name)
+          @@entries << name
+        end
+      end
+
+      class Plugins < Registry; end
+
+      Plugins.register 'a'
+    ").unwrap();
+
+    let registry = mruby.get_class("Registry").unwrap();
+    let plugins = mruby.get_class("Plugins").unwrap();
+
+    assert!(plugins.has_cv("@@entries"));
+    assert_eq!(registry.get_cv("@@entries").unwrap().to_vec().unwrap().len(), 1);
+
+    registry.set_cv("@@entries", mruby.array(vec![]));
+
+    assert_eq!(mruby.run("Plugins.register('b')").unwrap().to_vec().unwrap().len(), 1);
+    assert!(registry.get_cv("@@missing").is_none());
+}
+
+#[test]
+fn api_value_display() {
+    let mruby = Mruby::new();
+
+    struct Cont;
+
+    mruby.def_class_for::<Cont>("Container");
+
+    mruby.run("
+      class Broken
+        def to_s
+          raise 'no'
+        end
+      end
+
+      class Odd
+        def to_s
+          1
+        end
+      end
+    ").unwrap();
+
+    assert_eq!(format!("{}", mruby.nil()), "");
+    assert_eq!(format!("{}", mruby.bool(false)), "false");
+    assert_eq!(format!("{}", mruby.run("[[1, [2, [3, [nil]]]], 'a']").unwrap()),
+               "[[1, [2, [3, [nil]]]], \"a\"]");
+    assert!(mruby.obj(Cont).to_s().starts_with("#<Container"));
+    assert_eq!(mruby.run("Broken.new").unwrap().to_s(), "Broken");
+    assert_eq!(format!("{}", mruby.run("Odd.new").unwrap()), "Odd");
+}
+
+#[test]
+fn api_value_eq_ruby() {
+    let mruby = Mruby::new();
+
+    struct Cont {
+        value: i32
+    }
+
+    mruby.def_class_for::<Cont>("Container");
+    mruby.def_method_for::<Cont, _>("==", mrfn!(|mruby, slf: (&Cont), other: (&Cont)| {
+        mruby.bool(slf.value == other.value)
+    }));
+
+    assert_eq!(mruby.string("foo"), mruby.string("foo"));
+    assert!(mruby.string("foo") != mruby.string("bar"));
+    assert_eq!(mruby.run("[1, [2.0, 'a']]").unwrap(), mruby.run("[1, [2, 'a']]").unwrap());
+    assert_eq!(mruby.fixnum(2), mruby.float(2.0));
+    assert!(mruby.fixnum(2) != mruby.float(2.5));
+    assert_eq!(mruby.obj(Cont { value: 1 }), mruby.obj(Cont { value: 1 }));
+    assert!(mruby.obj(Cont { value: 1 }) != mruby.obj(Cont { value: 2 }));
+
+    mruby.run("
+      class Strict
+        def ==(other)
+          raise 'cannot compare'
+        end
+      end
+    ").unwrap();
+
+    assert!(!mruby.run("Strict.new").unwrap().eq_ruby(&mruby.fixnum(1)));
+    assert_eq!(mruby.run("1 + 1").unwrap().to_i32().unwrap(), 2);
+}
+
+#[test]
+fn api_method_introspection() {
+    let mruby = Mruby::new();
+
+    struct Cont;
+
+    mruby.def_class_for::<Cont>("Container");
+    mruby.def_method_for::<Cont, _>("value", mrfn!(|mruby, _slf: Value| {
+        mruby.fixnum(1)
+    }));
+    mruby.def_class_method_for::<Cont, _>("build", mrfn!(|mruby, _slf: Value| {
+        mruby.nil()
+    }));
+
+    mruby.run("
+      class Sub < Container
+        def self.extra; end
+      end
+    ").unwrap();
+
+    let sub = mruby.get_class("Sub").unwrap();
+
+    assert!(sub.method_defined("value"));
+    assert!(!sub.method_defined("build"));
+    assert!(sub.class_method_defined("build"));
+    assert!(sub.class_method_defined("extra"));
+    assert!(!mruby.get_class("Container").unwrap().class_method_defined("extra"));
+
+    let obj = mruby.obj(Cont);
+
+    assert!(obj.responds_to("value"));
+    assert!(!obj.responds_to("build"));
+    assert!(mruby.fixnum(1).responds_to("+"));
+    assert!(!mruby.nil().responds_to("each"));
+}
+
+#[test]
+fn api_value_is_a() {
+    let mruby = Mruby::new();
+
+    struct Cont;
+
+    mruby.def_class_for::<Cont>("Container");
+
+    mruby.run("
+      class Stack < Array; end
+      class Special < Container; end
+    ").unwrap();
+
+    let get = |name: &str| mruby.get_class(name).unwrap();
+
+    let stack = mruby.run("Stack.new").unwrap();
+
+    assert!(stack.is_a(get("Stack")));
+    assert!(stack.is_a(get("Array")));
+    assert!(stack.is_a(get("Object")));
+    assert!(!stack.is_a(get("Hash")));
+    assert_eq!(stack.class().to_str(), "Stack");
+
+    assert!(mruby.fixnum(3).is_a(get("Fixnum")));
+    assert!(mruby.fixnum(3).is_a(get("Integer")));
+    assert!(!mruby.fixnum(3).is_a(get("Float")));
+    assert!(mruby.nil().is_a(get("NilClass")));
+    assert!(mruby.bool(true).is_a(get("Object")));
+
+    assert!(mruby.run("Special.new").unwrap().is_a(get("Container")));
+    assert!(!mruby.obj(Cont).is_a(get("Special")));
+}
+
+#[test]
+fn api_hashed_value() {
+    use std::collections::HashMap;
+
+    use mrusty::HashedValue;
+
+    let mruby = Mruby::new();
+
+    let mut map = HashMap::new();
+
+    map.insert(HashedValue(mruby.string("name")), 1);
+    map.insert(HashedValue(mruby.symbol("name")), 2);
+    map.insert(HashedValue(mruby.run("[1, 'a']").unwrap()), 3);
+
+    assert_eq!(map.len(), 3);
+    assert_eq!(map[&HashedValue(mruby.run("'na' + 'me'").unwrap())], 1);
+    assert_eq!(map[&HashedValue(mruby.run(":name").unwrap())], 2);
+    assert_eq!(map[&HashedValue(mruby.run("[1, 'a']").unwrap())], 3);
+    assert!(map.get(&HashedValue(mruby.string("other"))).is_none());
+
+    let a = mruby.run("'x' * 3").unwrap().ruby_hash().unwrap();
+    let b = mruby.string("xxx").ruby_hash().unwrap();
+
+    assert_eq!(a, b);
+
+    mruby.run("
+      class Unhashable
+        def hash
+          raise 'nope'
+        end
+      end
+    ").unwrap();
+
+    assert!(mruby.run("Unhashable.new").unwrap().ruby_hash().is_err());
+
+    map.insert(HashedValue(mruby.fixnum(1)), 4);
+    map.insert(HashedValue(mruby.float(1.0)), 5);
+
+    assert_eq!(map[&HashedValue(mruby.fixnum(1))], 4);
+    assert_eq!(map[&HashedValue(mruby.float(1.0))], 5);
+}
+
+#[test]
+#[should_panic(expected = "HashedValue key cannot be hashed")]
+fn api_hashed_value_unhashable() {
+    use std::collections::HashSet;
+
+    use mrusty::HashedValue;
+
+    let mruby = Mruby::new();
+
+    let obj = mruby.run("
+      class Unhashable
+        def hash
+          raise 'nope'
+        end
+      end
+
+      Unhashable.new
+    ").unwrap();
+
+    HashSet::new().insert(HashedValue(obj));
+}
+
+#[test]
+fn api_value_inspect() {
+    let mruby = Mruby::new();
+
+    mruby.run("
+      class Broken
+        def inspect
+          raise 'nope'
+        end
+
+        def to_s
+          raise 'nope'
+        end
+      end
+    ").unwrap();
+
+    let broken = mruby.run("Broken.new").unwrap();
+
+    assert_eq!(broken.inspect(), "Broken");
+    assert_eq!(format!("{}", broken), "Broken");
+
+    assert_eq!(mruby.run("{a: 1}").unwrap().inspect(), "{:a=>1}");
+    assert_eq!(mruby.symbol("sym").inspect(), ":sym");
+    assert_eq!(mruby.symbol("sym").to_s(), "sym");
+}
+
+#[test]
+fn api_char() {
+    use mrusty::MrubyError;
+
+    let mruby = Mruby::new();
+
+    for &c in &['a', 'ß', '日', '🦀'] {
+        let value = mruby.char(c);
+
+        assert_eq!(value.to_char().unwrap(), c);
+        assert_eq!(mruby.fixnum(c as i32).to_char_from_codepoint().unwrap(), c);
+    }
+
+    assert_eq!(mruby.char('🦀').to_str().unwrap().len(), 4);
+
+    match mruby.string("").to_char() {
+        Err(MrubyError::Cast(expected)) => assert_eq!(expected, "String of length 1"),
+        _ => assert!(false)
+    }
+
+    assert!(mruby.string("e\u{301}").to_char().is_err());
+    assert!(mruby.fixnum(97).to_char().is_err());
+    assert!(mruby.string("a").to_char_from_codepoint().is_err());
+    assert!(mruby.fixnum(0x110000).to_char_from_codepoint().is_err());
+}
+
+#[test]
+fn api_tuples() {
+    use mrusty::MrubyError;
+
+    let mruby = Mruby::new();
+
+    let value = mruby.obj_from(("a", 1, true));
+
+    assert_eq!(value.to_vec().unwrap(), vec![mruby.string("a"), mruby.fixnum(1), mruby.bool(true)]);
+    assert_eq!(value.to_tuple::<(String, i32, bool)>().unwrap(), ("a".to_owned(), 1, true));
+
+    let nested = mruby.obj_from((1, (2.5, None::<i32>), vec![3]));
+
+    assert_eq!(nested.to_tuple::<(i32, (f64, Option<i32>), Vec<i32>)>().unwrap(),
+               (1, (2.5, None), vec![3]));
+
+    let eight = mruby.run("(1..8).to_a").unwrap();
+
+    assert_eq!(eight.to_tuple::<(i32, i32, i32, i32, i32, i32, i32, i32)>().unwrap(),
+               (1, 2, 3, 4, 5, 6, 7, 8));
+
+    match mruby.run("['a', 1]").unwrap().to_tuple::<(String, i32, bool)>() {
+        Err(MrubyError::Cast(expected)) => assert_eq!(expected, "3 elements, found 2"),
+        _ => assert!(false)
+    }
+
+    match value.to_tuple::<(String, String, bool)>() {
+        Err(err) => assert_eq!(err.to_string(), "Cast error: expected String at [1]"),
+        _ => assert!(false)
+    }
+
+    assert!(mruby.fixnum(1).to_tuple::<(i32,)>().is_err());
+}
+
+#[test]
+fn api_to_string_map() {
+    use mrusty::MrubyError;
+
+    let mruby = Mruby::new();
+
+    let options = mruby.run("{ width: 3, 'height' => 4 }").unwrap().to_string_map::<i32>().unwrap();
+
+    assert_eq!(options.len(), 2);
+    assert_eq!(options["width"], 3);
+    assert_eq!(options["height"], 4);
+
+    let nested = mruby.run("{ list: [1, 2] }").unwrap().to_string_map::<Vec<i32>>().unwrap();
+
+    assert_eq!(nested["list"], vec![1, 2]);
+
+    match mruby.run("{ a: 1, [1, 2] => 2 }").unwrap().to_string_map::<i32>() {
+        Err(MrubyError::Cast(expected)) => {
+            assert_eq!(expected, "String for Hash key [1, 2]");
+        },
+        _ => assert!(false)
+    }
+
+    match mruby.run("{ a: 1, 'a' => 2 }").unwrap().to_string_map::<i32>() {
+        Err(MrubyError::Cast(expected)) => {
+            assert_eq!(expected, "unique Hash keys, found duplicate \"a\"");
+        },
+        _ => assert!(false)
+    }
+
+    match mruby.run("{ a: 'x' }").unwrap().to_string_map::<i32>() {
+        Err(err) => assert_eq!(err.to_string(), "Cast error: expected Fixnum at [:a]"),
+        _ => assert!(false)
+    }
+
+    assert!(mruby.run("[]").unwrap().to_string_map::<i32>().is_err());
+}
+
+#[test]
+fn api_nested_conversions() {
+    use std::collections::HashMap;
+
+    use mrusty::{FromValue, MrubyError, ToValue};
+
+    let mruby = Mruby::new();
+
+    let matrix = Vec::<Vec<i32>>::from_value(mruby.run("[[1, 2], [3, 4]]").unwrap()).unwrap();
+
+    assert_eq!(matrix, vec![vec![1, 2], vec![3, 4]]);
+    assert_eq!(matrix.clone().to_value(&mruby).to_s(), "[[1, 2], [3, 4]]");
+
+    let rows = mruby.run("[{ 'a' => 1 }, { 'b' => 2, 'c' => 3 }]").unwrap();
+    let maps = Vec::<HashMap<String, i32>>::from_value(rows).unwrap();
+
+    assert_eq!(maps[1]["c"], 3);
+
+    let mut map = HashMap::new();
+
+    map.insert("k".to_owned(), vec![Some(1), None]);
+
+    assert_eq!(map.to_value(&mruby).to_s(), "{\"k\"=>[1, nil]}");
+
+    match Vec::<Vec<i32>>::from_value(mruby.run("[[1, 2], ['x', 4]]").unwrap()) {
+        Err(MrubyError::Nested { path, .. }) => assert_eq!(path, vec!["1", "0"]),
+        _ => assert!(false)
+    }
+
+    match Vec::<HashMap<String, Vec<i32>>>::from_value(mruby.run("[{}, { 'a' => [1, nil] }]").unwrap()) {
+        Err(err) => assert_eq!(err.to_string(), "Cast error: expected Fixnum at [1][\"a\"][1]"),
+        _ => assert!(false)
+    }
+
+    match Vec::<(i32, String)>::from_value(mruby.run("[[1, 'a'], [2, 3]]").unwrap()) {
+        Err(err) => assert_eq!(err.to_string(), "Cast error: expected String at [1][1]"),
+        _ => assert!(false)
+    }
+
+    match Vec::<i32>::from_value(mruby.run("[1, 2 ** 40]").unwrap()) {
+        Err(MrubyError::Nested { error, .. }) => match *error {
+            MrubyError::Overflow(err) => assert_eq!(err, "1099511627776 does not fit in i32"),
+            _ => assert!(false)
+        },
+        _ => assert!(false)
+    }
+}
+
+#[test]
+fn api_array_from_iter() {
+    use mrusty::CollectMruby;
+
+    let mruby = Mruby::new();
+
+    let empty = mruby.array_from_iter(Vec::new());
+
+    assert_eq!(empty.to_vec().unwrap(), vec![]);
+
+    let evens = (0..1000).filter(|i| i % 2 == 0).map(|i| mruby.fixnum(i)).collect_into_mruby(&mruby);
+
+    assert_eq!(mruby.run("->(a) { a.size }").unwrap().call("call", vec![evens.clone()]).unwrap()
+                    .to_i32().unwrap(), 500);
+    assert_eq!(evens.iter().last().unwrap().to_i32().unwrap(), 998);
+
+    let copy = evens.iter().collect_into_mruby(&mruby);
+
+    assert_eq!(copy, evens);
+}
+
+#[test]
+fn api_array_iter() {
+    let mruby = Mruby::new();
+
+    let array = mruby.run("[1, 'two', :three]").unwrap();
+
+    let values: Vec<_> = array.iter().collect();
+
+    assert_eq!(values, array.to_vec().unwrap());
+
+    let mut count = 0;
+
+    for value in &array {
+        assert!(value.eq_ruby(&values[count]));
+
+        count += 1;
+    }
+
+    assert_eq!(count, 3);
+
+    let growing = mruby.run("$growing = [1]").unwrap();
+    let mut iter = growing.iter();
+
+    assert_eq!(iter.next().unwrap().to_i32().unwrap(), 1);
+    assert!(iter.next().is_none());
+
+    mruby.run("$growing << 2").unwrap();
+
+    assert_eq!(iter.next().unwrap().to_i32().unwrap(), 2);
+
+    assert_eq!(mruby.nil().into_iter().count(), 0);
+    assert_eq!(mruby.run("{ a: 1 }").unwrap().iter().count(), 0);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn api_serialize() {
+    use mrusty::MrValueSerializer;
+
+    let mruby = Mruby::new();
+
+    let value = mruby.run("{ 'a' => [1, -2.5, :sym, 'str'], b: { c: nil, d: false } }").unwrap();
+
+    assert_eq!(serde_json::to_value(&value).unwrap(), serde_json::json!({
+        "a": [1, -2.5, "sym", "str"],
+        "b": { "c": null, "d": false }
+    }));
+
+    unsafe {
+        let mrb = mruby.borrow().mrb;
+        let serializer = MrValueSerializer::new(mrb, MrValue::fixnum(3));
+
+        assert_eq!(serde_json::to_string(&serializer).unwrap(), "3");
+    }
+
+    assert!(serde_json::to_string(&mruby.run("{ 1 => 2 }").unwrap()).is_err());
+    assert!(serde_json::to_string(&mruby.run("Object.new").unwrap()).is_err());
+    assert!(serde_json::to_string(&mruby.run("a = []; a << a").unwrap()).is_err());
+    assert!(serde_json::to_string(&mruby.run("\"\\xff\"").unwrap()).is_err());
+}
+
+#[cfg(feature = "deserializer")]
+#[test]
+fn api_deserialize() {
+    use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+    use mrusty::Value;
+
+    struct Point {
+        x: i32,
+        y: Option<f64>
+    }
+
+    impl Serialize for Point {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut point = serializer.serialize_struct("Point", 2)?;
+
+            point.serialize_field("x", &self.x)?;
+            point.serialize_field("y", &self.y)?;
+            point.end()
+        }
+    }
+
+    let mruby = Mruby::new();
+
+    unsafe {
+        let mrb = mruby.borrow().mrb;
+
+        let point = MrValue::from_serde(mrb, &Point { x: 1, y: None }).unwrap();
+
+        assert_eq!(Value::new(mruby.clone(), point).to_s(), "{:x=>1, :y=>nil}");
+
+        let json = serde_json::json!({ "a": [1, -2.5, "s", true, null], "b": {} });
+        let value = Value::new(mruby.clone(), MrValue::from_serde(mrb, &json).unwrap());
+
+        assert_eq!(serde_json::to_value(&value).unwrap(), json);
+
+        let tuple = MrValue::from_serde(mrb, &((), 'c', u32::max_value(), Some(b"\xff"))).unwrap();
+
+        assert_eq!(Value::new(mruby.clone(), tuple).to_s(), "[nil, \"c\", 4294967295, [255]]");
+
+        assert!(MrValue::from_serde(mrb, &u64::max_value()).is_err());
+    }
+}
+
+#[cfg(feature = "time")]
+#[test]
+fn api_time() {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use mrusty::{FromValue, MrubyError};
+
+    let mruby = Mruby::new();
+
+    let time = UNIX_EPOCH + Duration::new(1_234_567_890, 123_456_789);
+    let value = mruby.time(time).unwrap();
+
+    assert_eq!(value.call("usec", vec![]).unwrap().to_i32().unwrap(), 123_456);
+    assert_eq!(value.to_system_time().unwrap(), UNIX_EPOCH + Duration::new(1_234_567_890, 123_456_000));
+
+    let before = UNIX_EPOCH - Duration::new(10, 250_000_000);
+    let value = mruby.time(before).unwrap();
+
+    assert_eq!(value.call("to_f", vec![]).unwrap().to_f64().unwrap(), -10.25);
+    assert_eq!(SystemTime::from_value(value).unwrap(), before);
+
+    match mruby.fixnum(1).to_system_time() {
+        Err(MrubyError::Cast(expected)) => assert_eq!(expected, "Time"),
+        _ => assert!(false)
+    }
+
+    mruby.run("Object.send(:remove_const, :Time)").unwrap();
+
+    match mruby.time(time) {
+        Err(MrubyError::Runtime { message, .. }) => assert!(message.contains("mruby-time")),
+        _ => assert!(false)
+    }
+}
+
+#[cfg(feature = "datetime")]
+#[test]
+fn api_datetime() {
+    use mrusty::FromValue;
+
+    let mruby = Mruby::new();
+
+    let value = mruby.run("Time.at(1_000_000_000, 500_000)").unwrap();
+    let datetime = chrono::DateTime::<chrono::Utc>::from_value(value).unwrap();
+
+    assert_eq!(datetime.to_rfc3339(), "2001-09-09T01:46:40.500+00:00");
+
+    let value = mruby.time(datetime.into()).unwrap();
+
+    assert_eq!(value.to_datetime().unwrap(), datetime);
+}
+
+#[cfg(feature = "macros")]
+#[test]
+fn api_mruby_method() {
+    use mrusty::{ClassLike, MrClass, MrubyError};
+
+    #[mrusty::mruby_method]
+    fn add(x: i32, y: Option<i32>) -> i32 {
+        x + y.unwrap_or(1)
+    }
+
+    #[mrusty::mruby_method]
+    fn greet(name: String, excited: bool) -> String {
+        format!("hi {}{}", name, if excited { "!" } else { "" })
+    }
+
+    #[mrusty::mruby_method]
+    fn nothing() {}
+
+    #[mrusty::mruby_method]
+    fn explode(message: String) {
+        panic!("{}", message);
+    }
+
+    let mruby = Mruby::new();
+    let calc = mruby.def_class("Calc");
+
+    unsafe {
+        let mrb = mruby.borrow().mrb;
+
+        MrClass::define_method(mrb, calc.class(), "add", add_mrb);
+        MrClass::define_method(mrb, calc.class(), "greet", greet_mrb);
+        MrClass::define_class_method(mrb, calc.class(), "nothing", nothing_mrb);
+        MrClass::define_class_method(mrb, calc.class(), "explode", explode_mrb);
+    }
+
+    assert_eq!(add(1, Some(2)), 3);
+
+    assert_eq!(mruby.run("Calc.new.add(2, 3)").unwrap().to_i32().unwrap(), 5);
+    assert_eq!(mruby.run("Calc.new.add(2)").unwrap().to_i32().unwrap(), 3);
+    assert_eq!(mruby.run("Calc.new.add(2, nil)").unwrap().to_i32().unwrap(), 3);
+    assert_eq!(mruby.run("Calc.new.greet('you', true)").unwrap().to_str().unwrap(), "hi you!");
+    assert!(mruby.run("Calc.nothing").unwrap().is_nil());
+
+    match mruby.run("Calc.new.add") {
+        Err(MrubyError::Runtime { class, message, .. }) => {
+            assert_eq!(class, "ArgumentError");
+            assert_eq!(message, "Cast error: expected 1..2 arguments, found 0");
+        },
+        _ => assert!(false)
+    }
+
+    match mruby.run("Calc.new.greet('you')") {
+        Err(MrubyError::Runtime { message, .. }) => {
+            assert_eq!(message, "Cast error: expected 2 arguments, found 1");
+        },
+        _ => assert!(false)
+    }
+
+    match mruby.run("Calc.new.add('a')") {
+        Err(MrubyError::Runtime { class, .. }) => assert_eq!(class, "ArgumentError"),
+        _ => assert!(false)
+    }
+
+    match mruby.run("Calc.explode('boom')") {
+        Err(MrubyError::Runtime { class, message, .. }) => {
+            assert_eq!(class, "RustPanic");
+            assert_eq!(message, "boom");
+        },
+        _ => assert!(false)
+    }
+}
+
+#[test]
+fn api_class_lookup() {
+    let mruby = Mruby::new();
+
+    mruby.run("Shape = Class.new; Helpers = Module.new").unwrap();
+
+    let old = mruby.get_class("Shape").unwrap();
+
+    assert_eq!(mruby.get_class("Shape").unwrap(), old);
+    assert_eq!(mruby.get_module("Helpers").unwrap().to_str(), "Helpers");
+
+    mruby.run("Shape = Class.new; GC.start").unwrap();
+
+    let new = mruby.get_class("Shape").unwrap();
+
+    assert!(new != old);
+    assert_eq!(new.to_value(), mruby.run("Shape").unwrap());
+
+    mruby.run("Shape = 1; Helpers = Class.new").unwrap();
+
+    assert!(mruby.get_class("Shape").is_err());
+    assert!(mruby.get_module("Helpers").is_err());
+    assert!(mruby.get_class("Undefined").is_err());
+
+    mruby.run("Temp = Class.new { def hi; :hi; end }").unwrap();
+
+    let temp = mruby.get_class("Temp").unwrap();
+
+    mruby.run("Object.send(:remove_const, :Temp); GC.start").unwrap();
+
+    assert!(mruby.get_class("Temp").is_err());
+    assert_eq!(temp.to_value().call("new", vec![]).unwrap().call("hi", vec![]).unwrap(),
+               mruby.symbol("hi"));
+
+    mruby.clear_class_cache();
+}
+
+#[test]
+fn api_ordered_hashes() {
+    use std::collections::BTreeMap;
+
+    use mrusty::{FromValue, ToValue};
+
+    let mruby = Mruby::new();
+
+    let config = mruby.run("{ 'zeta' => 1, 'alpha' => 2, 'mu' => 3 }").unwrap();
+    let pairs = config.to_pairs().unwrap();
+
+    let keys: Vec<String> = pairs.iter().map(|&(ref key, _)| key.to_str().unwrap()).collect();
+
+    assert_eq!(keys, vec!["zeta", "alpha", "mu"]);
+
+    let rebuilt = mruby.hash_from_pairs(pairs.into_iter().rev());
+
+    assert_eq!(rebuilt.to_s(), "{\"mu\"=>3, \"alpha\"=>2, \"zeta\"=>1}");
+
+    let sorted = BTreeMap::<String, i32>::from_value(config).unwrap();
+
+    assert_eq!(sorted.keys().collect::<Vec<_>>(), vec!["alpha", "mu", "zeta"]);
+    assert_eq!(sorted.to_value(&mruby).to_s(), "{\"alpha\"=>2, \"mu\"=>3, \"zeta\"=>1}");
+
+    let result = BTreeMap::<String, i32>::from_value(mruby.run("{ 'a' => 'b' }").unwrap());
+
+    match result {
+        Err(err) => assert_eq!(err.to_string(), "Cast error: expected Fixnum at [\"a\"]"),
+        _ => assert!(false)
+    }
+}
+
+#[test]
+fn api_try_from() {
+    use std::convert::{TryFrom, TryInto};
+
+    use mrusty::{MrubyError, Value};
+
+    let mruby = Mruby::new();
+
+    assert_eq!(i32::try_from(mruby.fixnum(3)).unwrap(), 3);
+    assert_eq!(i64::try_from(mruby.fixnum_i64(1 << 40)).unwrap(), 1 << 40);
+    assert_eq!(f64::try_from(mruby.float(2.5)).unwrap(), 2.5);
+    assert_eq!(String::try_from(mruby.string("hi")).unwrap(), "hi");
+    assert!(bool::try_from(mruby.bool(true)).unwrap());
+
+    let values: Vec<Value> = mruby.run("[1, 'a']").unwrap().try_into().unwrap();
+
+    assert_eq!(values, vec![mruby.fixnum(1), mruby.string("a")]);
+
+    fn sum(value: Value) -> Result<i32, MrubyError> {
+        let values: Vec<Value> = value.try_into()?;
+        let mut sum = 0;
+
+        for value in values {
+            let value: i32 = value.try_into()?;
+
+            sum += value;
+        }
+
+        Ok(sum)
+    }
+
+    assert_eq!(sum(mruby.run("[1, 2, 3]").unwrap()).unwrap(), 6);
+
+    match sum(mruby.run("[1, 'a']").unwrap()) {
+        Err(MrubyError::Cast(expected)) => assert_eq!(expected, "Fixnum"),
+        _ => assert!(false)
+    }
+}
+
+#[test]
+fn api_duration() {
+    use std::time::Duration;
+
+    use mrusty::{FromValue, MrubyError, ToValue};
+
+    let mruby = Mruby::new();
+
+    let timeout = Duration::new(1, 234_567_000);
+    let value = timeout.to_value(&mruby);
+
+    assert_eq!(value.to_f64().unwrap(), 1.234567);
+    assert_eq!(Duration::from_value(value).unwrap(), timeout);
+
+    let object = mruby.get_class("Object").unwrap();
+
+    object.def_const("INTERVAL", Duration::from_micros(250).to_value(&mruby));
+
+    let doubled = mruby.run("INTERVAL * 2").unwrap().to_duration().unwrap();
+
+    assert_eq!(doubled, Duration::from_micros(500));
+
+    match mruby.float(-0.5).to_duration() {
+        Err(MrubyError::Cast(expected)) => assert_eq!(expected, "non-negative seconds, found -0.5"),
+        _ => assert!(false)
+    }
+
+    match mruby.run("0.0 / 0.0").unwrap().to_duration() {
+        Err(MrubyError::Cast(expected)) => assert_eq!(expected, "non-negative seconds, found NaN"),
+        _ => assert!(false)
+    }
+
+    match mruby.run("1.0 / 0.0").unwrap().to_duration() {
+        Err(MrubyError::Overflow(_)) => (),
+        _ => assert!(false)
+    }
+
+    assert!(mruby.string("1").to_duration().is_err());
+}
+
+#[test]
+fn api_mrfn_generic() {
+    use mrusty::MrubyError;
+
+    let mruby = Mruby::new();
+
+    struct Cont;
+
+    mruby.def_class_for::<Cont>("Container");
+    mruby.def_class_method_for::<Cont, _>("label", mrfn!(|mruby, _slf: Value, name: String,
+                                                         count: (Option<i64>), tags: (Vec<String>)| {
+        let count = count.unwrap_or(0);
+
+        mruby.string(&format!("{} x{} [{}]", name, count, tags.join(",")))
+    }));
+    mruby.def_class_method_for::<Cont, _>("half", mrfn!(|_mruby, _slf: Value, n: i64|
+                                                        -> Result<i64, MrubyError> {
+        if n % 2 == 0 {
+            Ok(n / 2)
+        } else {
+            Err(MrubyError::Cast("even Fixnum".to_owned()))
+        }
+    }));
+    mruby.def_method(mruby.def_class("String"), "twice", mrfn!(|mruby, slf: String| {
+        mruby.string(&slf.repeat(2))
+    }));
+
+    let result = mruby.run("Container.label 'box', nil, ['a', 'b']").unwrap();
+
+    assert_eq!(result.to_str().unwrap(), "box x0 [a,b]");
+    assert_eq!(mruby.run("Container.label :box, 3, []").unwrap().to_str().unwrap(), "box x3 []");
+    assert_eq!(mruby.run("Container.half 8").unwrap().to_i32().unwrap(), 4);
+    assert_eq!(mruby.run("'ab'.twice").unwrap().to_str().unwrap(), "abab");
+
+    let result = mruby.run("
+      begin
+        Container.half 3
+      rescue ArgumentError => e
+        e.message
+      end
+    ").unwrap();
+
+    assert_eq!(result.to_str().unwrap(), "Cast error: expected even Fixnum");
+
+    match mruby.run("Container.label 1, 2, []") {
+        Err(MrubyError::Runtime { class, .. }) => assert_eq!(class, "ArgumentError"),
+        _ => assert!(false)
+    }
+}
+
+#[test]
+fn api_paths() {
+    use std::path::{Path, PathBuf};
+
+    use mrusty::{FromValue, MrubyError, ToValue};
+
+    let mruby = Mruby::new();
+
+    let path = Path::new("scripts").join("main.rb");
+    let value = path.as_path().to_value(&mruby);
+
+    assert!(value.call("end_with?", vec![mruby.string(".rb")]).unwrap().to_bool().unwrap());
+    assert_eq!(PathBuf::from_value(value).unwrap(), path);
+    assert_eq!(path.clone().to_value(&mruby).to_path().unwrap(), path);
+
+    match mruby.bytes(b"a\0b").to_path() {
+        Err(MrubyError::Cast(expected)) => assert_eq!(expected, "path without NUL bytes"),
+        _ => assert!(false)
+    }
+
+    assert!(mruby.fixnum(1).to_path().is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn api_paths_non_utf8() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    let mruby = Mruby::new();
+
+    let path = Path::new(OsStr::from_bytes(b"data/\xff.bin"));
+    let value = mruby.path(path);
+
+    assert_eq!(value.to_bytes().unwrap(), b"data/\xff.bin");
+    assert!(value.to_str().is_err());
+    assert_eq!(value.to_path().unwrap(), path);
+}
+
+#[test]
+fn api_truthiness() {
+    let mruby = Mruby::new();
+
+    struct Cont;
+
+    mruby.def_class_for::<Cont>("Container");
+
+    let truthy = mruby.run("[true, 0, 0.0, '', [], :a, Object.new, Container.new]").unwrap();
+
+    for value in truthy.to_vec().unwrap() {
+        assert!(value.is_truthy());
+        assert!(!value.is_falsey());
+        assert!(!value.is_nil());
+    }
+
+    let nil = mruby.run("nil").unwrap();
+    let falsey = mruby.run("false").unwrap();
+
+    assert!(nil.is_nil() && nil.is_falsey() && !nil.is_truthy());
+    assert!(!falsey.is_nil() && falsey.is_falsey() && !falsey.is_truthy());
+}
+
+#[test]
+fn api_class_name() {
+    let mruby = Mruby::new();
+
+    struct Cont;
+
+    mruby.def_class_for::<Cont>("Container");
+
+    let values = mruby.run("[1, 1.5, nil, true, false, :a, 'a', [], {}, Container.new, Container]")
+        .unwrap().to_vec().unwrap();
+    let names: Vec<String> = values.iter().map(|value| value.class_name()).collect();
+
+    assert_eq!(names, vec!["Fixnum", "Float", "NilClass", "TrueClass", "FalseClass", "Symbol",
+                           "String", "Array", "Hash", "Container", "Class"]);
+
+    assert_eq!(mruby.obj(Cont).class().name(), "Container");
+    assert_eq!(mruby.run("o = Object.new; def o.hi; end; o").unwrap().class_name(), "Object");
+}
+
+#[test]
+fn api_is_instance_of() {
+    let mruby = Mruby::new();
+
+    struct Cont;
+    struct Other;
+
+    mruby.def_class_for::<Cont>("Container");
+    mruby.def_class_for::<Other>("Other");
+
+    let sub = mruby.run("class SubContainer < Container; end; SubContainer.new").unwrap();
+    let cont = mruby.obj(Cont);
+
+    assert!(cont.is_instance_of::<Cont>());
+    assert!(!cont.is_instance_of::<Other>());
+    assert!(sub.is_instance_of::<Cont>());
+    assert!(sub.is_a(mruby.get_class("Container").unwrap()));
+    assert!(!mruby.run("Container").unwrap().is_instance_of::<Cont>());
+    assert!(!mruby.nil().is_instance_of::<Cont>());
+    assert!(!mruby.fixnum(1).is_instance_of::<String>());
+}
+
+#[test]
+fn api_range_cast() {
+    use mrusty::MrubyError;
+
+    let mruby = Mruby::new();
+
+    let result = mruby.run("1.0..2.5").unwrap();
+
+    match result.to_range() {
+        Err(MrubyError::Cast(expected)) => assert_eq!(expected, "Range of Fixnum, found Float"),
+        _ => assert!(false)
+    }
+
+    assert!(mruby.fixnum(1).to_range().is_err());
+}
+
+#[test]
+fn api_range_i32() {
+    use mrusty::MrubyError;
+
+    let mruby = Mruby::new();
+
+    mruby.run("class Range; def sum; inject(0) { |sum, x| sum + x }; end; end").unwrap();
+
+    let range = mruby.int_range(1..5);
+
+    assert_eq!(range.call("sum", vec![]).unwrap().to_i32().unwrap(), 10);
+    assert_eq!(range.to_range_i32().unwrap(), 1..5);
+
+    let result = mruby.run("1..(2 ** 40)").unwrap();
+
+    match result.to_range_i32() {
+        Err(MrubyError::Overflow(err)) => {
+            assert_eq!(err, "Inclusive(1..=1099511627776) does not fit in Range<i32>");
+        },
+        _ => assert!(false)
+    }
+}
+
 #[test]
 fn api_mrb_sizeof() {
     {